@@ -8,14 +8,14 @@ pub fn main() {
     loop {
         engine.update().unwrap();
 
-        for gamepad in engine.gamepads() {
+        for (id, gamepad) in engine.iter() {
             for (key, button) in gamepad.buttons() {
                 if button.is_just_pressed() {
-                    println!("Just Pressed: {:?}", key);
+                    println!("Gamepad {} Just Pressed: {:?}", id, key);
                 }
             }
 
-            for (_, joystick) in gamepad.joysticks() {
+            for joystick in gamepad.joysticks().values() {
                 println!("{:?}", joystick);
             }
         }
@@ -0,0 +1,577 @@
+use crate::device::HatDirection;
+use crate::types::*;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// An input an action can be bound to
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Binding {
+    Button(Button),
+    /// Active while the axis is past the threshold, a negative threshold is crossed by
+    /// moving the axis below it and a positive threshold by moving the axis above it
+    AxisThreshold {
+        axis: Axis,
+        threshold: f32,
+    },
+    /// Active while the joystick is pushed in the direction, each component of the
+    /// direction must be past the threshold
+    StickDirection {
+        joystick: Joystick,
+        direction: HatDirection,
+        threshold: f32,
+    },
+    /// Active while all of the buttons are pressed
+    Chord(Vec<Button>),
+}
+impl Binding {
+    pub fn is_active(&self, gamepad: &GamepadState) -> bool {
+        match self {
+            Binding::Button(button) => gamepad.is_pressed(*button),
+            Binding::Chord(buttons) => {
+                !buttons.is_empty() && buttons.iter().all(|button| gamepad.is_pressed(*button))
+            }
+            _ => self.is_past_threshold(gamepad, false),
+        }
+    }
+
+    /// Whether the binding was active on the previous update
+    pub fn was_active(&self, gamepad: &GamepadState) -> bool {
+        let was_pressed = |button: &Button| {
+            gamepad
+                .buttons()
+                .get(button)
+                .is_some_and(|button_state| button_state.was_pressed)
+        };
+
+        match self {
+            Binding::Button(button) => was_pressed(button),
+            Binding::Chord(buttons) => !buttons.is_empty() && buttons.iter().all(was_pressed),
+            _ => self.is_past_threshold(gamepad, true),
+        }
+    }
+
+    /// How far the input is pushed from 0.0 to 1.0, a chord is as far pushed as its
+    /// least pushed button
+    pub fn value(&self, gamepad: &GamepadState) -> f32 {
+        match self {
+            Binding::Button(button) => gamepad.button_value(*button),
+            Binding::AxisThreshold { axis, threshold } => {
+                (gamepad.axis(*axis) * threshold.signum()).clamp(0.0, 1.0)
+            }
+            Binding::StickDirection {
+                joystick,
+                direction,
+                ..
+            } => {
+                let value = gamepad.joystick(*joystick);
+                let direction = direction.to_axes();
+                let length =
+                    ((direction.0 * direction.0 + direction.1 * direction.1) as f32).sqrt();
+                if length == 0.0 {
+                    return 0.0;
+                }
+
+                ((value.0 * direction.0 as f32 + value.1 * direction.1 as f32) / length)
+                    .clamp(0.0, 1.0)
+            }
+            Binding::Chord(buttons) => buttons
+                .iter()
+                .map(|button| gamepad.button_value(*button))
+                .reduce(f32::min)
+                .unwrap_or(0.0),
+        }
+    }
+
+    fn is_past_threshold(&self, gamepad: &GamepadState, previous: bool) -> bool {
+        let axis = |axis: Axis| {
+            if previous {
+                gamepad.previous_axis(axis)
+            } else {
+                gamepad.axis(axis)
+            }
+        };
+
+        match self {
+            Binding::AxisThreshold { axis: a, threshold } => {
+                let value = axis(*a);
+                if *threshold < 0.0 {
+                    value <= *threshold
+                } else {
+                    value >= *threshold && value > 0.0
+                }
+            }
+            Binding::StickDirection {
+                joystick,
+                direction,
+                threshold,
+            } => {
+                let (x_axis, y_axis) = match joystick {
+                    Joystick::Left => (Axis::LeftStickX, Axis::LeftStickY),
+                    Joystick::Right => (Axis::RightStickX, Axis::RightStickY),
+                };
+                let value = (axis(x_axis), axis(y_axis));
+                let past = |value: f32, direction: i8| {
+                    direction == 0 || value * direction as f32 >= threshold.max(f32::EPSILON)
+                };
+
+                let direction = direction.to_axes();
+                direction != (0, 0) && past(value.0, direction.0) && past(value.1, direction.1)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Binds the actions of a game to the inputs that trigger them, so inputs can be
+/// remapped without changing any game code.
+///
+/// An action is active while any of its bindings is active. Actions are read from the
+/// state of a single gamepad, or for a player through `GamepadEngine::action_pressed` and
+/// its siblings.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "A: serde::Serialize",
+        deserialize = "A: Eq + Hash + serde::Deserialize<'de>"
+    ))
+)]
+pub struct ActionMap<A> {
+    bindings: HashMap<A, Vec<Binding>>,
+}
+impl<A: Eq + Hash> ActionMap<A> {
+    pub fn new() -> Self {
+        ActionMap {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Adds a binding to the action, keeping its other bindings
+    pub fn bind(&mut self, action: A, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes a binding from the action, returns false if the action had no such binding
+    pub fn unbind(&mut self, action: &A, binding: &Binding) -> bool {
+        match self.bindings.get_mut(action) {
+            Some(bindings) => {
+                let count = bindings.len();
+                bindings.retain(|b| b != binding);
+
+                bindings.len() != count
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces all bindings of the action
+    pub fn set_bindings(&mut self, action: A, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    /// Removes all bindings of the action
+    pub fn clear_bindings(&mut self, action: &A) {
+        self.bindings.remove(action);
+    }
+
+    pub fn bindings(&self, action: &A) -> &[Binding] {
+        self.bindings.get(action).map_or(&[], |bindings| bindings)
+    }
+
+    /// Every action that has bindings
+    pub fn actions(&self) -> impl Iterator<Item = &A> {
+        self.bindings.keys()
+    }
+
+    pub fn pressed(&self, action: &A, gamepad: &GamepadState) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.is_active(gamepad))
+    }
+
+    fn was_pressed(&self, action: &A, gamepad: &GamepadState) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.was_active(gamepad))
+    }
+
+    /// Whether the action became active on the last update
+    pub fn just_pressed(&self, action: &A, gamepad: &GamepadState) -> bool {
+        self.pressed(action, gamepad) && !self.was_pressed(action, gamepad)
+    }
+
+    /// Whether the action stopped being active on the last update
+    pub fn just_released(&self, action: &A, gamepad: &GamepadState) -> bool {
+        !self.pressed(action, gamepad) && self.was_pressed(action, gamepad)
+    }
+
+    /// The value of the action from 0.0 to 1.0, the highest value of its bindings
+    pub fn value(&self, action: &A, gamepad: &GamepadState) -> f32 {
+        self.bindings(action)
+            .iter()
+            .map(|binding| binding.value(gamepad))
+            .fold(0.0, f32::max)
+    }
+}
+impl<A: Eq + Hash> Default for ActionMap<A> {
+    fn default() -> Self {
+        ActionMap::new()
+    }
+}
+
+/// Waits for the next meaningful input and turns it into a `Binding`, such as for
+/// "press a button for Jump" in an options menu.
+///
+/// Inputs that were already held when the capture began are ignored, a button must be
+/// pressed anew and an axis must move `axis_threshold` away from the value it rested at.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BindingCapture {
+    gamepad: Option<GamepadId>,
+    axis_threshold: f32,
+    rest: HashMap<GamepadId, HashMap<Axis, f32>>,
+}
+impl BindingCapture {
+    pub const DEFAULT_AXIS_THRESHOLD: f32 = 0.5;
+
+    /// The axes that can be captured, the D-pad is captured through its buttons instead
+    const AXES: [Axis; 6] = [
+        Axis::LeftStickX,
+        Axis::LeftStickY,
+        Axis::RightStickX,
+        Axis::RightStickY,
+        Axis::LeftTrigger,
+        Axis::RightTrigger,
+    ];
+
+    /// Creates a capture listening to a single gamepad, or to every gamepad for `None`
+    pub fn new(gamepad: Option<GamepadId>) -> Self {
+        BindingCapture {
+            gamepad,
+            axis_threshold: BindingCapture::DEFAULT_AXIS_THRESHOLD,
+            rest: HashMap::new(),
+        }
+    }
+
+    /// Sets how far an axis must move from its rest value to be captured
+    pub fn with_axis_threshold(mut self, axis_threshold: f32) -> Self {
+        self.axis_threshold = axis_threshold.clamp(f32::EPSILON, 1.0);
+        self
+    }
+
+    /// The gamepad the capture listens to, `None` if it listens to every gamepad
+    pub fn gamepad(&self) -> Option<GamepadId> {
+        self.gamepad
+    }
+
+    pub fn axis_threshold(&self) -> f32 {
+        self.axis_threshold
+    }
+
+    /// Checks the gamepad's state for a new input, returns the binding for it if found.
+    ///
+    /// The first state sampled for a gamepad gives the rest values of its axes.
+    pub fn sample(&mut self, id: GamepadId, gamepad: &GamepadState) -> Option<Binding> {
+        if self.gamepad.is_some_and(|listening| listening != id) {
+            return None;
+        }
+
+        let rest = match self.rest.get(&id) {
+            Some(rest) => rest,
+            None => {
+                let rest = BindingCapture::AXES
+                    .iter()
+                    .map(|axis| (*axis, gamepad.axis(*axis)))
+                    .collect();
+                self.rest.insert(id, rest);
+                return None;
+            }
+        };
+
+        let mut buttons = gamepad
+            .buttons()
+            .iter()
+            .filter(|(_, button_state)| button_state.is_just_pressed())
+            .map(|(button, _)| *button)
+            .collect::<Vec<Button>>();
+        // Buttons pressed on the same update are picked in a fixed order, named buttons
+        // win over buttons passed through by their code
+        buttons.sort_by_key(Button::order);
+        if let Some(button) = buttons.first() {
+            return Some(Binding::Button(*button));
+        }
+
+        for axis in BindingCapture::AXES.iter() {
+            let rest = rest.get(axis).copied().unwrap_or(0.0);
+            let offset = gamepad.axis(*axis) - rest;
+            let previous_offset = gamepad.previous_axis(*axis) - rest;
+
+            if offset.abs() >= self.axis_threshold && previous_offset.abs() < self.axis_threshold {
+                return Some(Binding::AxisThreshold {
+                    axis: *axis,
+                    threshold: (rest + self.axis_threshold.copysign(offset)).clamp(-1.0, 1.0),
+                });
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Action {
+        Jump,
+        Walk,
+        Menu,
+    }
+
+    fn with_button(
+        mut gamepad: GamepadState,
+        button: Button,
+        is_pressed: bool,
+        was_pressed: bool,
+    ) -> GamepadState {
+        gamepad
+            .buttons
+            .insert(button, ButtonState::new(is_pressed, was_pressed));
+        gamepad
+    }
+
+    fn with_stick(
+        mut gamepad: GamepadState,
+        value: (f32, f32),
+        previous: (f32, f32),
+    ) -> GamepadState {
+        gamepad
+            .joysticks
+            .insert(Joystick::Left, JoystickState::new((0, 0), value));
+        gamepad.previous_axes.insert(Axis::LeftStickX, previous.0);
+        gamepad.previous_axes.insert(Axis::LeftStickY, previous.1);
+        gamepad
+    }
+
+    #[test]
+    fn button_bindings_follow_the_button() {
+        let binding = Binding::Button(Button::South);
+        let gamepad = with_button(GamepadState::new(), Button::South, true, false);
+
+        assert!(binding.is_active(&gamepad));
+        assert!(!binding.was_active(&gamepad));
+        assert_eq!(binding.value(&gamepad), 1.0);
+        assert!(!binding.is_active(&GamepadState::new()));
+    }
+
+    #[test]
+    fn axis_thresholds_are_crossed_in_their_direction() {
+        let left = Binding::AxisThreshold {
+            axis: Axis::LeftStickX,
+            threshold: -0.5,
+        };
+        let right = Binding::AxisThreshold {
+            axis: Axis::LeftStickX,
+            threshold: 0.5,
+        };
+
+        let gamepad = with_stick(GamepadState::new(), (-0.75, 0.0), (0.6, 0.0));
+        assert!(left.is_active(&gamepad));
+        assert!(!left.was_active(&gamepad));
+        assert_eq!(left.value(&gamepad), 0.75);
+        assert!(!right.is_active(&gamepad));
+        assert!(right.was_active(&gamepad));
+        assert_eq!(right.value(&gamepad), 0.0);
+
+        // A zero threshold still needs the axis to move
+        let any = Binding::AxisThreshold {
+            axis: Axis::LeftStickX,
+            threshold: 0.0,
+        };
+        assert!(!any.is_active(&GamepadState::new()));
+    }
+
+    #[test]
+    fn stick_directions_need_every_component() {
+        let north_east = Binding::StickDirection {
+            joystick: Joystick::Left,
+            direction: HatDirection::NorthEast,
+            threshold: 0.5,
+        };
+
+        let gamepad = with_stick(GamepadState::new(), (0.7, 0.7), (0.7, 0.2));
+        assert!(north_east.is_active(&gamepad));
+        assert!(!north_east.was_active(&gamepad));
+        assert!((north_east.value(&gamepad) - 0.7 * 2f32.sqrt()).abs() < 1e-4);
+
+        let gamepad = with_stick(GamepadState::new(), (0.9, 0.0), (0.0, 0.0));
+        assert!(!north_east.is_active(&gamepad));
+
+        let centered = Binding::StickDirection {
+            joystick: Joystick::Left,
+            direction: HatDirection::Centered,
+            threshold: 0.5,
+        };
+        assert!(!centered.is_active(&gamepad));
+        assert_eq!(centered.value(&gamepad), 0.0);
+    }
+
+    #[test]
+    fn chords_need_every_button() {
+        let chord = Binding::Chord(vec![Button::LeftShoulder, Button::RightShoulder]);
+
+        let gamepad = with_button(GamepadState::new(), Button::LeftShoulder, true, true);
+        assert!(!chord.is_active(&gamepad));
+        assert_eq!(chord.value(&gamepad), 0.0);
+
+        let gamepad = with_button(gamepad, Button::RightShoulder, true, false);
+        assert!(chord.is_active(&gamepad));
+        assert!(!chord.was_active(&gamepad));
+        assert_eq!(chord.value(&gamepad), 1.0);
+
+        let empty = Binding::Chord(Vec::new());
+        assert!(!empty.is_active(&gamepad));
+        assert_eq!(empty.value(&gamepad), 0.0);
+    }
+
+    #[test]
+    fn actions_are_active_through_any_binding() {
+        let mut actions = ActionMap::new();
+        actions.bind(Action::Jump, Binding::Button(Button::South));
+        actions.bind(Action::Jump, Binding::Button(Button::East));
+        actions.bind(Action::Jump, Binding::Button(Button::East));
+        actions.bind(
+            Action::Walk,
+            Binding::AxisThreshold {
+                axis: Axis::LeftStickY,
+                threshold: 0.25,
+            },
+        );
+        assert_eq!(actions.bindings(&Action::Jump).len(), 2);
+        assert!(actions.bindings(&Action::Menu).is_empty());
+
+        let gamepad = with_button(GamepadState::new(), Button::South, false, true);
+        let gamepad = with_button(gamepad, Button::East, true, false);
+        let gamepad = with_stick(gamepad, (0.0, 0.5), (0.0, 0.0));
+
+        // South was released on the same update East was pressed
+        assert!(actions.pressed(&Action::Jump, &gamepad));
+        assert!(!actions.just_pressed(&Action::Jump, &gamepad));
+        assert!(!actions.just_released(&Action::Jump, &gamepad));
+        assert!(actions.just_pressed(&Action::Walk, &gamepad));
+        assert_eq!(actions.value(&Action::Walk, &gamepad), 0.5);
+        assert!(!actions.pressed(&Action::Menu, &gamepad));
+
+        assert!(actions.unbind(&Action::Jump, &Binding::Button(Button::East)));
+        assert!(!actions.unbind(&Action::Jump, &Binding::Button(Button::East)));
+        assert!(!actions.pressed(&Action::Jump, &gamepad));
+        assert!(actions.just_released(&Action::Jump, &gamepad));
+
+        actions.set_bindings(Action::Menu, vec![Binding::Button(Button::Start)]);
+        actions.clear_bindings(&Action::Walk);
+        let mut bound = actions.actions().copied().collect::<Vec<Action>>();
+        bound.sort_by_key(|action| *action as u8);
+        assert_eq!(bound, vec![Action::Jump, Action::Menu]);
+    }
+
+    fn with_trigger(mut gamepad: GamepadState, value: f32, previous: f32) -> GamepadState {
+        gamepad.buttons.insert(
+            Button::LeftTrigger,
+            ButtonState::analog(value, 0, 1.0, false),
+        );
+        gamepad.previous_axes.insert(Axis::LeftTrigger, previous);
+        gamepad
+    }
+
+    #[test]
+    fn capture_picks_buttons_in_a_fixed_order() {
+        let id = GamepadId::new(0);
+        let mut capture = BindingCapture::new(None);
+        assert_eq!(capture.sample(id, &GamepadState::new()), None);
+
+        let mut gamepad = GamepadState::new();
+        for button in [
+            Button::Other(7),
+            Button::Other(3),
+            Button::Start,
+            Button::East,
+        ] {
+            gamepad = with_button(gamepad, button, true, false);
+        }
+        // Held buttons are ignored
+        gamepad = with_button(gamepad, Button::DPadNorth, true, true);
+        assert_eq!(
+            capture.sample(id, &gamepad),
+            Some(Binding::Button(Button::East))
+        );
+
+        let mut gamepad = GamepadState::new();
+        for button in [Button::Other(7), Button::Other(3)] {
+            gamepad = with_button(gamepad, button, true, false);
+        }
+        assert_eq!(
+            capture.sample(id, &gamepad),
+            Some(Binding::Button(Button::Other(3)))
+        );
+    }
+
+    #[test]
+    fn capture_measures_axes_from_their_rest_value() {
+        let id = GamepadId::new(0);
+        let mut capture = BindingCapture::new(Some(id)).with_axis_threshold(0.5);
+
+        // The trigger rests half pressed, such as a trigger held when the capture began
+        capture.sample(id, &with_trigger(GamepadState::new(), 0.5, 0.5));
+        assert_eq!(
+            capture.sample(id, &with_trigger(GamepadState::new(), 0.9, 0.5)),
+            None
+        );
+        // Axes are only captured on the update they cross the threshold
+        assert_eq!(
+            capture.sample(id, &with_trigger(GamepadState::new(), 0.0, 0.0)),
+            None
+        );
+        assert_eq!(
+            capture.sample(id, &with_trigger(GamepadState::new(), 0.0, 0.1)),
+            Some(Binding::AxisThreshold {
+                axis: Axis::LeftTrigger,
+                threshold: 0.0,
+            })
+        );
+
+        assert_eq!(
+            capture.sample(
+                id,
+                &with_stick(GamepadState::new(), (-0.6, 0.0), (0.0, 0.0))
+            ),
+            Some(Binding::AxisThreshold {
+                axis: Axis::LeftStickX,
+                threshold: -0.5,
+            })
+        );
+    }
+
+    #[test]
+    fn capture_listens_to_its_gamepad() {
+        let (listening, other) = (GamepadId::new(0), GamepadId::new(1));
+        let mut capture = BindingCapture::new(Some(listening));
+        let pressed = with_button(GamepadState::new(), Button::South, true, false);
+
+        assert_eq!(capture.gamepad(), Some(listening));
+        capture.sample(other, &GamepadState::new());
+        assert_eq!(capture.sample(other, &pressed), None);
+
+        capture.sample(listening, &GamepadState::new());
+        assert_eq!(
+            capture.sample(listening, &pressed),
+            Some(Binding::Button(Button::South))
+        );
+    }
+}
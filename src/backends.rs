@@ -1,22 +1,350 @@
 #[cfg(not(any(target_family = "wasm", target_os = "android")))]
 pub(crate) mod gilrs;
 
-#[cfg(any(target_family = "wasm", target_os = "android"))]
 pub(crate) mod dummy;
 
+#[cfg(target_os = "linux")]
+pub(crate) mod evdev;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod joydev;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod hidraw;
+
+#[cfg(windows)]
+pub(crate) mod xinput;
+
+// The button indices of the Gamepad API are kept until the browser bindings are written
+#[cfg(target_family = "wasm")]
+#[allow(dead_code)]
+pub(crate) mod wasm;
+
+use crate::device::*;
+use crate::sony::{TriggerEffect, TriggerSide};
 use crate::types::*;
 
-pub(crate) trait GamepadEngineBackend {
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use std::collections::BTreeSet;
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::time::{Instant, SystemTime};
+
+/// How often the Linux backends try again to open device nodes that could not be opened,
+/// udev may fix the permissions of a node only after it appeared
+#[cfg(target_os = "linux")]
+const DEVICE_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A directory of device nodes, such as `/dev/input`, watched by the Linux backends for
+/// nodes to open
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub(crate) struct DeviceDirectory {
+    path: PathBuf,
+    /// The start of the name of every node the backend reads, such as `event`
+    prefix: &'static str,
+    modified: Option<SystemTime>,
+    /// Nodes that could not be opened, usually for lack of permission
+    unopened: BTreeSet<PathBuf>,
+    last_retry: Option<Instant>,
+}
+#[cfg(target_os = "linux")]
+impl DeviceDirectory {
+    pub(crate) fn new(path: PathBuf, prefix: &'static str) -> Self {
+        DeviceDirectory {
+            path,
+            prefix,
+            modified: None,
+            unopened: BTreeSet::new(),
+            last_retry: None,
+        }
+    }
+
+    /// Passes every node to `open` when the contents of the directory changed since the
+    /// last scan, otherwise only the nodes that failed to open once `DEVICE_RETRY_INTERVAL`
+    /// has passed.
+    ///
+    /// `open` skips the nodes that are already open. Nodes it fails to open for any reason
+    /// other than being gone are tried again.
+    pub(crate) fn scan<F: FnMut(&Path) -> io::Result<()>>(&mut self, mut open: F) {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let retry = !self.unopened.is_empty()
+            && self.last_retry.map_or(true, |last_retry| {
+                last_retry.elapsed() >= DEVICE_RETRY_INTERVAL
+            });
+
+        let paths = if modified.is_none() || modified != self.modified {
+            self.modified = modified;
+            self.unopened.clear();
+
+            let prefix = self.prefix;
+            let mut paths = match std::fs::read_dir(&self.path) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.file_name()
+                            .and_then(|name| name.to_str())
+                            .is_some_and(|name| name.starts_with(prefix))
+                    })
+                    .collect::<Vec<PathBuf>>(),
+                Err(_) => return,
+            };
+            paths.sort();
+
+            paths
+        } else if retry {
+            std::mem::take(&mut self.unopened).into_iter().collect()
+        } else {
+            return;
+        };
+        self.last_retry = Some(Instant::now());
+
+        for path in paths {
+            if let Err(e) = open(&path) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    self.unopened.insert(path);
+                }
+            }
+        }
+    }
+}
+
+/// A source of gamepad input, see `GamepadEngine::with_backend`.
+///
+/// Only `gamepads` has to be implemented, every other method has a default that reports
+/// nothing or returns an `Unsupported` error. Backends report the raw state of each gamepad,
+/// mappings, deadzones, calibration and input events are handled by the engine.
+pub trait GamepadEngineBackend {
     /// This should be called every frame.
     fn update(&mut self) -> Result<(), GamepadError> {
         Ok(())
     }
 
     /// This should be called to retrieve all of the events since the last update.
+    ///
+    /// Backends only need to report `Connected` and `Disconnected` events, input events are
+    /// derived by the engine from the gamepad states.
     fn poll_events(&mut self) -> Vec<GamepadEvent> {
         Vec::new()
     }
 
-    fn gamepads(&self) -> &Vec<GamepadState>;
-    fn gamepads_mut(&mut self) -> &mut Vec<GamepadState>;
+    /// Describes the device behind the gamepad, `None` if the backend knows nothing about it.
+    fn gamepad_info(&self, _id: GamepadId) -> Option<&GamepadInfo> {
+        None
+    }
+
+    /// The current state of every connected gamepad.
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState>;
+
+    /// Connected devices read as plain axes, buttons and hats, a device may also be
+    /// reported as a gamepad under the same id.
+    fn joystick_devices(&self) -> &BTreeMap<GamepadId, JoystickDevice> {
+        static NO_DEVICES: BTreeMap<GamepadId, JoystickDevice> = BTreeMap::new();
+        &NO_DEVICES
+    }
+
+    /// Rumbles the gamepad for the given duration, replacing the rumble that is playing.
+    ///
+    /// `strong` and `weak` are the magnitudes of the low and high frequency motors,
+    /// ranging from 0.0 to 1.0.
+    fn set_rumble(
+        &mut self,
+        _id: GamepadId,
+        _strong: f32,
+        _weak: f32,
+        _duration: Duration,
+    ) -> Result<(), GamepadError> {
+        Err(GamepadError::new(
+            "Rumble is not supported by this backend",
+            ErrorType::Unsupported,
+        ))
+    }
+
+    /// Stops the rumble that is playing.
+    fn stop_rumble(&mut self, _id: GamepadId) -> Result<(), GamepadError> {
+        Err(GamepadError::new(
+            "Rumble is not supported by this backend",
+            ErrorType::Unsupported,
+        ))
+    }
+
+    /// Sets the color of the gamepad's light, such as the light bar of Playstation gamepads.
+    fn set_light(&mut self, _id: GamepadId, _rgb: (u8, u8, u8)) -> Result<(), GamepadError> {
+        Err(GamepadError::new(
+            "Lights are not supported by this backend",
+            ErrorType::Unsupported,
+        ))
+    }
+
+    /// Sets the resistance of an adaptive trigger.
+    fn set_trigger_effect(
+        &mut self,
+        _id: GamepadId,
+        _side: TriggerSide,
+        _effect: TriggerEffect,
+    ) -> Result<(), GamepadError> {
+        Err(GamepadError::new(
+            "Trigger effects are not supported by this backend",
+            ErrorType::Unsupported,
+        ))
+    }
+}
+
+/// The built in backends, used to choose the input source at runtime with
+/// `GamepadEngine::with_backend_kind`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BackendKind {
+    /// The backend `GamepadEngine::new` uses on this platform
+    #[default]
+    Default,
+    Dummy,
+    Gilrs,
+    XInput,
+    Evdev,
+    Joydev,
+    Hidraw,
+    Wasm,
+}
+impl BackendKind {
+    pub(crate) const NAMES: &'static [(BackendKind, &'static str)] = &[
+        (BackendKind::Default, "default"),
+        (BackendKind::Dummy, "dummy"),
+        (BackendKind::Gilrs, "gilrs"),
+        (BackendKind::XInput, "xinput"),
+        (BackendKind::Evdev, "evdev"),
+        (BackendKind::Joydev, "joydev"),
+        (BackendKind::Hidraw, "hidraw"),
+        (BackendKind::Wasm, "wasm"),
+    ];
+
+    /// The backends that are built for this platform
+    pub fn available() -> Vec<BackendKind> {
+        BackendKind::NAMES
+            .iter()
+            .map(|(kind, _)| *kind)
+            .filter(|kind| kind.is_available())
+            .collect()
+    }
+
+    /// Whether the backend is built for this platform
+    pub fn is_available(&self) -> bool {
+        match self {
+            BackendKind::Default | BackendKind::Dummy => true,
+            BackendKind::Gilrs => cfg!(not(any(target_family = "wasm", target_os = "android"))),
+            BackendKind::XInput => cfg!(windows),
+            BackendKind::Evdev | BackendKind::Joydev | BackendKind::Hidraw => {
+                cfg!(target_os = "linux")
+            }
+            BackendKind::Wasm => cfg!(target_family = "wasm"),
+        }
+    }
+
+    pub(crate) fn create(
+        self,
+    ) -> Result<Box<dyn GamepadEngineBackend + Send + Sync>, GamepadError> {
+        match self {
+            #[cfg(not(any(target_family = "wasm", target_os = "android")))]
+            BackendKind::Default | BackendKind::Gilrs => Ok(Box::new(gilrs::GilrsBackend::new()?)),
+            #[cfg(any(target_family = "wasm", target_os = "android"))]
+            BackendKind::Default => Ok(Box::new(dummy::DummyBackend::new())),
+            BackendKind::Dummy => Ok(Box::new(dummy::DummyBackend::new())),
+            #[cfg(windows)]
+            BackendKind::XInput => Ok(Box::new(xinput::XInputBackend::new())),
+            #[cfg(target_os = "linux")]
+            BackendKind::Evdev => Ok(Box::new(evdev::EvdevBackend::new())),
+            #[cfg(target_os = "linux")]
+            BackendKind::Joydev => Ok(Box::new(joydev::JoydevBackend::new())),
+            #[cfg(target_os = "linux")]
+            BackendKind::Hidraw => Ok(Box::new(hidraw::HidrawBackend::new())),
+            #[cfg(target_family = "wasm")]
+            BackendKind::Wasm => Ok(Box::new(wasm::WasmBackend::new())),
+            #[allow(unreachable_patterns)]
+            kind => Err(GamepadError::new(
+                format!("The {} backend is not available on this platform", kind),
+                ErrorType::Unsupported,
+            )),
+        }
+    }
+}
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = BackendKind::NAMES
+            .iter()
+            .find(|(kind, _)| kind == self)
+            .map_or("", |(_, name)| name);
+
+        write!(f, "{}", name)
+    }
+}
+impl FromStr for BackendKind {
+    type Err = GamepadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+
+        BackendKind::NAMES
+            .iter()
+            .find(|(_, kind_name)| *kind_name == name)
+            .map(|(kind, _)| *kind)
+            .ok_or_else(|| {
+                GamepadError::new(
+                    format!("Invalid backend name: {}", s),
+                    ErrorType::InvalidBackendName,
+                )
+            })
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_pass_new_and_failed_nodes() {
+        let path = std::env::temp_dir().join(format!("gamepad-scan-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        for name in ["event1", "event0", "js0"] {
+            std::fs::write(path.join(name), []).unwrap();
+        }
+
+        let mut directory = DeviceDirectory::new(path.clone(), "event");
+        let mut scanned = Vec::new();
+        directory.scan(|node| {
+            scanned.push(node.to_path_buf());
+            match node.ends_with("event1") {
+                true => Err(io::Error::from(io::ErrorKind::PermissionDenied)),
+                false => Ok(()),
+            }
+        });
+        assert_eq!(scanned, vec![path.join("event0"), path.join("event1")]);
+
+        // Nothing changed and the failed node is only retried after a while
+        scanned.clear();
+        directory.scan(|node| {
+            scanned.push(node.to_path_buf());
+            Ok(())
+        });
+        assert!(scanned.is_empty());
+
+        directory.last_retry = Some(Instant::now() - DEVICE_RETRY_INTERVAL);
+        directory.scan(|node| {
+            scanned.push(node.to_path_buf());
+            Ok(())
+        });
+        assert_eq!(scanned, vec![path.join("event1")]);
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
 }
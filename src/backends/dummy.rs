@@ -1,21 +1,133 @@
 use crate::backends::GamepadEngineBackend;
-use crate::types::GamepadState;
+use crate::device::*;
+use crate::types::*;
 
-pub(crate) struct DummyBackend {
-    gamepads: Vec<GamepadState>,
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A rumble request received by the `DummyBackend`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RumbleCommand {
+    Set {
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    },
+    Stop {
+        id: GamepadId,
+    },
+}
+
+/// A handle to the rumble requests recorded by a `DummyBackend`, it stays usable after the
+/// backend is moved into a `GamepadEngine`.
+#[derive(Clone, Debug, Default)]
+pub struct RumbleRecording {
+    commands: Arc<Mutex<Vec<RumbleCommand>>>,
+}
+impl RumbleRecording {
+    /// Every rumble request received so far, oldest first
+    pub fn commands(&self) -> Vec<RumbleCommand> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.commands.lock().unwrap().clear();
+    }
+
+    fn push(&self, command: RumbleCommand) {
+        self.commands.lock().unwrap().push(command);
+    }
+}
+
+/// A backend without any devices, gamepads can be added by hand and every output
+/// request is recorded so it can be inspected without hardware.
+pub struct DummyBackend {
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    joystick_devices: BTreeMap<GamepadId, JoystickDevice>,
+    rumble_recording: RumbleRecording,
 }
 impl DummyBackend {
     pub fn new() -> Self {
         DummyBackend {
-            gamepads: Vec::new(),
+            gamepads: BTreeMap::new(),
+            joystick_devices: BTreeMap::new(),
+            rumble_recording: RumbleRecording::default(),
         }
     }
+
+    pub fn gamepads_mut(&mut self) -> &mut BTreeMap<GamepadId, GamepadState> {
+        &mut self.gamepads
+    }
+
+    pub fn joystick_devices_mut(&mut self) -> &mut BTreeMap<GamepadId, JoystickDevice> {
+        &mut self.joystick_devices
+    }
+
+    /// Every rumble request received so far, oldest first
+    pub fn rumble_commands(&self) -> Vec<RumbleCommand> {
+        self.rumble_recording.commands()
+    }
+
+    pub fn clear_rumble_commands(&mut self) {
+        self.rumble_recording.clear();
+    }
+
+    /// A handle to the recorded rumble requests, keep it before handing the backend to
+    /// `GamepadEngine::with_backend` to inspect the requests made through the engine
+    pub fn rumble_recording(&self) -> RumbleRecording {
+        self.rumble_recording.clone()
+    }
+
+    fn check_connected(&self, id: GamepadId) -> Result<(), GamepadError> {
+        if self.gamepads.contains_key(&id) {
+            Ok(())
+        } else {
+            Err(GamepadError::new(
+                format!("Gamepad {} is not connected", id),
+                ErrorType::GamepadNotFound { id },
+            ))
+        }
+    }
+}
+impl Default for DummyBackend {
+    fn default() -> Self {
+        DummyBackend::new()
+    }
 }
 impl GamepadEngineBackend for DummyBackend {
-    fn gamepads(&self) -> &Vec<GamepadState> {
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
         &self.gamepads
     }
-    fn gamepads_mut(&mut self) -> &mut Vec<GamepadState> {
-        &mut self.gamepads
+
+    fn joystick_devices(&self) -> &BTreeMap<GamepadId, JoystickDevice> {
+        &self.joystick_devices
+    }
+
+    fn set_rumble(
+        &mut self,
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    ) -> Result<(), GamepadError> {
+        self.check_connected(id)?;
+        self.rumble_recording.push(RumbleCommand::Set {
+            id,
+            strong,
+            weak,
+            duration,
+        });
+
+        Ok(())
+    }
+
+    fn stop_rumble(&mut self, id: GamepadId) -> Result<(), GamepadError> {
+        self.check_connected(id)?;
+        self.rumble_recording.push(RumbleCommand::Stop { id });
+
+        Ok(())
     }
 }
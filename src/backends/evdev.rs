@@ -0,0 +1,1254 @@
+use crate::backends::{DeviceDirectory, GamepadEngineBackend};
+use crate::device::*;
+use crate::types::*;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The directory the kernel creates evdev device nodes in
+pub const DEFAULT_INPUT_DIRECTORY: &str = "/dev/input";
+/// Describes the nodes of `DEFAULT_INPUT_DIRECTORY` without opening them
+const SYSFS_INPUT_DIRECTORY: &str = "/sys/class/input";
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+
+const SYN_REPORT: u16 = 0x00;
+const SYN_DROPPED: u16 = 0x03;
+
+const KEY_MAX: u16 = 0x2ff;
+const BTN_JOYSTICK: u16 = 0x120;
+const BTN_GAMEPAD: u16 = 0x130;
+const BTN_DIGI: u16 = 0x140;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_Z: u16 = 0x02;
+const ABS_RX: u16 = 0x03;
+const ABS_RY: u16 = 0x04;
+const ABS_RZ: u16 = 0x05;
+const ABS_THROTTLE: u16 = 0x06;
+const ABS_GAS: u16 = 0x09;
+const ABS_BRAKE: u16 = 0x0a;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+const ABS_HAT3Y: u16 = 0x17;
+const ABS_MAX: u16 = 0x3f;
+
+const BTN_TL2: u16 = 0x138;
+const BTN_TR2: u16 = 0x139;
+
+fn get_evdev_to_gamepad_buttons() -> Vec<(u16, Button)> {
+    vec![
+        (0x130, Button::South),         // BTN_SOUTH
+        (0x131, Button::East),          // BTN_EAST
+        (0x132, Button::C),             // BTN_C
+        (0x133, Button::North),         // BTN_NORTH
+        (0x134, Button::West),          // BTN_WEST
+        (0x135, Button::Z),             // BTN_Z
+        (0x136, Button::LeftShoulder),  // BTN_TL
+        (0x137, Button::RightShoulder), // BTN_TR
+        (BTN_TL2, Button::LeftTrigger),
+        (BTN_TR2, Button::RightTrigger),
+        (0x13a, Button::Select),     // BTN_SELECT
+        (0x13b, Button::Start),      // BTN_START
+        (0x13c, Button::Guide),      // BTN_MODE
+        (0x13d, Button::LeftStick),  // BTN_THUMBL
+        (0x13e, Button::RightStick), // BTN_THUMBR
+        (0x220, Button::DPadNorth),  // BTN_DPAD_UP
+        (0x221, Button::DPadSouth),  // BTN_DPAD_DOWN
+        (0x222, Button::DPadWest),   // BTN_DPAD_LEFT
+        (0x223, Button::DPadEast),   // BTN_DPAD_RIGHT
+    ]
+}
+
+/// The size of an `input_event` record on this platform, its timestamp is made of two `long`s
+const INPUT_EVENT_SIZE: usize = 2 * std::mem::size_of::<libc::c_long>() + 8;
+
+/// A single `input_event` record as read from an evdev device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub(crate) time: Duration,
+    pub(crate) kind: u16,
+    pub(crate) code: u16,
+    pub(crate) value: i32,
+}
+impl InputEvent {
+    pub fn new(time: Duration, kind: u16, code: u16, value: i32) -> Self {
+        InputEvent {
+            time,
+            kind,
+            code,
+            value,
+        }
+    }
+
+    /// Reads a record in the layout of this platform, `None` if there are too few bytes
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < INPUT_EVENT_SIZE {
+            return None;
+        }
+
+        // The record is a C struct, every field is in the byte order of this platform
+        let long = std::mem::size_of::<libc::c_long>();
+        let read_long = |offset: usize| {
+            let mut value = [0; std::mem::size_of::<libc::c_long>()];
+            value.copy_from_slice(&bytes[offset..offset + long]);
+            libc::c_long::from_ne_bytes(value)
+        };
+        let seconds = read_long(0).max(0) as u64;
+        let microseconds = read_long(long).clamp(0, 999_999) as u32;
+
+        let rest = &bytes[2 * long..];
+        Some(InputEvent {
+            time: Duration::new(seconds, microseconds * 1000),
+            kind: u16::from_ne_bytes([rest[0], rest[1]]),
+            code: u16::from_ne_bytes([rest[2], rest[3]]),
+            value: i32::from_ne_bytes([rest[4], rest[5], rest[6], rest[7]]),
+        })
+    }
+
+    /// Writes the record in the layout of this platform, such as to build a recording
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(INPUT_EVENT_SIZE);
+
+        bytes.extend_from_slice(&(self.time.as_secs() as libc::c_long).to_ne_bytes());
+        bytes.extend_from_slice(&(self.time.subsec_micros() as libc::c_long).to_ne_bytes());
+        bytes.extend_from_slice(&self.kind.to_ne_bytes());
+        bytes.extend_from_slice(&self.code.to_ne_bytes());
+        bytes.extend_from_slice(&self.value.to_ne_bytes());
+
+        bytes
+    }
+
+    /// When the kernel received the event
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// The event type, such as `EV_KEY` (1) or `EV_ABS` (3)
+    pub fn kind(&self) -> u16 {
+        self.kind
+    }
+
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+}
+
+/// The range of an absolute axis as declared by the device, mirroring `input_absinfo`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AbsInfo {
+    pub(crate) value: i32,
+    pub(crate) minimum: i32,
+    pub(crate) maximum: i32,
+    pub(crate) fuzz: i32,
+    pub(crate) flat: i32,
+    pub(crate) resolution: i32,
+}
+impl AbsInfo {
+    pub fn new(minimum: i32, maximum: i32) -> Self {
+        AbsInfo {
+            value: 0,
+            minimum,
+            maximum,
+            fuzz: 0,
+            flat: 0,
+            resolution: 0,
+        }
+    }
+
+    /// The range assumed for an axis the device never declared, as the xpad driver
+    /// reports them
+    fn assumed(code: u16) -> Self {
+        match code {
+            ABS_HAT0X..=ABS_HAT3Y => AbsInfo::new(-1, 1),
+            ABS_Z | ABS_RZ | ABS_GAS | ABS_BRAKE => AbsInfo::new(0, 255),
+            _ => AbsInfo::new(i16::MIN as i32, i16::MAX as i32),
+        }
+    }
+
+    /// The value of the axis when it was queried
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    pub fn minimum(&self) -> i32 {
+        self.minimum
+    }
+
+    pub fn maximum(&self) -> i32 {
+        self.maximum
+    }
+
+    pub fn fuzz(&self) -> i32 {
+        self.fuzz
+    }
+
+    pub fn flat(&self) -> i32 {
+        self.flat
+    }
+
+    pub fn resolution(&self) -> i32 {
+        self.resolution
+    }
+
+    fn device_axis(&self, value: i32) -> DeviceAxis {
+        DeviceAxis::new(value, self.minimum, self.maximum)
+    }
+}
+
+/// Describes an evdev device: its name, GUID and the key and axis codes it supports.
+///
+/// Real devices are described by the kernel. Devices read from a byte stream are
+/// described by hand, codes that show up in the stream without being described are added
+/// as they are seen, axes with an assumed range.
+#[derive(Clone, Debug, Default)]
+pub struct EvdevDeviceInfo {
+    pub(crate) name: String,
+    pub(crate) guid: Guid,
+    pub(crate) keys: BTreeSet<u16>,
+    pub(crate) axes: BTreeMap<u16, AbsInfo>,
+    /// Whether the capabilities came from the kernel, learned devices count as gamepads
+    described: bool,
+}
+impl EvdevDeviceInfo {
+    pub fn new<T: Into<String>>(name: T, guid: Guid) -> Self {
+        EvdevDeviceInfo {
+            name: name.into(),
+            guid,
+            keys: BTreeSet::new(),
+            axes: BTreeMap::new(),
+            described: false,
+        }
+    }
+
+    pub fn with_key(mut self, code: u16) -> Self {
+        self.keys.insert(code);
+        self
+    }
+
+    pub fn with_axis(mut self, code: u16, abs_info: AbsInfo) -> Self {
+        self.axes.insert(code, abs_info);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn guid(&self) -> Guid {
+        self.guid
+    }
+
+    pub fn keys(&self) -> &BTreeSet<u16> {
+        &self.keys
+    }
+
+    pub fn axes(&self) -> &BTreeMap<u16, AbsInfo> {
+        &self.axes
+    }
+
+    /// Queries the kernel for the description of an opened device node, `None` if the
+    /// file is not an evdev device
+    fn query(file: &File) -> Option<Self> {
+        let fd = file.as_raw_fd();
+
+        let mut id = [0u16; 4];
+        ioctl_read(fd, b'E', 0x02, &mut id).ok()?;
+
+        let mut name = [0u8; 256];
+        let name = match ioctl_read(fd, b'E', 0x06, &mut name) {
+            Ok(_) => {
+                let len = name
+                    .iter()
+                    .position(|byte| *byte == 0)
+                    .unwrap_or(name.len());
+                String::from_utf8_lossy(&name[..len]).into_owned()
+            }
+            Err(_) => String::new(),
+        };
+
+        let mut info = EvdevDeviceInfo::new(name, sdl_guid(id[0], id[1], id[2], id[3]));
+        info.described = true;
+
+        let mut keys = [0u8; (KEY_MAX as usize + 1) / 8];
+        if ioctl_read(fd, b'E', 0x20 + EV_KEY as u8, &mut keys).is_ok() {
+            info.keys = set_bits(&keys).collect();
+        }
+
+        let mut axes = [0u8; (ABS_MAX as usize + 1) / 8];
+        if ioctl_read(fd, b'E', 0x20 + EV_ABS as u8, &mut axes).is_ok() {
+            for code in set_bits(&axes) {
+                let mut abs_info = [0i32; 6];
+                if ioctl_read(fd, b'E', 0x40 + code as u8, &mut abs_info).is_ok() {
+                    let [value, minimum, maximum, fuzz, flat, resolution] = abs_info;
+                    info.axes.insert(
+                        code,
+                        AbsInfo {
+                            value,
+                            minimum,
+                            maximum,
+                            fuzz,
+                            flat,
+                            resolution,
+                        },
+                    );
+                }
+            }
+        }
+
+        Some(info)
+    }
+
+    /// The buttons in the order SDL assigns their indices, joystick and gamepad buttons first
+    pub(super) fn sdl_button_codes(&self) -> impl Iterator<Item = u16> + '_ {
+        (BTN_JOYSTICK..=KEY_MAX)
+            .chain(0..BTN_JOYSTICK)
+            .filter(move |code| self.keys.contains(code))
+    }
+
+    /// The axes in the order SDL assigns their indices, hat axes are read as hats instead
+    pub(super) fn sdl_axis_codes(&self) -> impl Iterator<Item = u16> + '_ {
+        self.axes
+            .keys()
+            .copied()
+            .filter(|code| !(ABS_HAT0X..=ABS_HAT3Y).contains(code))
+    }
+
+    /// The x axis of every hat in the order SDL assigns their indices, the y axis follows it
+    pub(super) fn sdl_hat_codes(&self) -> impl Iterator<Item = u16> + '_ {
+        (ABS_HAT0X..=ABS_HAT3Y).step_by(2).filter(move |x_code| {
+            self.axes.contains_key(x_code) || self.axes.contains_key(&(x_code + 1))
+        })
+    }
+
+    /// Devices with the buttons of a gamepad
+    fn is_gamepad(&self) -> bool {
+        !self.described || self.keys.contains(&BTN_GAMEPAD)
+    }
+
+    /// Devices with joystick buttons or the axes of wheels and pedals, which leaves out
+    /// keyboards, mice and touchpads
+    fn is_joystick(&self) -> bool {
+        !self.described
+            || self.keys.range(BTN_JOYSTICK..BTN_DIGI).next().is_some()
+            || self.axes.range(ABS_THROTTLE..=ABS_BRAKE).next().is_some()
+    }
+}
+
+/// Builds the GUID SDL uses for an evdev device from its `input_id`
+pub(super) fn sdl_guid(bustype: u16, vendor: u16, product: u16, version: u16) -> Guid {
+    let mut bytes = [0; 16];
+    for (i, word) in [bustype, vendor, product, version].iter().enumerate() {
+        bytes[i * 4..i * 4 + 2].copy_from_slice(&word.to_le_bytes());
+    }
+
+    Guid(bytes)
+}
+
+/// Describes the device node with the GUID, preferring the node with the same name.
+///
+/// Backends that read devices through another library use this to learn every element
+/// a device declares. Nodes are matched through the ids sysfs exposes so only the
+/// matching nodes are opened.
+pub(super) fn find_device_info(guid: Guid, name: &str) -> Option<EvdevDeviceInfo> {
+    let mut nodes = std::fs::read_dir(SYSFS_INPUT_DIRECTORY)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_event_node(path))
+        .filter(|path| sysfs_guid(path) == Some(guid))
+        .collect::<Vec<PathBuf>>();
+    nodes.sort();
+
+    let mut found = None;
+    for node in nodes {
+        let path = match node.file_name() {
+            Some(file_name) => Path::new(DEFAULT_INPUT_DIRECTORY).join(file_name),
+            None => continue,
+        };
+        let info = match File::open(&path)
+            .ok()
+            .and_then(|file| EvdevDeviceInfo::query(&file))
+        {
+            Some(info) if info.guid == guid => info,
+            _ => continue,
+        };
+
+        if info.name == name {
+            return Some(info);
+        }
+        found.get_or_insert(info);
+    }
+
+    found
+}
+
+/// The GUID of an event node from the `input_id` sysfs exposes under `device/id`
+fn sysfs_guid(node: &Path) -> Option<Guid> {
+    let id = |field: &str| {
+        let value = std::fs::read_to_string(node.join("device/id").join(field)).ok()?;
+        u16::from_str_radix(value.trim(), 16).ok()
+    };
+
+    Some(sdl_guid(
+        id("bustype")?,
+        id("vendor")?,
+        id("product")?,
+        id("version")?,
+    ))
+}
+
+fn is_event_node(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("event"))
+}
+
+fn set_bits(bits: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    (0..bits.len() * 8)
+        .filter(move |bit| bits[bit / 8] & (1 << (bit % 8)) != 0)
+        .map(|bit| bit as u16)
+}
+
+/// The `_IOC` request layout: the number, kind, size and direction packed from the
+/// lowest bit up. MIPS, PowerPC and SPARC use fewer size bits and other direction values
+/// than the generic layout.
+#[cfg(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc",
+    target_arch = "sparc64"
+))]
+mod ioc {
+    pub const SIZE_BITS: u32 = 13;
+    pub const READ: libc::c_ulong = 2;
+    pub const WRITE: libc::c_ulong = 4;
+}
+#[cfg(not(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc",
+    target_arch = "sparc64"
+)))]
+mod ioc {
+    pub const SIZE_BITS: u32 = 14;
+    pub const READ: libc::c_ulong = 2;
+    pub const WRITE: libc::c_ulong = 1;
+}
+
+/// Plain integers the kernel may fill with any bit pattern
+pub(super) trait IoctlData: Copy {}
+impl IoctlData for u8 {}
+impl IoctlData for u16 {}
+impl IoctlData for i32 {}
+
+/// Builds an `_IOC(direction, kind, nr, size)` request, `None` if the size doesn't fit
+fn ioc_request(direction: libc::c_ulong, kind: u8, nr: u8, size: usize) -> Option<libc::c_ulong> {
+    let size = size as libc::c_ulong;
+    if size >= 1 << ioc::SIZE_BITS {
+        return None;
+    }
+
+    Some(
+        direction << (16 + ioc::SIZE_BITS)
+            | size << 16
+            | (kind as libc::c_ulong) << 8
+            | nr as libc::c_ulong,
+    )
+}
+
+/// Sends a request that reads into the buffer, the size in the request is the size of
+/// the buffer so the kernel can't write past its end
+fn ioctl<T: IoctlData>(
+    fd: libc::c_int,
+    direction: libc::c_ulong,
+    kind: u8,
+    nr: u8,
+    buffer: &mut [T],
+) -> io::Result<()> {
+    let request = ioc_request(direction, kind, nr, std::mem::size_of_val(buffer))
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let result = unsafe { libc::ioctl(fd, request as _, buffer.as_mut_ptr()) };
+
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Sends an `_IOR` request, such as `EVIOCGNAME`, that fills the buffer
+pub(super) fn ioctl_read<T: IoctlData>(
+    fd: libc::c_int,
+    kind: u8,
+    nr: u8,
+    buffer: &mut [T],
+) -> io::Result<()> {
+    ioctl(fd, ioc::READ, kind, nr, buffer)
+}
+
+/// Sends an `_IOWR` request, such as `HIDIOCGFEATURE`, that reads the buffer and fills it
+pub(super) fn ioctl_read_write<T: IoctlData>(
+    fd: libc::c_int,
+    kind: u8,
+    nr: u8,
+    buffer: &mut [T],
+) -> io::Result<()> {
+    ioctl(fd, ioc::READ | ioc::WRITE, kind, nr, buffer)
+}
+
+enum EventSource {
+    Device(File),
+    Stream(Box<dyn Read + Send + Sync>),
+}
+impl EventSource {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self {
+            EventSource::Device(file) => file.read(buffer),
+            EventSource::Stream(reader) => reader.read(buffer),
+        }
+    }
+}
+
+struct EvdevDevice {
+    path: Option<PathBuf>,
+    source: EventSource,
+    info: EvdevDeviceInfo,
+    buffer: Vec<u8>,
+    keys: HashMap<u16, bool>,
+    axes: HashMap<u16, i32>,
+    pending: Vec<InputEvent>,
+    /// Events are dropped after `SYN_DROPPED` until the next `SYN_REPORT`
+    dropped: bool,
+}
+impl EvdevDevice {
+    fn new(path: Option<PathBuf>, source: EventSource, info: EvdevDeviceInfo) -> Self {
+        let axes = info
+            .axes
+            .iter()
+            .map(|(code, abs_info)| (*code, abs_info.value))
+            .collect();
+
+        let mut device = EvdevDevice {
+            path,
+            source,
+            info,
+            buffer: Vec::new(),
+            keys: HashMap::new(),
+            axes,
+            pending: Vec::new(),
+            dropped: false,
+        };
+        device.sync_keys();
+
+        device
+    }
+
+    /// Reads every event available, returns false once the device is gone
+    fn read_events(&mut self) -> bool {
+        let mut chunk = [0u8; INPUT_EVENT_SIZE * 64];
+
+        loop {
+            match self.source.read(&mut chunk) {
+                // Streams and recordings have no more events for now
+                Ok(0) => return true,
+                Ok(len) => {
+                    self.buffer.extend_from_slice(&chunk[..len]);
+                    self.process_buffer();
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    fn process_buffer(&mut self) {
+        let complete = self.buffer.len() / INPUT_EVENT_SIZE * INPUT_EVENT_SIZE;
+        let bytes = self.buffer.drain(..complete).collect::<Vec<u8>>();
+
+        for record in bytes.chunks(INPUT_EVENT_SIZE) {
+            if let Some(event) = InputEvent::from_bytes(record) {
+                self.process_event(event);
+            }
+        }
+    }
+
+    fn process_event(&mut self, event: InputEvent) {
+        match (event.kind, event.code) {
+            (EV_SYN, SYN_DROPPED) => {
+                self.pending.clear();
+                self.dropped = true;
+            }
+            (EV_SYN, SYN_REPORT) if self.dropped => {
+                self.dropped = false;
+                self.sync();
+            }
+            (EV_SYN, SYN_REPORT) => {
+                for event in self.pending.drain(..) {
+                    match event.kind {
+                        EV_KEY => {
+                            self.keys.insert(event.code, event.value != 0);
+                        }
+                        _ => {
+                            self.axes.insert(event.code, event.value);
+                        }
+                    }
+                }
+            }
+            (EV_KEY, code) if !self.dropped && code <= KEY_MAX => {
+                self.info.keys.insert(code);
+                self.pending.push(event);
+            }
+            (EV_ABS, code) if !self.dropped && code <= ABS_MAX => {
+                self.info
+                    .axes
+                    .entry(code)
+                    .or_insert_with(|| AbsInfo::assumed(code));
+                self.pending.push(event);
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads the whole state from the kernel after events were dropped
+    fn sync(&mut self) {
+        self.sync_keys();
+
+        if let EventSource::Device(file) = &self.source {
+            let fd = file.as_raw_fd();
+            for (code, value) in self.axes.iter_mut() {
+                let mut abs_info = [0i32; 6];
+                if ioctl_read(fd, b'E', 0x40 + *code as u8, &mut abs_info).is_ok() {
+                    *value = abs_info[0];
+                }
+            }
+        }
+    }
+
+    fn sync_keys(&mut self) {
+        if let EventSource::Device(file) = &self.source {
+            let mut keys = [0u8; (KEY_MAX as usize + 1) / 8];
+            if ioctl_read(file.as_raw_fd(), b'E', 0x18, &mut keys).is_ok() {
+                let pressed = set_bits(&keys).collect::<BTreeSet<u16>>();
+                for code in self.info.keys.iter() {
+                    self.keys.insert(*code, pressed.contains(code));
+                }
+            }
+        }
+    }
+
+    fn is_pressed(&self, code: u16) -> bool {
+        self.keys.get(&code).copied().unwrap_or(false)
+    }
+
+    fn device_axis(&self, code: u16) -> Option<DeviceAxis> {
+        let abs_info = self.info.axes.get(&code)?;
+        let value = self.axes.get(&code).copied().unwrap_or(abs_info.value);
+
+        Some(abs_info.device_axis(value))
+    }
+
+    /// The value of the axis from -1.0 to 1.0, 0.0 if the device has no such axis
+    fn axis_value(&self, code: u16) -> f32 {
+        self.device_axis(code).map_or(0.0, |axis| axis.value())
+    }
+
+    /// The value of the axis from 0.0 to 1.0, `None` if the device has no such axis
+    fn trigger_value(&self, code: u16) -> Option<f32> {
+        self.device_axis(code).map(|axis| axis.unipolar_value())
+    }
+
+    /// The raw stick value, kept as is if the declared range fits into 16 bits and scaled
+    /// into 16 bits otherwise. The y-axis is flipped, evdev reports down as positive.
+    fn joystick_state(&self, x_code: u16, y_code: u16) -> Option<JoystickState> {
+        let x = self.device_axis(x_code)?;
+        let y = self.device_axis(y_code)?;
+        let (min, max) = x.range().min(y.range());
+
+        let flip = |axis: DeviceAxis| {
+            let (min, max) = axis.range();
+            DeviceAxis::new(min + max - axis.raw_value(), min, max)
+        };
+        let y = flip(y);
+
+        let fits = min >= i16::MIN as i32 && max <= i16::MAX as i32;
+        if fits && x.range() == y.range() {
+            return Some(JoystickState::from_raw(
+                (x.raw_value() as i16, y.raw_value() as i16),
+                (min as i16, max as i16),
+            ));
+        }
+
+        let to_raw = |axis: DeviceAxis| (axis.value() * i16::MAX as f32).round() as i16;
+        Some(JoystickState::from_raw(
+            (to_raw(x), to_raw(y)),
+            (-i16::MAX, i16::MAX),
+        ))
+    }
+
+    fn gamepad_state(&self) -> GamepadState {
+        let mut gamepad_state = GamepadState::new();
+
+        for code in self.info.keys.iter() {
+            gamepad_state.raw_buttons.insert(
+                *code as u32,
+                ButtonState::new(self.is_pressed(*code), false),
+            );
+        }
+        for code in self.info.axes.keys() {
+            gamepad_state
+                .raw_axes
+                .insert(*code as u32, self.axis_value(*code));
+        }
+
+        let buttons = get_evdev_to_gamepad_buttons();
+        for (code, button) in buttons.iter() {
+            if self.info.keys.contains(code) {
+                gamepad_state
+                    .buttons
+                    .insert(*button, ButtonState::new(self.is_pressed(*code), false));
+            }
+        }
+
+        // Buttons without a name in the joystick and gamepad ranges are passed through
+        for code in self.info.keys.range(BTN_JOYSTICK..BTN_DIGI) {
+            if !buttons.iter().any(|(named, _)| named == code) {
+                gamepad_state.buttons.insert(
+                    Button::Other(*code as u32),
+                    ButtonState::new(self.is_pressed(*code), false),
+                );
+            }
+        }
+
+        // The D-pad is reported either as buttons or as the first hat
+        if self.info.axes.contains_key(&ABS_HAT0X) || self.info.axes.contains_key(&ABS_HAT0Y) {
+            let hat = HatDirection::from_axes(
+                self.axis_value(ABS_HAT0X),
+                -self.axis_value(ABS_HAT0Y),
+                0.5,
+            );
+            for (button, pressed) in [
+                (Button::DPadNorth, hat.is_north()),
+                (Button::DPadSouth, hat.is_south()),
+                (Button::DPadWest, hat.is_west()),
+                (Button::DPadEast, hat.is_east()),
+            ] {
+                let pressed = pressed || gamepad_state.is_pressed(button);
+                gamepad_state
+                    .buttons
+                    .insert(button, ButtonState::new(pressed, false));
+            }
+        }
+
+        // Analog triggers, combined with the digital trigger buttons some pads also report
+        for (codes, key, button) in [
+            ([ABS_Z, ABS_BRAKE], BTN_TL2, Button::LeftTrigger),
+            ([ABS_RZ, ABS_GAS], BTN_TR2, Button::RightTrigger),
+        ] {
+            if let Some(value) = codes.iter().find_map(|code| self.trigger_value(*code)) {
+                let value = value.max(self.is_pressed(key) as u8 as f32);
+                gamepad_state.buttons.insert(
+                    button,
+                    ButtonState::analog(
+                        value,
+                        (value * u8::MAX as f32).round() as u16,
+                        ButtonState::DEFAULT_PRESS_THRESHOLD,
+                        false,
+                    ),
+                );
+            }
+        }
+
+        for (joystick, x_code, y_code) in [
+            (Joystick::Left, ABS_X, ABS_Y),
+            (Joystick::Right, ABS_RX, ABS_RY),
+        ] {
+            if let Some(joystick_state) = self.joystick_state(x_code, y_code) {
+                gamepad_state.joysticks.insert(joystick, joystick_state);
+            }
+        }
+
+        gamepad_state
+    }
+
+    /// The device with its elements in the order SDL enumerates them, so SDL mappings
+    /// refer to the same elements
+    fn joystick_device(&self) -> JoystickDevice {
+        let buttons = self
+            .info
+            .sdl_button_codes()
+            .map(|code| ButtonState::new(self.is_pressed(code), false))
+            .collect();
+
+        let axes = self
+            .info
+            .sdl_axis_codes()
+            .filter_map(|code| self.device_axis(code))
+            .collect();
+
+        let hats = self
+            .info
+            .sdl_hat_codes()
+            .map(|x_code| {
+                HatDirection::from_axes(self.axis_value(x_code), -self.axis_value(x_code + 1), 0.5)
+            })
+            .collect();
+
+        JoystickDevice::new(axes, buttons, hats)
+    }
+}
+
+/// Reads gamepads and joysticks straight from the Linux evdev interface.
+///
+/// Device nodes are found in a directory, `/dev/input` unless another one is given,
+/// which is scanned again whenever its contents change. Devices can also be read from any
+/// byte stream of `input_event` records, such as a recording.
+pub struct EvdevBackend {
+    directory: Option<DeviceDirectory>,
+    devices: BTreeMap<GamepadId, EvdevDevice>,
+    next_id: usize,
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    gamepad_infos: BTreeMap<GamepadId, GamepadInfo>,
+    joystick_devices: BTreeMap<GamepadId, JoystickDevice>,
+    events: Vec<GamepadEvent>,
+}
+impl EvdevBackend {
+    /// Creates a backend reading the device nodes in `/dev/input`
+    pub fn new() -> Self {
+        EvdevBackend::with_directory(DEFAULT_INPUT_DIRECTORY)
+    }
+
+    /// Creates a backend reading the `event*` files in the directory
+    pub fn with_directory<P: AsRef<Path>>(directory: P) -> Self {
+        let mut backend = EvdevBackend::from_streams();
+        backend.directory = Some(DeviceDirectory::new(
+            directory.as_ref().to_path_buf(),
+            "event",
+        ));
+
+        backend
+    }
+
+    /// Creates a backend without any devices, they are added through `add_stream`
+    pub fn from_streams() -> Self {
+        EvdevBackend {
+            directory: None,
+            devices: BTreeMap::new(),
+            next_id: 0,
+            gamepads: BTreeMap::new(),
+            gamepad_infos: BTreeMap::new(),
+            joystick_devices: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds a device reading `input_event` records from the stream, it is connected on the
+    /// next update. Reads from the stream should not block, the end of the stream
+    /// leaves the device connected.
+    pub fn add_stream<R: Read + Send + Sync + 'static>(
+        &mut self,
+        stream: R,
+        info: EvdevDeviceInfo,
+    ) -> GamepadId {
+        self.add_device(EvdevDevice::new(
+            None,
+            EventSource::Stream(Box::new(stream)),
+            info,
+        ))
+    }
+
+    /// Disconnects the device on the next update
+    pub fn remove_device(&mut self, id: GamepadId) -> bool {
+        match self.devices.remove(&id) {
+            Some(device) => {
+                self.events.push(GamepadEvent::Disconnected {
+                    id,
+                    name: device.info.name,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn add_device(&mut self, device: EvdevDevice) -> GamepadId {
+        let id = GamepadId(self.next_id);
+        self.next_id += 1;
+
+        self.events.push(GamepadEvent::Connected {
+            id,
+            name: device.info.name.clone(),
+        });
+        self.devices.insert(id, device);
+
+        id
+    }
+
+    /// Opens the joysticks that appeared in the directory, see `DeviceDirectory::scan`
+    fn scan_directory(&mut self) {
+        if let Some(mut directory) = self.directory.take() {
+            directory.scan(|path| self.open_node(path));
+            self.directory = Some(directory);
+        }
+    }
+
+    fn open_node(&mut self, path: &Path) -> io::Result<()> {
+        if self
+            .devices
+            .values()
+            .any(|device| device.path.as_deref() == Some(path))
+        {
+            return Ok(());
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+
+        let info = match EvdevDeviceInfo::query(&file) {
+            Some(info) if !info.is_joystick() => return Ok(()),
+            Some(info) => info,
+            // Recordings are files without a kernel description
+            None => EvdevDeviceInfo::new(
+                path.file_name().unwrap_or_default().to_string_lossy(),
+                Guid::default(),
+            ),
+        };
+
+        self.add_device(EvdevDevice::new(
+            Some(path.to_path_buf()),
+            EventSource::Device(file),
+            info,
+        ));
+
+        Ok(())
+    }
+}
+impl Default for EvdevBackend {
+    fn default() -> Self {
+        EvdevBackend::new()
+    }
+}
+impl GamepadEngineBackend for EvdevBackend {
+    fn update(&mut self) -> Result<(), GamepadError> {
+        self.scan_directory();
+
+        let gone = self
+            .devices
+            .iter_mut()
+            .filter_map(|(id, device)| (!device.read_events()).then_some(*id))
+            .collect::<Vec<GamepadId>>();
+        for id in gone {
+            self.remove_device(id);
+        }
+
+        self.gamepads.clear();
+        self.gamepad_infos.clear();
+        self.joystick_devices.clear();
+        for (id, device) in self.devices.iter() {
+            if device.info.is_gamepad() {
+                self.gamepads.insert(*id, device.gamepad_state());
+            }
+            self.joystick_devices.insert(*id, device.joystick_device());
+            self.gamepad_infos.insert(
+                *id,
+                GamepadInfo::new(
+                    device.info.name.clone(),
+                    device.info.guid,
+                    PowerInfo::Unknown,
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn poll_events(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn gamepad_info(&self, id: GamepadId) -> Option<&GamepadInfo> {
+        self.gamepad_infos.get(&id)
+    }
+
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
+    }
+
+    fn joystick_devices(&self) -> &BTreeMap<GamepadId, JoystickDevice> {
+        &self.joystick_devices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    const BTN_SOUTH: u16 = 0x130;
+    const BTN_EAST: u16 = 0x131;
+    const BTN_TRIGGER: u16 = 0x120;
+
+    fn stream(events: &[(u16, u16, i32)]) -> Cursor<Vec<u8>> {
+        let bytes = events
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (kind, code, value))| {
+                InputEvent::new(Duration::from_millis(i as u64), *kind, *code, *value).to_bytes()
+            })
+            .collect();
+
+        Cursor::new(bytes)
+    }
+
+    fn abs_info(minimum: i32, maximum: i32, value: i32) -> AbsInfo {
+        AbsInfo {
+            value,
+            ..AbsInfo::new(minimum, maximum)
+        }
+    }
+
+    fn gamepad_info() -> EvdevDeviceInfo {
+        let mut info = EvdevDeviceInfo::new("Pad", sdl_guid(0x03, 0x045e, 0x028e, 0x0114))
+            .with_key(BTN_SOUTH)
+            .with_key(BTN_EAST)
+            .with_key(BTN_TRIGGER)
+            .with_axis(ABS_X, abs_info(0, 255, 128))
+            .with_axis(ABS_Y, abs_info(0, 255, 128))
+            .with_axis(ABS_Z, abs_info(0, 1023, 0))
+            .with_axis(ABS_HAT0X, abs_info(-1, 1, 0))
+            .with_axis(ABS_HAT0Y, abs_info(-1, 1, 0));
+        info.described = true;
+
+        info
+    }
+
+    fn read(info: EvdevDeviceInfo, events: &[(u16, u16, i32)]) -> (EvdevBackend, GamepadId) {
+        let mut backend = EvdevBackend::from_streams();
+        let id = backend.add_stream(stream(events), info);
+        backend.update().unwrap();
+
+        (backend, id)
+    }
+
+    #[test]
+    fn input_event_round_trip() {
+        let event = InputEvent::new(Duration::new(1_700_000_000, 123_000), EV_ABS, ABS_Y, -42);
+        let bytes = event.to_bytes();
+
+        assert_eq!(bytes.len(), INPUT_EVENT_SIZE);
+        assert_eq!(InputEvent::from_bytes(&bytes), Some(event));
+        assert_eq!(InputEvent::from_bytes(&bytes[..INPUT_EVENT_SIZE - 1]), None);
+
+        // Every field is laid out like the kernel struct, in native byte order
+        let long = std::mem::size_of::<libc::c_long>();
+        assert_eq!(
+            &bytes[..long],
+            &(1_700_000_000 as libc::c_long).to_ne_bytes()[..]
+        );
+        assert_eq!(
+            &bytes[long..2 * long],
+            &(123 as libc::c_long).to_ne_bytes()[..]
+        );
+        assert_eq!(&bytes[2 * long..2 * long + 2], &EV_ABS.to_ne_bytes()[..]);
+        assert_eq!(&bytes[2 * long + 2..2 * long + 4], &ABS_Y.to_ne_bytes()[..]);
+        assert_eq!(&bytes[2 * long + 4..], &(-42i32).to_ne_bytes()[..]);
+    }
+
+    #[test]
+    fn stream_events_update_gamepad() {
+        let (backend, id) = read(
+            gamepad_info(),
+            &[
+                (EV_KEY, BTN_SOUTH, 1),
+                (EV_KEY, BTN_TRIGGER, 1),
+                (EV_ABS, ABS_X, 255),
+                (EV_ABS, ABS_Y, 0),
+                (EV_ABS, ABS_Z, 1023),
+                (EV_ABS, ABS_HAT0X, -1),
+                (EV_SYN, SYN_REPORT, 0),
+                // Events after the last report are not applied yet
+                (EV_KEY, BTN_EAST, 1),
+            ],
+        );
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(gamepad.is_pressed(Button::South));
+        assert!(!gamepad.is_pressed(Button::East));
+        assert!(gamepad.is_pressed(Button::Other(BTN_TRIGGER as u32)));
+        assert!(gamepad.is_pressed(Button::DPadWest));
+        assert_eq!(gamepad.button_value(Button::LeftTrigger), 1.0);
+
+        // evdev reports down as positive, up is positive here
+        assert_eq!(gamepad.joystick_raw(Joystick::Left), (255, 255));
+        assert_eq!(gamepad.joystick(Joystick::Left), (1.0, 1.0));
+
+        assert_eq!(
+            backend.gamepad_info(id).unwrap().guid(),
+            sdl_guid(0x03, 0x045e, 0x028e, 0x0114)
+        );
+    }
+
+    #[test]
+    fn axes_are_normalized_by_absinfo() {
+        let info = gamepad_info().with_axis(ABS_RX, abs_info(-100, 100, 50));
+        let (backend, id) = read(info, &[(EV_ABS, ABS_Z, 256), (EV_SYN, SYN_REPORT, 0)]);
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!((gamepad.raw_axis(ABS_RX as u32) - 0.5).abs() < 1e-5);
+        assert!((gamepad.raw_axis(ABS_X as u32) - 1.0 / 255.0).abs() < 1e-5);
+        assert!((gamepad.button_value(Button::LeftTrigger) - 256.0 / 1023.0).abs() < 1e-3);
+
+        // Devices list their axes in SDL order with the hat axes read as hats
+        let device = &backend.joystick_devices()[&id];
+        let ranges = device
+            .axes()
+            .iter()
+            .map(|axis| axis.range())
+            .collect::<Vec<(i32, i32)>>();
+        assert_eq!(ranges, vec![(0, 255), (0, 255), (0, 1023), (-100, 100)]);
+        assert_eq!(device.axis(2).unwrap().raw_value(), 256);
+        assert_eq!(device.hats().len(), 1);
+        assert_eq!(device.buttons().len(), 3);
+    }
+
+    #[test]
+    fn joystick_device_buttons_follow_sdl_order() {
+        let (backend, id) = read(
+            gamepad_info(),
+            &[(EV_KEY, BTN_TRIGGER, 1), (EV_SYN, SYN_REPORT, 0)],
+        );
+
+        let device = &backend.joystick_devices()[&id];
+        let pressed = device
+            .buttons()
+            .iter()
+            .map(|button| button.is_pressed())
+            .collect::<Vec<bool>>();
+        assert_eq!(pressed, vec![true, false, false]);
+    }
+
+    #[test]
+    fn dropped_events_are_discarded() {
+        let (backend, id) = read(
+            gamepad_info(),
+            &[
+                (EV_KEY, BTN_SOUTH, 1),
+                (EV_SYN, SYN_DROPPED, 0),
+                (EV_KEY, BTN_EAST, 1),
+                (EV_SYN, SYN_REPORT, 0),
+                (EV_KEY, BTN_EAST, 1),
+            ],
+        );
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(!gamepad.is_pressed(Button::South));
+        assert!(!gamepad.is_pressed(Button::East));
+    }
+
+    #[test]
+    fn undescribed_codes_are_learned() {
+        let info = EvdevDeviceInfo::new("Recording", Guid::default());
+        let (backend, id) = read(
+            info,
+            &[
+                (EV_KEY, BTN_EAST, 1),
+                (EV_ABS, ABS_RZ, 255),
+                (EV_SYN, SYN_REPORT, 0),
+            ],
+        );
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(gamepad.is_pressed(Button::East));
+        assert_eq!(gamepad.button_value(Button::RightTrigger), 1.0);
+    }
+
+    #[test]
+    fn streams_connect_and_disconnect() {
+        let mut backend = EvdevBackend::from_streams();
+        let id = backend.add_stream(stream(&[]), gamepad_info());
+        backend.update().unwrap();
+
+        match &backend.poll_events()[..] {
+            [GamepadEvent::Connected {
+                id: connected,
+                name,
+            }] => {
+                assert_eq!(*connected, id);
+                assert_eq!(name, "Pad");
+            }
+            events => panic!("Unexpected events {:?}", events),
+        }
+
+        assert!(backend.remove_device(id));
+        backend.update().unwrap();
+        assert!(backend.gamepads().is_empty());
+        match &backend.poll_events()[..] {
+            [GamepadEvent::Disconnected {
+                id: disconnected,
+                name,
+            }] => {
+                assert_eq!(*disconnected, id);
+                assert_eq!(name, "Pad");
+            }
+            events => panic!("Unexpected events {:?}", events),
+        }
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn requests_follow_the_kernel_layout() {
+        // EVIOCGNAME(256) and HIDIOCGFEATURE(41) as the kernel headers define them
+        assert_eq!(ioc_request(ioc::READ, b'E', 0x06, 256), Some(0x8100_4506));
+        assert_eq!(
+            ioc_request(ioc::READ | ioc::WRITE, b'H', 0x07, 41),
+            Some(0xc029_4807)
+        );
+        assert_eq!(ioc_request(ioc::READ, b'E', 0x06, 1 << 14), None);
+    }
+
+    #[test]
+    fn sysfs_ids_build_the_sdl_guid() {
+        let node =
+            std::env::temp_dir().join(format!("gamepad-sysfs-{}/event7", std::process::id()));
+        let id = node.join("device/id");
+        std::fs::create_dir_all(&id).unwrap();
+        for (field, value) in [
+            ("bustype", "0003\n"),
+            ("vendor", "054c\n"),
+            ("product", "09cc\n"),
+        ] {
+            std::fs::write(id.join(field), value).unwrap();
+        }
+
+        // Nodes without a complete id are skipped
+        assert_eq!(sysfs_guid(&node), None);
+
+        std::fs::write(id.join("version"), "8111\n").unwrap();
+        assert_eq!(
+            sysfs_guid(&node),
+            Some(sdl_guid(0x0003, 0x054c, 0x09cc, 0x8111))
+        );
+
+        std::fs::remove_dir_all(node.parent().unwrap()).unwrap();
+    }
+}
@@ -1,7 +1,19 @@
 use crate::backends::GamepadEngineBackend;
+use crate::device::*;
 use crate::types::*;
 
-use gilrs::{Axis, Button as GilrsButton, Gamepad, Gilrs};
+use gilrs::ev::filter::{axis_dpad_to_button, Filter, Jitter};
+use gilrs::ev::AxisOrBtn;
+use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat, Replay, Ticks};
+use gilrs::{
+    Axis, Button as GilrsButton, EventType, Gamepad, GamepadId as GilrsGamepadId, Gilrs,
+    GilrsBuilder, PowerInfo as GilrsPowerInfo,
+};
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 
 fn get_gilrs_to_gamepad_buttons() -> Vec<(GilrsButton, Button)> {
     vec![
@@ -15,98 +27,769 @@ fn get_gilrs_to_gamepad_buttons() -> Vec<(GilrsButton, Button)> {
         (GilrsButton::DPadRight, Button::DPadEast),
         (GilrsButton::Start, Button::Start),
         (GilrsButton::Select, Button::Select),
-        (GilrsButton::LeftTrigger, Button::LeftTrigger),
-        (GilrsButton::LeftTrigger2, Button::LeftShoulder),
-        (GilrsButton::RightTrigger, Button::RightTrigger),
-        (GilrsButton::RightTrigger2, Button::RightShoulder),
+        (GilrsButton::Mode, Button::Guide),
+        (GilrsButton::LeftThumb, Button::LeftStick),
+        (GilrsButton::RightThumb, Button::RightStick),
+        (GilrsButton::C, Button::C),
+        (GilrsButton::Z, Button::Z),
+        (GilrsButton::LeftTrigger, Button::LeftShoulder),
+        (GilrsButton::LeftTrigger2, Button::LeftTrigger),
+        (GilrsButton::RightTrigger, Button::RightShoulder),
+        (GilrsButton::RightTrigger2, Button::RightTrigger),
     ]
 }
 
-pub struct GilrsBackend {
-    gilrs: Gilrs,
-    gamepads: Vec<GamepadState>,
+/// gilrs only reports normalized button values, the raw value is scaled to the
+/// same 0..255 range XInput uses for its triggers
+fn to_raw_button(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u16
 }
-impl GilrsBackend {
-    pub fn new() -> Self {
-        GilrsBackend {
-            gilrs: Gilrs::new().unwrap(),
-            gamepads: Vec::new(),
+
+/// gilrs only reports normalized axis values. Where the range the device declares is
+/// unknown they are scaled to 16 bits, these raw values are not in device units and
+/// calibrations captured from them only apply to this backend.
+const GILRS_AXIS_RANGE: (i16, i16) = (-i16::MAX, i16::MAX);
+
+fn to_raw_axis(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+/// Turns a normalized value back into the units of the range it was normalized from
+fn to_device_units(value: f32, (min, max): (i32, i32)) -> i32 {
+    let offset = (value.clamp(-1.0, 1.0) + 1.0) / 2.0 * (max as f32 - min as f32);
+
+    (min as f32 + offset).round() as i32
+}
+
+/// Some gamepads report the D-pad as `DPadX`/`DPadY` axes that gilrs doesn't turn into
+/// buttons, this reads the direction of those axes
+fn to_dpad_axes(gamepad: &Gamepad) -> HatDirection {
+    let value = |axis: Axis| {
+        gamepad
+            .axis_data(axis)
+            .map_or(0.0, |axis_data| axis_data.value())
+    };
+
+    HatDirection::from_axes(value(Axis::DPadX), value(Axis::DPadY), 0.5)
+}
+
+fn to_ff_error(id: GamepadId, error: gilrs::ff::Error) -> GamepadError {
+    GamepadError::new(
+        format!("Force feedback failed for gamepad {}: {}", id, error),
+        ErrorType::ForceFeedback,
+    )
+}
+
+fn to_power_info(power_info: GilrsPowerInfo) -> PowerInfo {
+    match power_info {
+        GilrsPowerInfo::Unknown => PowerInfo::Unknown,
+        GilrsPowerInfo::Wired => PowerInfo::Wired,
+        GilrsPowerInfo::Discharging(charge) => PowerInfo::Discharging(charge),
+        GilrsPowerInfo::Charging(charge) => PowerInfo::Charging(charge),
+        GilrsPowerInfo::Charged => PowerInfo::Charged,
+    }
+}
+
+/// gilrs packs the event type into the upper half of the codes of Linux devices
+#[cfg(target_os = "linux")]
+const EV_KEY: u32 = 0x01;
+#[cfg(target_os = "linux")]
+const EV_ABS: u32 = 0x03;
+
+/// The elements of a device in the order SDL assigns their indices, so SDL mappings
+/// refer to the same elements. It is read once when the gamepad connects, elements keep
+/// their index whether or not they have been used.
+#[derive(Clone, Debug, Default)]
+struct DeviceLayout {
+    /// The code of each axis and its value from -1.0 to 1.0 before it reports one
+    axes: Vec<(u32, f32)>,
+    buttons: Vec<u32>,
+    /// The x axis code of each hat, `None` for the D-pad as gilrs reports it
+    hats: Vec<Option<u32>>,
+    /// The range each axis declares, only known for devices read through evdev
+    ranges: HashMap<u32, (i32, i32)>,
+}
+impl DeviceLayout {
+    fn new(gamepad: &Gamepad) -> Self {
+        #[cfg(target_os = "linux")]
+        if let Some(layout) = DeviceLayout::from_evdev(gamepad) {
+            return layout;
+        }
+
+        DeviceLayout::from_names(gamepad)
+    }
+
+    /// Reads the elements the device node declares, gilrs itself only exposes the
+    /// elements it has a name for
+    #[cfg(target_os = "linux")]
+    fn from_evdev(gamepad: &Gamepad) -> Option<Self> {
+        let info = super::evdev::find_device_info(Guid(gamepad.uuid()), gamepad.os_name())?;
+
+        let ranges = info
+            .axes()
+            .iter()
+            .map(|(code, abs_info)| {
+                let code = EV_ABS << 16 | u32::from(*code);
+                (code, (abs_info.minimum(), abs_info.maximum()))
+            })
+            .collect();
+        let axes = info
+            .sdl_axis_codes()
+            .map(|code| {
+                let abs_info = info.axes()[&code];
+                let value =
+                    DeviceAxis::new(abs_info.value(), abs_info.minimum(), abs_info.maximum())
+                        .value();
+
+                (EV_ABS << 16 | u32::from(code), value)
+            })
+            .collect();
+        let buttons = info
+            .sdl_button_codes()
+            .map(|code| EV_KEY << 16 | u32::from(code))
+            .collect();
+
+        // gilrs turns the first hat into D-pad buttons
+        let hats = info
+            .sdl_hat_codes()
+            .enumerate()
+            .map(|(i, code)| match i {
+                0 => None,
+                _ => Some(EV_ABS << 16 | u32::from(code)),
+            })
+            .collect();
+
+        Some(DeviceLayout {
+            axes,
+            buttons,
+            hats,
+            ranges,
+        })
+    }
+
+    /// The elements gilrs has a name for, ordered by their codes
+    fn from_names(gamepad: &Gamepad) -> Self {
+        let mut axes = [
+            Axis::LeftStickX,
+            Axis::LeftStickY,
+            Axis::LeftZ,
+            Axis::RightStickX,
+            Axis::RightStickY,
+            Axis::RightZ,
+        ]
+        .iter()
+        .filter_map(|axis| gamepad.axis_code(*axis))
+        .map(|code| code.into_u32())
+        .collect::<Vec<u32>>();
+        axes.sort_unstable();
+        axes.dedup();
+
+        let mut buttons = get_gilrs_to_gamepad_buttons()
+            .iter()
+            .filter_map(|(button, _)| gamepad.button_code(*button))
+            .map(|code| code.into_u32())
+            .collect::<Vec<u32>>();
+        buttons.sort_unstable();
+        buttons.dedup();
+
+        let dpad = [
+            GilrsButton::DPadUp,
+            GilrsButton::DPadDown,
+            GilrsButton::DPadLeft,
+            GilrsButton::DPadRight,
+        ];
+        let has_dpad = gamepad.axis_code(Axis::DPadX).is_some()
+            || dpad
+                .iter()
+                .any(|button| gamepad.button_code(*button).is_some());
+
+        DeviceLayout {
+            axes: axes.into_iter().map(|code| (code, 0.0)).collect(),
+            buttons,
+            hats: if has_dpad { vec![None] } else { Vec::new() },
+            ranges: HashMap::new(),
+        }
+    }
+
+    /// The axis in the units of the range it declares, or scaled to 16 bits if that is
+    /// unknown
+    fn device_axis(&self, code: u32, value: f32) -> DeviceAxis {
+        match self.ranges.get(&code) {
+            Some(range) => DeviceAxis::new(to_device_units(value, *range), range.0, range.1),
+            None => DeviceAxis::new(
+                to_raw_axis(value) as i32,
+                GILRS_AXIS_RANGE.0 as i32,
+                GILRS_AXIS_RANGE.1 as i32,
+            ),
+        }
+    }
+
+    /// The stick in the units of the range its axes declare, like the evdev backend reads
+    /// it, or scaled to 16 bits if that is unknown or doesn't fit
+    fn joystick_state(
+        &self,
+        codes: (Option<u32>, Option<u32>),
+        value: (f32, f32),
+    ) -> JoystickState {
+        let range = match codes {
+            (Some(x), Some(y)) => self
+                .ranges
+                .get(&x)
+                .filter(|range| self.ranges.get(&y) == Some(*range))
+                .filter(|(min, max)| *min >= i16::MIN as i32 && *max <= i16::MAX as i32),
+            _ => None,
+        };
+
+        match range {
+            Some(range) => JoystickState::from_raw(
+                (
+                    to_device_units(value.0, *range) as i16,
+                    to_device_units(value.1, *range) as i16,
+                ),
+                (range.0 as i16, range.1 as i16),
+            ),
+            None => JoystickState::from_raw(
+                (to_raw_axis(value.0), to_raw_axis(value.1)),
+                GILRS_AXIS_RANGE,
+            ),
         }
     }
 }
-impl GamepadEngineBackend for GilrsBackend {
-    /// This should be called every frame.
-    fn update(&mut self) -> Result<(), GamepadError> {
-        while let Some(_) = self.gilrs.next_event() {}
 
+/// Reads every element of the device in the order of its layout.
+///
+/// Elements that have not reported a value yet are read at rest. The D-pad, whether gilrs
+/// reports it as buttons or axes, is read as a hat.
+fn to_joystick_device(gamepad: &Gamepad, layout: &DeviceLayout) -> JoystickDevice {
+    let axis_values = gamepad
+        .state()
+        .axes()
+        .map(|(code, axis_data)| (code.into_u32(), axis_data.value()))
+        .collect::<HashMap<u32, f32>>();
+    let button_values = gamepad
+        .state()
+        .buttons()
+        .map(|(code, button_data)| (code.into_u32(), button_data.value()))
+        .collect::<HashMap<u32, f32>>();
+
+    let axes = layout
+        .axes
+        .iter()
+        .map(|(code, rest)| {
+            // Triggers are axes that gilrs reports as buttons from 0.0 to 1.0
+            let value = match (axis_values.get(code), button_values.get(code)) {
+                (Some(value), _) => *value,
+                (None, Some(value)) => value * 2.0 - 1.0,
+                (None, None) => *rest,
+            };
+
+            layout.device_axis(*code, value)
+        })
+        .collect();
+
+    let buttons = layout
+        .buttons
+        .iter()
+        .map(|code| {
+            let value = button_values.get(code).copied().unwrap_or(0.0);
+
+            ButtonState::analog(
+                value,
+                to_raw_button(value),
+                ButtonState::DEFAULT_PRESS_THRESHOLD,
+                false,
+            )
+        })
+        .collect();
+
+    let hats = layout
+        .hats
+        .iter()
+        .map(|hat| match hat {
+            Some(x_code) => {
+                let value = |code| axis_values.get(&code).copied().unwrap_or(0.0);
+                HatDirection::from_axes(value(*x_code), -value(x_code + 1), 0.5)
+            }
+            None => {
+                let (x, y) = to_dpad_axes(gamepad).to_axes();
+                HatDirection::from_buttons(
+                    gamepad.is_pressed(GilrsButton::DPadUp) || y > 0,
+                    gamepad.is_pressed(GilrsButton::DPadDown) || y < 0,
+                    gamepad.is_pressed(GilrsButton::DPadLeft) || x < 0,
+                    gamepad.is_pressed(GilrsButton::DPadRight) || x > 0,
+                )
+            }
+        })
+        .collect();
+
+    JoystickDevice::new(axes, buttons, hats)
+}
+
+/// The gilrs context, it holds raw handles of the platform, such as the udev monitor on
+/// Linux, so it stays on the thread that created it
+struct GilrsContext {
+    gilrs: Gilrs,
+    /// The states of the previous update, to tell which buttons were pressed before
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    layouts: BTreeMap<GamepadId, DeviceLayout>,
+    rumble_effects: BTreeMap<GamepadId, Effect>,
+}
+impl GilrsContext {
+    fn new() -> Result<Self, GamepadError> {
+        // The default filters include a deadzone, deadzones are applied by the engine
+        // instead so they behave the same on every backend
+        let gilrs = GilrsBuilder::new()
+            .with_default_filters(false)
+            .set_update_state(false)
+            .build()
+            .map_err(|e| {
+                let error_type = match e {
+                    gilrs::Error::NotImplemented(_) => ErrorType::Unsupported,
+                    _ => ErrorType::Unknown,
+                };
+                GamepadError::new(format!("Failed to initialize gilrs: {}", e), error_type)
+            })?;
+
+        Ok(GilrsContext {
+            gilrs,
+            gamepads: BTreeMap::new(),
+            layouts: BTreeMap::new(),
+            rumble_effects: BTreeMap::new(),
+        })
+    }
+
+    /// Answers the requests of the backend until it is dropped
+    fn run(mut self, requests: Receiver<GilrsRequest>) {
+        // Replies are dropped if the backend stopped waiting for them
+        for request in requests {
+            match request {
+                GilrsRequest::Update(reply) => {
+                    let _ = reply.send(self.update());
+                }
+                GilrsRequest::SetRumble {
+                    id,
+                    strong,
+                    weak,
+                    duration,
+                    reply,
+                } => {
+                    let _ = reply.send(self.set_rumble(id, strong, weak, duration));
+                }
+                GilrsRequest::StopRumble { id, reply } => {
+                    let _ = reply.send(self.stop_rumble(id));
+                }
+            }
+        }
+    }
+
+    fn gilrs_gamepad_id(&self, id: GamepadId) -> Result<GilrsGamepadId, GamepadError> {
+        self.gilrs
+            .gamepads()
+            .map(|(gamepad_id, _)| gamepad_id)
+            .find(|gamepad_id| GamepadId((*gamepad_id).into()) == id)
+            .ok_or_else(|| {
+                GamepadError::new(
+                    format!("Gamepad {} is not connected", id),
+                    ErrorType::GamepadNotFound { id },
+                )
+            })
+    }
+
+    fn update(&mut self) -> GilrsUpdate {
+        let mut events = Vec::new();
+
+        // Input is read from the gamepad state below, only connection changes are forwarded
+        let jitter = Jitter::new();
+        loop {
+            let event = self
+                .gilrs
+                .next_event()
+                .filter_ev(&axis_dpad_to_button, &mut self.gilrs)
+                .filter_ev(&jitter, &mut self.gilrs);
+            let event = match event {
+                Some(event) if event.is_dropped() => continue,
+                Some(event) => event,
+                None => break,
+            };
+            self.gilrs.update(&event);
+
+            let id = GamepadId(event.id.into());
+            let name = self.gilrs.gamepad(event.id).name().to_string();
+
+            match event.event {
+                EventType::Connected => events.push(GamepadEvent::Connected { id, name }),
+                EventType::Disconnected => events.push(GamepadEvent::Disconnected { id, name }),
+                _ => {}
+            }
+        }
+
+        // gilrs keeps the id of a gamepad for as long as it is connected
         let gamepads = self
             .gilrs
             .gamepads()
-            .map(|(_gamepad_id, gamepad)| gamepad.clone())
-            .collect::<Vec<Gamepad>>();
-        let mut new_gamepads = Vec::new();
-        let mut i = 0;
-        for gamepad in gamepads {
+            .map(|(gamepad_id, gamepad)| (GamepadId(gamepad_id.into()), gamepad))
+            .collect::<Vec<(GamepadId, Gamepad)>>();
+        let mut new_gamepads = BTreeMap::new();
+        let mut new_gamepad_infos = BTreeMap::new();
+        let mut new_joystick_devices = BTreeMap::new();
+        for (id, gamepad) in gamepads {
+            new_gamepad_infos.insert(
+                id,
+                GamepadInfo::new(
+                    gamepad.name(),
+                    Guid(gamepad.uuid()),
+                    to_power_info(gamepad.power_info()),
+                ),
+            );
+
+            let layout = self
+                .layouts
+                .entry(id)
+                .or_insert_with(|| DeviceLayout::new(&gamepad));
             let mut gamepad_state = GamepadState::new();
 
+            let (dpad_x, dpad_y) = to_dpad_axes(&gamepad).to_axes();
+
             for (gilrs_button, button) in get_gilrs_to_gamepad_buttons() {
-                let is_pressed = gamepad.is_pressed(gilrs_button);
                 let mut was_pressed = false;
 
-                if let Some(prev_gamepad) = self.gamepads.get(i) {
-                    was_pressed = prev_gamepad.is_pressed(button.clone());
+                if let Some(prev_gamepad) = self.gamepads.get(&id) {
+                    was_pressed = prev_gamepad.is_pressed(button);
                 }
 
-                gamepad_state
-                    .buttons
-                    .insert(button, ButtonState::new(is_pressed, was_pressed));
-            }
+                let value = match gamepad.button_data(gilrs_button) {
+                    Some(button_data) => button_data.value(),
+                    None => gamepad.is_pressed(gilrs_button) as u8 as f32,
+                };
+                let pressed_on_axes = match button {
+                    Button::DPadNorth => dpad_y > 0,
+                    Button::DPadSouth => dpad_y < 0,
+                    Button::DPadWest => dpad_x < 0,
+                    Button::DPadEast => dpad_x > 0,
+                    _ => false,
+                };
+                let value = if pressed_on_axes { 1.0 } else { value };
 
-            if let (Some(left_x), Some(left_y)) = (
-                gamepad.axis_data(Axis::LeftStickX),
-                gamepad.axis_data(Axis::LeftStickY),
-            ) {
-                gamepad_state.joysticks.insert(
-                    Joystick::Left,
-                    JoystickState::new(
-                        (left_x.value() as i16, left_y.value() as i16),
-                        (left_x.value(), left_y.value()),
+                gamepad_state.buttons.insert(
+                    button,
+                    ButtonState::analog(
+                        value,
+                        to_raw_button(value),
+                        ButtonState::DEFAULT_PRESS_THRESHOLD,
+                        was_pressed,
                     ),
                 );
             }
 
-            if let (Some(right_x), Some(right_y)) = (
-                gamepad.axis_data(Axis::RightStickX),
-                gamepad.axis_data(Axis::RightStickY),
-            ) {
-                gamepad_state.joysticks.insert(
-                    Joystick::Right,
-                    JoystickState::new(
-                        (right_x.value() as i16, right_y.value() as i16),
-                        (right_x.value(), right_y.value()),
-                    ),
+            for (code, button_data) in gamepad.state().buttons() {
+                let button_state = ButtonState::analog(
+                    button_data.value(),
+                    to_raw_button(button_data.value()),
+                    ButtonState::DEFAULT_PRESS_THRESHOLD,
+                    false,
                 );
+
+                // Buttons without a gilrs mapping are passed through by their code
+                match gamepad.axis_or_btn_name(code) {
+                    None | Some(AxisOrBtn::Btn(GilrsButton::Unknown)) => {
+                        gamepad_state
+                            .buttons
+                            .insert(Button::Other(code.into_u32()), button_state.clone());
+                    }
+                    _ => {}
+                }
+
+                gamepad_state
+                    .raw_buttons
+                    .insert(code.into_u32(), button_state);
+            }
+
+            for (code, axis_data) in gamepad.state().axes() {
+                gamepad_state
+                    .raw_axes
+                    .insert(code.into_u32(), axis_data.value());
             }
 
-            new_gamepads.push(gamepad_state);
-            i += 1;
+            for (joystick, x_axis, y_axis) in [
+                (Joystick::Left, Axis::LeftStickX, Axis::LeftStickY),
+                (Joystick::Right, Axis::RightStickX, Axis::RightStickY),
+            ]
+            .iter()
+            {
+                if let (Some(x), Some(y)) = (gamepad.axis_data(*x_axis), gamepad.axis_data(*y_axis))
+                {
+                    let codes = (
+                        gamepad.axis_code(*x_axis).map(|code| code.into_u32()),
+                        gamepad.axis_code(*y_axis).map(|code| code.into_u32()),
+                    );
+                    gamepad_state.joysticks.insert(
+                        *joystick,
+                        layout.joystick_state(codes, (x.value(), y.value())),
+                    );
+                }
+            }
+
+            new_gamepads.insert(id, gamepad_state);
+            new_joystick_devices.insert(id, to_joystick_device(&gamepad, layout));
+        }
+
+        self.rumble_effects
+            .retain(|id, _| new_gamepads.contains_key(id));
+        self.layouts.retain(|id, _| new_gamepads.contains_key(id));
+        self.gamepads = new_gamepads.clone();
+
+        GilrsUpdate {
+            gamepads: new_gamepads,
+            gamepad_infos: new_gamepad_infos,
+            joystick_devices: new_joystick_devices,
+            events,
+        }
+    }
+
+    fn set_rumble(
+        &mut self,
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    ) -> Result<(), GamepadError> {
+        let gamepad_id = self.gilrs_gamepad_id(id)?;
+        if !self.gilrs.gamepad(gamepad_id).is_ff_supported() {
+            return Err(GamepadError::new(
+                format!("Gamepad {} does not support force feedback", id),
+                ErrorType::Unsupported,
+            ));
         }
 
-        self.gamepads = new_gamepads;
+        let millis = duration.as_millis().min(u32::MAX as u128) as u32;
+        if millis == 0 {
+            return self.stop_rumble(id);
+        }
+
+        let scheduling = Replay {
+            play_for: Ticks::from_ms(millis),
+            ..Default::default()
+        };
+        let magnitude = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong {
+                    magnitude: magnitude(strong),
+                },
+                scheduling,
+                envelope: Default::default(),
+            })
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Weak {
+                    magnitude: magnitude(weak),
+                },
+                scheduling,
+                envelope: Default::default(),
+            })
+            .repeat(Repeat::For(Ticks::from_ms(millis)))
+            .gamepads(&[gamepad_id])
+            .finish(&mut self.gilrs)
+            .map_err(|e| to_ff_error(id, e))?;
+        effect.play().map_err(|e| to_ff_error(id, e))?;
+
+        // Dropping the previous effect stops it
+        self.rumble_effects.insert(id, effect);
+
+        Ok(())
+    }
+
+    fn stop_rumble(&mut self, id: GamepadId) -> Result<(), GamepadError> {
+        self.gilrs_gamepad_id(id)?;
+
+        if let Some(effect) = self.rumble_effects.remove(&id) {
+            effect.stop().map_err(|e| to_ff_error(id, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The states read by the gilrs context on an update
+struct GilrsUpdate {
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    gamepad_infos: BTreeMap<GamepadId, GamepadInfo>,
+    joystick_devices: BTreeMap<GamepadId, JoystickDevice>,
+    events: Vec<GamepadEvent>,
+}
+
+/// A request to the gilrs context and where to send its reply
+enum GilrsRequest {
+    Update(Sender<GilrsUpdate>),
+    SetRumble {
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+        reply: Sender<Result<(), GamepadError>>,
+    },
+    StopRumble {
+        id: GamepadId,
+        reply: Sender<Result<(), GamepadError>>,
+    },
+}
+
+fn context_stopped() -> GamepadError {
+    GamepadError::new("The gilrs thread has stopped", ErrorType::Unknown)
+}
+
+/// Reads gamepads through gilrs.
+///
+/// The gilrs context can't be sent to other threads on every platform, so it runs on a
+/// thread of its own which the backend hands its requests to.
+pub struct GilrsBackend {
+    /// Only used through `&mut self`, the mutex makes the backend `Sync`
+    requests: Mutex<Sender<GilrsRequest>>,
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    gamepad_infos: BTreeMap<GamepadId, GamepadInfo>,
+    joystick_devices: BTreeMap<GamepadId, JoystickDevice>,
+    events: Vec<GamepadEvent>,
+}
+impl GilrsBackend {
+    /// Starts the gilrs thread, fails if gilrs doesn't support this platform
+    pub fn new() -> Result<Self, GamepadError> {
+        let (requests, receiver) = mpsc::channel();
+        let (started, start) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("gilrs".to_string())
+            .spawn(move || match GilrsContext::new() {
+                Ok(context) => {
+                    let _ = started.send(Ok(()));
+                    context.run(receiver);
+                }
+                Err(e) => {
+                    let _ = started.send(Err(e));
+                }
+            })
+            .map_err(|e| {
+                GamepadError::new(
+                    format!("Failed to start the gilrs thread: {}", e),
+                    ErrorType::Io,
+                )
+            })?;
+        start.recv().map_err(|_| context_stopped())??;
+
+        Ok(GilrsBackend {
+            requests: Mutex::new(requests),
+            gamepads: BTreeMap::new(),
+            gamepad_infos: BTreeMap::new(),
+            joystick_devices: BTreeMap::new(),
+            events: Vec::new(),
+        })
+    }
+
+    /// Hands a request to the gilrs thread and waits for its reply
+    fn request<T, F: FnOnce(Sender<T>) -> GilrsRequest>(
+        &mut self,
+        request: F,
+    ) -> Result<T, GamepadError> {
+        let (reply, replies) = mpsc::channel();
+
+        self.requests
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(request(reply))
+            .map_err(|_| context_stopped())?;
+        replies.recv().map_err(|_| context_stopped())
+    }
+}
+impl GamepadEngineBackend for GilrsBackend {
+    /// This should be called every frame.
+    fn update(&mut self) -> Result<(), GamepadError> {
+        let update = self.request(GilrsRequest::Update)?;
+
+        self.gamepads = update.gamepads;
+        self.gamepad_infos = update.gamepad_infos;
+        self.joystick_devices = update.joystick_devices;
+        self.events.extend(update.events);
 
         Ok(())
     }
 
     /// This should be called to retrieve all of the events since the last update.
     fn poll_events(&mut self) -> Vec<GamepadEvent> {
-        Vec::new()
+        std::mem::take(&mut self.events)
+    }
+
+    fn gamepad_info(&self, id: GamepadId) -> Option<&GamepadInfo> {
+        self.gamepad_infos.get(&id)
     }
 
-    fn gamepads(&self) -> &Vec<GamepadState> {
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
         &self.gamepads
     }
 
-    fn gamepads_mut(&mut self) -> &mut Vec<GamepadState> {
-        &mut self.gamepads
+    fn joystick_devices(&self) -> &BTreeMap<GamepadId, JoystickDevice> {
+        &self.joystick_devices
+    }
+
+    fn set_rumble(
+        &mut self,
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    ) -> Result<(), GamepadError> {
+        self.request(|reply| GilrsRequest::SetRumble {
+            id,
+            strong,
+            weak,
+            duration,
+            reply,
+        })?
+    }
+
+    fn stop_rumble(&mut self, id: GamepadId) -> Result<(), GamepadError> {
+        self.request(|reply| GilrsRequest::StopRumble { id, reply })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(ranges: &[(u32, (i32, i32))]) -> DeviceLayout {
+        DeviceLayout {
+            ranges: ranges.iter().copied().collect(),
+            ..DeviceLayout::default()
+        }
+    }
+
+    #[test]
+    fn device_units_cover_the_declared_range() {
+        assert_eq!(to_device_units(-1.0, (0, 255)), 0);
+        assert_eq!(to_device_units(1.0, (0, 255)), 255);
+        assert_eq!(to_device_units(0.0, (-100, 100)), 0);
+        assert_eq!(to_device_units(2.0, (-100, 100)), 100);
+        assert_eq!(to_device_units(-0.5, (-100, 100)), -50);
+    }
+
+    #[test]
+    fn axes_use_their_declared_range() {
+        let layout = layout(&[(1, (0, 1023))]);
+
+        assert_eq!(layout.device_axis(1, 1.0).raw_value(), 1023);
+        assert_eq!(layout.device_axis(1, 1.0).range(), (0, 1023));
+        assert_eq!(layout.device_axis(2, -1.0).raw_value(), -(i16::MAX as i32));
+    }
+
+    #[test]
+    fn joysticks_use_their_declared_range() {
+        let layout = layout(&[(1, (-512, 511)), (2, (-512, 511)), (3, (0, 255))]);
+
+        let state = layout.joystick_state((Some(1), Some(2)), (-1.0, 1.0));
+        assert_eq!(state.raw_value(), (-512, 511));
+
+        // Sticks whose axes differ or whose range is unknown are scaled to 16 bits
+        let state = layout.joystick_state((Some(1), Some(3)), (1.0, -1.0));
+        assert_eq!(state.raw_value(), (i16::MAX, -i16::MAX));
+        let state = layout.joystick_state((Some(1), None), (1.0, 0.0));
+        assert_eq!(state.raw_value(), (i16::MAX, 0));
     }
 }
@@ -0,0 +1,467 @@
+use crate::backends::evdev::{ioctl_read, ioctl_read_write, sdl_guid};
+use crate::backends::{DeviceDirectory, GamepadEngineBackend};
+use crate::sony::*;
+use crate::switch::*;
+use crate::types::*;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The directory the kernel creates hidraw device nodes in
+pub const DEFAULT_HIDRAW_DIRECTORY: &str = "/dev";
+
+const BUS_BLUETOOTH: u32 = 0x05;
+
+/// The largest input report read from any supported gamepad
+const MAX_REPORT_SIZE: usize = 512;
+
+/// How long to wait between the requests sent to set up a gamepad, Switch controllers
+/// drop requests that arrive while they are busy with the previous one
+const REQUEST_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Turns the input reports of one kind of gamepad into gamepad states
+enum ReportParser {
+    Sony(SonyReportParser),
+    Switch(SwitchReportParser),
+}
+impl ReportParser {
+    fn parse(&mut self, report: &[u8]) -> Option<(GamepadState, PowerInfo)> {
+        match self {
+            ReportParser::Sony(parser) => {
+                let report = parser.parse(report).ok()?;
+                let power_info = report.power_info();
+
+                Some((report.into_gamepad(), power_info))
+            }
+            ReportParser::Switch(parser) => {
+                let report = parser.parse(report).ok()?;
+                let power_info = report.power_info();
+
+                Some((report.into_gamepad(), power_info))
+            }
+        }
+    }
+}
+
+struct HidrawDevice {
+    path: PathBuf,
+    file: File,
+    parser: ReportParser,
+    connection: HidConnection,
+    info: GamepadInfo,
+    gamepad: GamepadState,
+    output: SonyOutputReport,
+    rumble_until: Option<Instant>,
+    requests: VecDeque<Vec<u8>>,
+    last_request: Option<Instant>,
+}
+impl HidrawDevice {
+    /// Opens the device node, `None` if it is not a supported gamepad
+    fn open(path: PathBuf) -> io::Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)?;
+
+        Ok(HidrawDevice::from_file(path, file))
+    }
+
+    fn from_file(path: PathBuf, file: File) -> Option<Self> {
+        let fd = file.as_raw_fd();
+
+        // `struct hidraw_devinfo`: the bus as u32 followed by the vendor and product ids
+        let mut devinfo = [0u8; 8];
+        ioctl_read(fd, b'H', 0x03, &mut devinfo).ok()?;
+        let bustype = u32::from_ne_bytes([devinfo[0], devinfo[1], devinfo[2], devinfo[3]]);
+        let vendor = u16::from_ne_bytes([devinfo[4], devinfo[5]]);
+        let product = u16::from_ne_bytes([devinfo[6], devinfo[7]]);
+
+        let connection = match bustype {
+            BUS_BLUETOOTH => HidConnection::Bluetooth,
+            _ => HidConnection::Usb,
+        };
+
+        let mut requests = VecDeque::new();
+        let parser = match vendor {
+            SONY_VENDOR_ID => ReportParser::Sony(SonyReportParser::new(
+                SonyController::from_product_id(product)?,
+            )),
+            NINTENDO_VENDOR_ID => {
+                let parser = SwitchReportParser::new(SwitchController::from_product_id(product)?);
+
+                // Over USB the controller only talks HID after a handshake
+                if connection == HidConnection::Usb {
+                    requests.push_back(vec![0x80, 0x02]);
+                    requests.push_back(vec![0x80, 0x04]);
+                }
+                let (factory_address, factory_length) =
+                    SwitchReportParser::FACTORY_STICK_CALIBRATION;
+                let (user_address, user_length) = SwitchReportParser::USER_STICK_CALIBRATION;
+                for (counter, subcommand) in [
+                    SwitchSubcommand::SetInputReportMode(0x30),
+                    SwitchSubcommand::EnableImu(true),
+                    SwitchSubcommand::SpiRead {
+                        address: factory_address,
+                        length: factory_length,
+                    },
+                    SwitchSubcommand::SpiRead {
+                        address: user_address,
+                        length: user_length,
+                    },
+                ]
+                .iter()
+                .enumerate()
+                {
+                    requests.push_back(subcommand.to_bytes(counter as u8));
+                }
+
+                ReportParser::Switch(parser)
+            }
+            _ => return None,
+        };
+
+        let mut name = [0u8; 256];
+        let name = match ioctl_read(fd, b'H', 0x04, &mut name) {
+            Ok(_) => {
+                let len = name
+                    .iter()
+                    .position(|byte| *byte == 0)
+                    .unwrap_or(name.len());
+                String::from_utf8_lossy(&name[..len]).into_owned()
+            }
+            Err(_) => String::new(),
+        };
+
+        // Over Bluetooth Sony gamepads only send reduced reports until their calibration
+        // feature report is read
+        if connection == HidConnection::Bluetooth {
+            let mut feature = [0u8; 41];
+            feature[0] = 0x05;
+            let _ = ioctl_read_write(fd, b'H', 0x07, &mut feature);
+        }
+
+        Some(HidrawDevice {
+            path,
+            file,
+            parser,
+            connection,
+            info: GamepadInfo::new(
+                name,
+                sdl_guid(bustype as u16, vendor, product, 0),
+                PowerInfo::Unknown,
+            ),
+            gamepad: GamepadState::new(),
+            output: SonyOutputReport::new(),
+            rumble_until: None,
+            requests,
+            last_request: None,
+        })
+    }
+
+    /// Replaces the state of the outputs and sends it, the state is kept as it was if the
+    /// gamepad has no supported outputs
+    fn send_output(&mut self, output: SonyOutputReport) -> Result<(), GamepadError> {
+        let controller = match &self.parser {
+            ReportParser::Sony(parser) => parser.controller(),
+            ReportParser::Switch(_) => {
+                return Err(GamepadError::new(
+                    "Outputs of Switch controllers are not supported",
+                    ErrorType::Unsupported,
+                ))
+            }
+        };
+
+        self.output = output.with_sequence(self.output.sequence().wrapping_add(1));
+        let report = self.output.to_bytes(controller, self.connection);
+
+        self.file.write_all(&report).map_err(|e| {
+            GamepadError::new(
+                format!("Failed to send output report: {}", e),
+                ErrorType::Io,
+            )
+        })
+    }
+
+    /// Sends the next request to set up the gamepad once the previous one had time to
+    /// be handled
+    fn send_request(&mut self, now: Instant) {
+        if self
+            .last_request
+            .is_some_and(|last| now.duration_since(last) < REQUEST_INTERVAL)
+        {
+            return;
+        }
+
+        if let Some(request) = self.requests.pop_front() {
+            // A gamepad that can't be reached is disconnected on the next read
+            let _ = self.file.write_all(&request);
+            self.last_request = Some(now);
+        }
+    }
+
+    /// Hands the reports waiting in the node to the parser, false once the gamepad was unplugged
+    fn read_reports(&mut self) -> bool {
+        let mut report = [0u8; MAX_REPORT_SIZE];
+
+        loop {
+            // Every read returns a single report
+            match self.file.read(&mut report) {
+                Ok(0) => return true,
+                Ok(len) => {
+                    // Reports the parser doesn't know, such as replies to requests, are skipped
+                    if let Some((gamepad, power_info)) = self.parser.parse(&report[..len]) {
+                        self.gamepad = gamepad;
+                        self.info.power_info = power_info;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+/// Reads gamepads through the Linux hidraw interface, parsing their raw HID reports.
+///
+/// This reaches data the kernel drivers don't pass on in the same detail, such as the
+/// touchpad and battery of Playstation gamepads. Only gamepads with a report parser are
+/// opened, currently the DualShock 4, DualSense, Switch Pro Controller and Joy-Con.
+pub struct HidrawBackend {
+    directory: DeviceDirectory,
+    devices: BTreeMap<GamepadId, HidrawDevice>,
+    next_id: usize,
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    events: Vec<GamepadEvent>,
+}
+impl HidrawBackend {
+    /// Creates a backend reading the `hidraw*` device nodes in `/dev`
+    pub fn new() -> Self {
+        HidrawBackend::with_directory(DEFAULT_HIDRAW_DIRECTORY)
+    }
+
+    /// Creates a backend reading the `hidraw*` device nodes in the directory
+    pub fn with_directory<P: AsRef<Path>>(directory: P) -> Self {
+        HidrawBackend {
+            directory: DeviceDirectory::new(directory.as_ref().to_path_buf(), "hidraw"),
+            devices: BTreeMap::new(),
+            next_id: 0,
+            gamepads: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn device_mut(&mut self, id: GamepadId) -> Result<&mut HidrawDevice, GamepadError> {
+        self.devices.get_mut(&id).ok_or_else(|| {
+            GamepadError::new(
+                format!("Gamepad {} is not connected", id),
+                ErrorType::GamepadNotFound { id },
+            )
+        })
+    }
+
+    /// Opens the gamepads with a report parser that appeared in the directory
+    fn scan_directory(&mut self) {
+        let devices = &mut self.devices;
+        let next_id = &mut self.next_id;
+        let events = &mut self.events;
+
+        self.directory.scan(|path| {
+            if devices.values().any(|device| device.path == path) {
+                return Ok(());
+            }
+
+            if let Some(device) = HidrawDevice::open(path.to_path_buf())? {
+                let id = GamepadId(*next_id);
+                *next_id += 1;
+
+                events.push(GamepadEvent::Connected {
+                    id,
+                    name: device.info.name.clone(),
+                });
+                devices.insert(id, device);
+            }
+
+            Ok(())
+        });
+    }
+}
+impl Default for HidrawBackend {
+    fn default() -> Self {
+        HidrawBackend::new()
+    }
+}
+impl GamepadEngineBackend for HidrawBackend {
+    fn update(&mut self) -> Result<(), GamepadError> {
+        self.scan_directory();
+
+        let gone = self
+            .devices
+            .iter_mut()
+            .filter_map(|(id, device)| (!device.read_reports()).then_some(*id))
+            .collect::<Vec<GamepadId>>();
+        for id in gone {
+            if let Some(device) = self.devices.remove(&id) {
+                self.events.push(GamepadEvent::Disconnected {
+                    id,
+                    name: device.info.name,
+                });
+            }
+        }
+
+        let now = Instant::now();
+        for device in self.devices.values_mut() {
+            device.send_request(now);
+
+            if device.rumble_until.is_some_and(|until| until <= now) {
+                device.rumble_until = None;
+                // A gamepad that can't be reached is disconnected on the next read
+                let _ = device.send_output(device.output.with_rumble(0, 0));
+            }
+        }
+
+        self.gamepads = self
+            .devices
+            .iter()
+            .map(|(id, device)| (*id, device.gamepad.clone()))
+            .collect();
+
+        Ok(())
+    }
+
+    fn poll_events(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn gamepad_info(&self, id: GamepadId) -> Option<&GamepadInfo> {
+        self.devices.get(&id).map(|device| &device.info)
+    }
+
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
+    }
+
+    fn set_rumble(
+        &mut self,
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    ) -> Result<(), GamepadError> {
+        let device = self.device_mut(id)?;
+
+        let magnitude = |value: f32| (value * u8::MAX as f32).round() as u8;
+        device.send_output(
+            device
+                .output
+                .with_rumble(magnitude(strong), magnitude(weak)),
+        )?;
+        device.rumble_until = Some(Instant::now() + duration);
+
+        Ok(())
+    }
+
+    fn stop_rumble(&mut self, id: GamepadId) -> Result<(), GamepadError> {
+        let device = self.device_mut(id)?;
+
+        device.rumble_until = None;
+        device.send_output(device.output.with_rumble(0, 0))
+    }
+
+    fn set_light(&mut self, id: GamepadId, rgb: (u8, u8, u8)) -> Result<(), GamepadError> {
+        let device = self.device_mut(id)?;
+
+        device.send_output(device.output.with_light(rgb))
+    }
+
+    fn set_trigger_effect(
+        &mut self,
+        id: GamepadId,
+        side: TriggerSide,
+        effect: TriggerEffect,
+    ) -> Result<(), GamepadError> {
+        let device = self.device_mut(id)?;
+        match &device.parser {
+            ReportParser::Sony(parser) if parser.controller() == SonyController::DualSense => {}
+            _ => {
+                return Err(GamepadError::new(
+                    "The gamepad has no adaptive triggers",
+                    ErrorType::Unsupported,
+                ))
+            }
+        }
+
+        device.send_output(device.output.with_trigger_effect(side, effect))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::hex_fixture;
+
+    #[test]
+    fn sony_reports_are_parsed() {
+        let mut parser = ReportParser::Sony(SonyReportParser::new(SonyController::DualShock4));
+        let mut bluetooth = hex_fixture("sony/ds4_bluetooth.hex");
+
+        let (gamepad, power_info) = parser.parse(&bluetooth).unwrap();
+        assert!(gamepad.is_pressed(Button::South));
+        assert!(gamepad.touchpad().is_some());
+        assert_eq!(power_info, PowerInfo::Discharging(85));
+
+        // Reports that fail their CRC check are dropped
+        bluetooth[20] ^= 0xff;
+        assert!(parser.parse(&bluetooth).is_none());
+        assert!(parser.parse(&[0x05, 0x00]).is_none());
+    }
+
+    /// A device writing its output reports to a temporary file
+    fn device(parser: ReportParser) -> HidrawDevice {
+        let path = std::env::temp_dir().join(format!("gamepad-hidraw-{}", std::process::id()));
+        let file = File::create(&path).unwrap();
+
+        HidrawDevice {
+            path,
+            file,
+            parser,
+            connection: HidConnection::Usb,
+            info: GamepadInfo::new("Pad", Guid::default(), PowerInfo::Unknown),
+            gamepad: GamepadState::new(),
+            output: SonyOutputReport::new(),
+            rumble_until: None,
+            requests: VecDeque::new(),
+            last_request: None,
+        }
+    }
+
+    #[test]
+    fn unsupported_outputs_keep_the_output_state() {
+        let mut backend = HidrawBackend::new();
+        let id = GamepadId(0);
+        backend.devices.insert(
+            id,
+            device(ReportParser::Switch(SwitchReportParser::new(
+                SwitchController::ProController,
+            ))),
+        );
+
+        let error = backend
+            .set_rumble(id, 1.0, 1.0, Duration::from_secs(1))
+            .unwrap_err();
+        assert!(matches!(error.error_type, ErrorType::Unsupported));
+        assert!(backend.set_light(id, (255, 0, 0)).is_err());
+
+        let device = &backend.devices[&id];
+        assert_eq!(device.output, SonyOutputReport::new());
+        assert_eq!(device.rumble_until, None);
+
+        let _ = std::fs::remove_file(&device.path);
+    }
+}
@@ -0,0 +1,751 @@
+use crate::backends::evdev::{ioctl_read, DEFAULT_INPUT_DIRECTORY};
+use crate::backends::{DeviceDirectory, GamepadEngineBackend};
+use crate::device::*;
+use crate::types::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+const JS_EVENT_INIT: u8 = 0x80;
+
+/// Joydev reports every axis from -32767 to 32767
+const JS_AXIS_MAX: i16 = i16::MAX;
+
+/// A single `js_event` record as read from a joydev device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JsEvent {
+    pub(crate) time: u32,
+    pub(crate) value: i16,
+    pub(crate) kind: u8,
+    pub(crate) number: u8,
+}
+impl JsEvent {
+    /// The size of a `js_event` record
+    pub const SIZE: usize = 8;
+
+    pub fn new(time: u32, value: i16, kind: u8, number: u8) -> Self {
+        JsEvent {
+            time,
+            value,
+            kind,
+            number,
+        }
+    }
+
+    /// Creates the event a button sends when it is pressed or released
+    pub fn button(time: u32, number: u8, is_pressed: bool) -> Self {
+        JsEvent::new(time, is_pressed as i16, JS_EVENT_BUTTON, number)
+    }
+
+    /// Creates the event an axis sends when it moves
+    pub fn axis(time: u32, number: u8, value: i16) -> Self {
+        JsEvent::new(time, value, JS_EVENT_AXIS, number)
+    }
+
+    /// Marks the event as one of the events the driver sends on open to describe the
+    /// initial state of the device
+    pub fn with_init(mut self) -> Self {
+        self.kind |= JS_EVENT_INIT;
+        self
+    }
+
+    /// Reads a record in the byte order of this platform, `None` if there are too few bytes
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < JsEvent::SIZE {
+            return None;
+        }
+
+        Some(JsEvent {
+            time: u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            value: i16::from_ne_bytes([bytes[4], bytes[5]]),
+            kind: bytes[6],
+            number: bytes[7],
+        })
+    }
+
+    /// Writes the record in the byte order of this platform, such as to build a recording
+    pub fn to_bytes(&self) -> [u8; JsEvent::SIZE] {
+        let mut bytes = [0; JsEvent::SIZE];
+        bytes[..4].copy_from_slice(&self.time.to_ne_bytes());
+        bytes[4..6].copy_from_slice(&self.value.to_ne_bytes());
+        bytes[6] = self.kind;
+        bytes[7] = self.number;
+
+        bytes
+    }
+
+    /// When the driver received the event in milliseconds, from an unspecified starting point
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// 0 or 1 for buttons, -32767 to 32767 for axes
+    pub fn value(&self) -> i16 {
+        self.value
+    }
+
+    /// The event type, `JS_EVENT_BUTTON` (1) or `JS_EVENT_AXIS` (2) together with
+    /// the `JS_EVENT_INIT` flag (0x80)
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+
+    /// The number of the button or axis
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    pub fn is_button(&self) -> bool {
+        self.kind & !JS_EVENT_INIT == JS_EVENT_BUTTON
+    }
+
+    pub fn is_axis(&self) -> bool {
+        self.kind & !JS_EVENT_INIT == JS_EVENT_AXIS
+    }
+
+    pub fn is_init(&self) -> bool {
+        self.kind & JS_EVENT_INIT != 0
+    }
+}
+
+/// Tells which button or axis of a gamepad each numbered button and axis of a joydev
+/// device is. Numbers without an entry are passed through as `Button::Other` and raw axes.
+///
+/// The default follows how the xpad driver numbers the elements of an Xbox controller.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoydevMapping {
+    buttons: BTreeMap<u8, Button>,
+    axes: BTreeMap<u8, Axis>,
+}
+impl JoydevMapping {
+    /// Creates a mapping without any entries
+    pub fn new() -> Self {
+        JoydevMapping {
+            buttons: BTreeMap::new(),
+            axes: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_button(mut self, number: u8, button: Button) -> Self {
+        self.buttons.insert(number, button);
+        self
+    }
+
+    /// Maps the axis, joydev reports the y-axes of sticks with down as positive and
+    /// triggers from -32767 at rest to 32767, which is corrected for
+    pub fn with_axis(mut self, number: u8, axis: Axis) -> Self {
+        self.axes.insert(number, axis);
+        self
+    }
+
+    pub fn button(&self, number: u8) -> Option<Button> {
+        self.buttons.get(&number).copied()
+    }
+
+    pub fn axis(&self, number: u8) -> Option<Axis> {
+        self.axes.get(&number).copied()
+    }
+
+    pub fn buttons(&self) -> &BTreeMap<u8, Button> {
+        &self.buttons
+    }
+
+    pub fn axes(&self) -> &BTreeMap<u8, Axis> {
+        &self.axes
+    }
+}
+impl Default for JoydevMapping {
+    fn default() -> Self {
+        JoydevMapping::new()
+            .with_button(0, Button::South)
+            .with_button(1, Button::East)
+            .with_button(2, Button::West)
+            .with_button(3, Button::North)
+            .with_button(4, Button::LeftShoulder)
+            .with_button(5, Button::RightShoulder)
+            .with_button(6, Button::Select)
+            .with_button(7, Button::Start)
+            .with_button(8, Button::Guide)
+            .with_button(9, Button::LeftStick)
+            .with_button(10, Button::RightStick)
+            .with_axis(0, Axis::LeftStickX)
+            .with_axis(1, Axis::LeftStickY)
+            .with_axis(2, Axis::LeftTrigger)
+            .with_axis(3, Axis::RightStickX)
+            .with_axis(4, Axis::RightStickY)
+            .with_axis(5, Axis::RightTrigger)
+            .with_axis(6, Axis::DPadX)
+            .with_axis(7, Axis::DPadY)
+    }
+}
+
+struct JoydevDevice {
+    path: Option<PathBuf>,
+    name: String,
+    source: Box<dyn Read + Send + Sync>,
+    buffer: Vec<u8>,
+    buttons: Vec<bool>,
+    axes: Vec<i16>,
+}
+impl JoydevDevice {
+    fn new(
+        path: Option<PathBuf>,
+        name: String,
+        source: Box<dyn Read + Send + Sync>,
+        axis_count: usize,
+        button_count: usize,
+    ) -> Self {
+        JoydevDevice {
+            path,
+            name,
+            source,
+            buffer: Vec::new(),
+            buttons: vec![false; button_count],
+            axes: vec![0; axis_count],
+        }
+    }
+
+    /// Drains the `js_event` records waiting in the source, false once the node was unplugged
+    fn read_events(&mut self) -> bool {
+        let mut chunk = [0u8; JsEvent::SIZE * 64];
+
+        loop {
+            match self.source.read(&mut chunk) {
+                // The end of a stream keeps the device connected
+                Ok(0) => return true,
+                Ok(len) => {
+                    self.buffer.extend_from_slice(&chunk[..len]);
+
+                    let complete = self.buffer.len() / JsEvent::SIZE * JsEvent::SIZE;
+                    let bytes = self.buffer.drain(..complete).collect::<Vec<u8>>();
+                    for record in bytes.chunks(JsEvent::SIZE) {
+                        if let Some(event) = JsEvent::from_bytes(record) {
+                            self.process_event(event);
+                        }
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// Init events carry the state the device had when it was opened and are applied
+    /// like any other event
+    fn process_event(&mut self, event: JsEvent) {
+        let number = event.number as usize;
+
+        if event.is_button() {
+            if self.buttons.len() <= number {
+                self.buttons.resize(number + 1, false);
+            }
+            self.buttons[number] = event.value != 0;
+        } else if event.is_axis() {
+            if self.axes.len() <= number {
+                self.axes.resize(number + 1, 0);
+            }
+            self.axes[number] = event.value;
+        }
+    }
+
+    /// The value of the axis from -1.0 to 1.0
+    fn axis_value(&self, number: usize) -> f32 {
+        self.axes
+            .get(number)
+            .map_or(0.0, |value| *value as f32 / JS_AXIS_MAX as f32)
+            .clamp(-1.0, 1.0)
+    }
+
+    fn gamepad_state(&self, mapping: &JoydevMapping) -> GamepadState {
+        let mut gamepad_state = GamepadState::new();
+
+        for (number, is_pressed) in self.buttons.iter().enumerate() {
+            let button_state = ButtonState::new(*is_pressed, false);
+            gamepad_state
+                .raw_buttons
+                .insert(number as u32, button_state);
+
+            let button = mapping
+                .button(number as u8)
+                .unwrap_or(Button::Other(number as u32));
+            let is_pressed = *is_pressed || gamepad_state.is_pressed(button);
+            gamepad_state
+                .buttons
+                .insert(button, ButtonState::new(is_pressed, false));
+        }
+
+        let mut sticks = HashMap::new();
+        let mut dpad = (0.0, 0.0);
+        for number in 0..self.axes.len() {
+            let value = self.axis_value(number);
+            gamepad_state.raw_axes.insert(number as u32, value);
+
+            let axis = match mapping.axis(number as u8) {
+                Some(axis) => axis,
+                None => continue,
+            };
+            let raw = self.axes[number].max(-JS_AXIS_MAX);
+            match axis {
+                Axis::LeftStickX => sticks.entry(Joystick::Left).or_insert((0, 0)).0 = raw,
+                Axis::LeftStickY => sticks.entry(Joystick::Left).or_insert((0, 0)).1 = -raw,
+                Axis::RightStickX => sticks.entry(Joystick::Right).or_insert((0, 0)).0 = raw,
+                Axis::RightStickY => sticks.entry(Joystick::Right).or_insert((0, 0)).1 = -raw,
+                Axis::LeftTrigger | Axis::RightTrigger => {
+                    let button = match axis {
+                        Axis::LeftTrigger => Button::LeftTrigger,
+                        _ => Button::RightTrigger,
+                    };
+                    let value = (value + 1.0) / 2.0;
+                    gamepad_state.buttons.insert(
+                        button,
+                        ButtonState::analog(
+                            value,
+                            (value * u8::MAX as f32).round() as u16,
+                            ButtonState::DEFAULT_PRESS_THRESHOLD,
+                            false,
+                        ),
+                    );
+                }
+                Axis::DPadX => dpad.0 = value,
+                Axis::DPadY => dpad.1 = -value,
+            }
+        }
+
+        for (joystick, raw_value) in sticks {
+            gamepad_state.joysticks.insert(
+                joystick,
+                JoystickState::from_raw(raw_value, (-JS_AXIS_MAX, JS_AXIS_MAX)),
+            );
+        }
+
+        // The D-pad is reported either as buttons or as a pair of axes
+        if mapping
+            .axes
+            .values()
+            .any(|axis| *axis == Axis::DPadX || *axis == Axis::DPadY)
+        {
+            let hat = HatDirection::from_axes(dpad.0, dpad.1, 0.5);
+            for (button, pressed) in [
+                (Button::DPadNorth, hat.is_north()),
+                (Button::DPadSouth, hat.is_south()),
+                (Button::DPadWest, hat.is_west()),
+                (Button::DPadEast, hat.is_east()),
+            ] {
+                let pressed = pressed || gamepad_state.is_pressed(button);
+                gamepad_state
+                    .buttons
+                    .insert(button, ButtonState::new(pressed, false));
+            }
+        }
+
+        gamepad_state
+    }
+
+    /// The device as numbered by the driver, joydev reports hats as pairs of axes
+    fn joystick_device(&self) -> JoystickDevice {
+        let axes = self
+            .axes
+            .iter()
+            .map(|value| DeviceAxis::new(*value as i32, -JS_AXIS_MAX as i32, JS_AXIS_MAX as i32))
+            .collect();
+        let buttons = self
+            .buttons
+            .iter()
+            .map(|is_pressed| ButtonState::new(*is_pressed, false))
+            .collect();
+
+        JoystickDevice::new(axes, buttons, Vec::new())
+    }
+}
+
+/// Reads gamepads and joysticks through the legacy Linux joystick interface, for systems
+/// that don't expose evdev devices.
+///
+/// Device nodes are found in a directory, `/dev/input` unless another one is given,
+/// which is scanned again whenever its contents change. Devices can also be read from any
+/// byte stream of `js_event` records, such as a recording.
+pub struct JoydevBackend {
+    directory: Option<DeviceDirectory>,
+    mapping: JoydevMapping,
+    devices: BTreeMap<GamepadId, JoydevDevice>,
+    next_id: usize,
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    gamepad_infos: BTreeMap<GamepadId, GamepadInfo>,
+    joystick_devices: BTreeMap<GamepadId, JoystickDevice>,
+    events: Vec<GamepadEvent>,
+}
+impl JoydevBackend {
+    /// Creates a backend reading the device nodes in `/dev/input`
+    pub fn new() -> Self {
+        JoydevBackend::with_directory(DEFAULT_INPUT_DIRECTORY)
+    }
+
+    /// Creates a backend reading the `js*` files in the directory
+    pub fn with_directory<P: AsRef<Path>>(directory: P) -> Self {
+        let mut backend = JoydevBackend::from_streams();
+        backend.directory = Some(DeviceDirectory::new(directory.as_ref().to_path_buf(), "js"));
+
+        backend
+    }
+
+    /// Creates a backend that doesn't watch a directory, every device comes from `add_stream`
+    pub fn from_streams() -> Self {
+        JoydevBackend {
+            directory: None,
+            mapping: JoydevMapping::default(),
+            devices: BTreeMap::new(),
+            next_id: 0,
+            gamepads: BTreeMap::new(),
+            gamepad_infos: BTreeMap::new(),
+            joystick_devices: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Sets the mapping used for every device
+    pub fn with_mapping(mut self, mapping: JoydevMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    pub fn mapping(&self) -> &JoydevMapping {
+        &self.mapping
+    }
+
+    pub fn set_mapping(&mut self, mapping: JoydevMapping) {
+        self.mapping = mapping;
+    }
+
+    /// Adds a device reading `js_event` records from the stream, it is connected on the
+    /// next update. Reads from the stream should not block, the end of the stream
+    /// leaves the device connected.
+    pub fn add_stream<R: Read + Send + Sync + 'static, T: Into<String>>(
+        &mut self,
+        stream: R,
+        name: T,
+    ) -> GamepadId {
+        self.add_device(JoydevDevice::new(None, name.into(), Box::new(stream), 0, 0))
+    }
+
+    /// Drops the device, returns false if there is no such device.
+    ///
+    /// Its `Disconnected` event is reported by the next `poll_events`.
+    pub fn remove_device(&mut self, id: GamepadId) -> bool {
+        match self.devices.remove(&id) {
+            Some(device) => {
+                self.events.push(GamepadEvent::Disconnected {
+                    id,
+                    name: device.name,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn add_device(&mut self, device: JoydevDevice) -> GamepadId {
+        let id = GamepadId(self.next_id);
+        self.next_id += 1;
+
+        self.events.push(GamepadEvent::Connected {
+            id,
+            name: device.name.clone(),
+        });
+        self.devices.insert(id, device);
+
+        id
+    }
+
+    fn scan_directory(&mut self) {
+        if let Some(mut directory) = self.directory.take() {
+            directory.scan(|path| self.open_node(path));
+            self.directory = Some(directory);
+        }
+    }
+
+    fn open_node(&mut self, path: &Path) -> io::Result<()> {
+        if self
+            .devices
+            .values()
+            .any(|device| device.path.as_deref() == Some(path))
+        {
+            return Ok(());
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+
+        let (name, axis_count, button_count) = query_device(&file).unwrap_or_else(|| {
+            // Recordings are files without a driver to describe them
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            (name.into_owned(), 0, 0)
+        });
+
+        self.add_device(JoydevDevice::new(
+            Some(path.to_path_buf()),
+            name,
+            Box::new(file),
+            axis_count,
+            button_count,
+        ));
+
+        Ok(())
+    }
+}
+impl Default for JoydevBackend {
+    fn default() -> Self {
+        JoydevBackend::new()
+    }
+}
+impl GamepadEngineBackend for JoydevBackend {
+    fn update(&mut self) -> Result<(), GamepadError> {
+        self.scan_directory();
+
+        let gone = self
+            .devices
+            .iter_mut()
+            .filter_map(|(id, device)| (!device.read_events()).then_some(*id))
+            .collect::<Vec<GamepadId>>();
+        for id in gone {
+            self.remove_device(id);
+        }
+
+        self.gamepads.clear();
+        self.gamepad_infos.clear();
+        self.joystick_devices.clear();
+        for (id, device) in self.devices.iter() {
+            self.gamepads
+                .insert(*id, device.gamepad_state(&self.mapping));
+            self.joystick_devices.insert(*id, device.joystick_device());
+            // Joydev doesn't tell the bus, vendor or product of the device
+            self.gamepad_infos.insert(
+                *id,
+                GamepadInfo::new(device.name.clone(), Guid::default(), PowerInfo::Unknown),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn poll_events(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn gamepad_info(&self, id: GamepadId) -> Option<&GamepadInfo> {
+        self.gamepad_infos.get(&id)
+    }
+
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
+    }
+
+    fn joystick_devices(&self) -> &BTreeMap<GamepadId, JoystickDevice> {
+        &self.joystick_devices
+    }
+}
+
+/// Asks the driver for the name and the number of axes and buttons of an opened device
+/// node, `None` if the file is not a joydev device
+fn query_device(file: &File) -> Option<(String, usize, usize)> {
+    let fd = file.as_raw_fd();
+
+    let mut axis_count = [0u8; 1];
+    ioctl_read(fd, b'j', 0x11, &mut axis_count).ok()?;
+
+    let mut button_count = [0u8; 1];
+    ioctl_read(fd, b'j', 0x12, &mut button_count).ok()?;
+
+    let mut name = [0u8; 128];
+    let name = match ioctl_read(fd, b'j', 0x13, &mut name) {
+        Ok(_) => {
+            let len = name
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..len]).into_owned()
+        }
+        Err(_) => String::new(),
+    };
+
+    Some((name, axis_count[0] as usize, button_count[0] as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn stream(events: &[JsEvent]) -> Cursor<Vec<u8>> {
+        Cursor::new(events.iter().flat_map(|event| event.to_bytes()).collect())
+    }
+
+    fn read(mapping: JoydevMapping, events: &[JsEvent]) -> (JoydevBackend, GamepadId) {
+        let mut backend = JoydevBackend::from_streams().with_mapping(mapping);
+        let id = backend.add_stream(stream(events), "Pad");
+        backend.update().unwrap();
+
+        (backend, id)
+    }
+
+    #[test]
+    fn js_event_round_trip() {
+        let event = JsEvent::axis(123_456, 3, -1234).with_init();
+        let bytes = event.to_bytes();
+
+        assert_eq!(JsEvent::from_bytes(&bytes), Some(event));
+        assert_eq!(JsEvent::from_bytes(&bytes[..JsEvent::SIZE - 1]), None);
+
+        // Laid out like the kernel struct, in native byte order
+        assert_eq!(&bytes[..4], &123_456u32.to_ne_bytes()[..]);
+        assert_eq!(&bytes[4..6], &(-1234i16).to_ne_bytes()[..]);
+        assert_eq!(bytes[6], JS_EVENT_AXIS | JS_EVENT_INIT);
+        assert_eq!(bytes[7], 3);
+    }
+
+    #[test]
+    fn event_kinds() {
+        let button = JsEvent::button(0, 1, true);
+        assert!(button.is_button() && !button.is_axis() && !button.is_init());
+        assert_eq!(button.value(), 1);
+
+        let init = button.with_init();
+        assert!(init.is_button() && init.is_init());
+        assert_eq!(init.kind(), JS_EVENT_BUTTON | JS_EVENT_INIT);
+
+        let axis = JsEvent::axis(0, 2, 100).with_init();
+        assert!(axis.is_axis() && !axis.is_button() && axis.is_init());
+    }
+
+    #[test]
+    fn init_events_set_the_initial_state() {
+        let (backend, id) = read(
+            JoydevMapping::default(),
+            &[
+                JsEvent::button(0, 0, true).with_init(),
+                JsEvent::button(0, 1, false).with_init(),
+                JsEvent::axis(0, 0, JS_AXIS_MAX).with_init(),
+                JsEvent::axis(0, 1, 0).with_init(),
+            ],
+        );
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(gamepad.is_pressed(Button::South));
+        assert!(!gamepad.is_pressed(Button::East));
+        assert_eq!(gamepad.joystick(Joystick::Left), (1.0, 0.0));
+
+        let device = &backend.joystick_devices()[&id];
+        assert_eq!(device.buttons().len(), 2);
+        assert_eq!(device.axes().len(), 2);
+    }
+
+    #[test]
+    fn later_events_replace_the_initial_state() {
+        let (backend, id) = read(
+            JoydevMapping::default(),
+            &[
+                JsEvent::button(0, 0, true).with_init(),
+                JsEvent::button(10, 0, false),
+                JsEvent::button(20, 1, true),
+            ],
+        );
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(!gamepad.is_pressed(Button::South));
+        assert!(gamepad.is_pressed(Button::East));
+    }
+
+    #[test]
+    fn default_mapping_follows_xpad() {
+        let (backend, id) = read(
+            JoydevMapping::default(),
+            &[
+                JsEvent::button(0, 7, true),
+                JsEvent::button(0, 12, true),
+                JsEvent::axis(0, 1, -JS_AXIS_MAX),
+                JsEvent::axis(0, 2, -JS_AXIS_MAX),
+                JsEvent::axis(0, 5, JS_AXIS_MAX),
+                JsEvent::axis(0, 6, -JS_AXIS_MAX),
+                JsEvent::axis(0, 7, JS_AXIS_MAX),
+            ],
+        );
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(gamepad.is_pressed(Button::Start));
+        assert!(gamepad.is_pressed(Button::Other(12)));
+
+        // Joydev reports up as negative, up is positive here
+        assert_eq!(gamepad.joystick(Joystick::Left), (0.0, 1.0));
+
+        // Triggers rest at -32767
+        assert_eq!(gamepad.button_value(Button::LeftTrigger), 0.0);
+        assert_eq!(gamepad.button_value(Button::RightTrigger), 1.0);
+        assert!(gamepad.is_pressed(Button::RightTrigger));
+
+        assert!(gamepad.is_pressed(Button::DPadWest));
+        assert!(gamepad.is_pressed(Button::DPadSouth));
+        assert!(!gamepad.is_pressed(Button::DPadNorth));
+        assert!(!gamepad.is_pressed(Button::DPadEast));
+
+        assert_eq!(gamepad.raw_axis(1), -1.0);
+        assert!(gamepad.raw_buttons()[&7].is_pressed());
+    }
+
+    #[test]
+    fn custom_mapping() {
+        let mapping = JoydevMapping::new()
+            .with_button(3, Button::South)
+            .with_axis(4, Axis::RightStickX);
+        assert_eq!(mapping.button(3), Some(Button::South));
+        assert_eq!(mapping.button(0), None);
+        assert_eq!(mapping.axis(4), Some(Axis::RightStickX));
+        assert_eq!(mapping.axes().len(), 1);
+
+        let events = [
+            JsEvent::button(0, 0, true),
+            JsEvent::button(0, 3, true),
+            JsEvent::axis(0, 0, JS_AXIS_MAX),
+            JsEvent::axis(0, 4, -JS_AXIS_MAX),
+        ];
+        let (mut backend, id) = read(mapping, &events);
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(gamepad.is_pressed(Button::South));
+        assert!(gamepad.is_pressed(Button::Other(0)));
+        assert_eq!(gamepad.joystick(Joystick::Right), (-1.0, 0.0));
+        assert_eq!(gamepad.joystick(Joystick::Left), (0.0, 0.0));
+
+        // A new mapping applies from the next update
+        backend.set_mapping(JoydevMapping::default());
+        backend.update().unwrap();
+
+        let gamepad = &backend.gamepads()[&id];
+        assert!(gamepad.is_pressed(Button::South));
+        assert!(gamepad.is_pressed(Button::North));
+        assert_eq!(gamepad.joystick(Joystick::Left), (1.0, 0.0));
+    }
+
+    #[test]
+    fn partial_records_are_not_applied() {
+        let bytes = JsEvent::button(0, 0, true).to_bytes();
+        let mut backend = JoydevBackend::from_streams();
+        let id = backend.add_stream(Cursor::new(bytes[..5].to_vec()), "Pad");
+        backend.update().unwrap();
+
+        assert!(!backend.gamepads()[&id].is_pressed(Button::South));
+        assert!(matches!(
+            backend.poll_events().as_slice(),
+            [GamepadEvent::Connected { id: connected, .. }] if *connected == id
+        ));
+    }
+}
@@ -1,33 +1,40 @@
-
 use crate::backends::GamepadEngineBackend;
 use crate::types::*;
 
+use std::collections::BTreeMap;
+
 // extern "C" {
 //     fn initiate_gamepad_update();
 // }
-  
+
 // #[no_mangle]
 // extern "C" fn update_gamepads() {
 // }
 
-
-pub(crate) struct WasmBackend {
-    gamepads: Vec<GamepadState>,
+/// Placeholder for the browser Gamepad API, reports no gamepads until the bindings are written.
+pub struct WasmBackend {
+    gamepads: BTreeMap<GamepadId, GamepadState>,
 }
 impl WasmBackend {
     pub fn new() -> Self {
         WasmBackend {
-            gamepads: Vec::new(),
+            gamepads: BTreeMap::new(),
         }
     }
 }
+impl Default for WasmBackend {
+    fn default() -> Self {
+        WasmBackend::new()
+    }
+}
 impl GamepadEngineBackend for WasmBackend {
-    fn gamepads(&self) -> &Vec<GamepadState> { &self.gamepads }
-    fn gamepads_mut(&mut self) -> &mut Vec<GamepadState> { &mut self.gamepads }
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
+    }
 }
 
 /// https://developer.mozilla.org/en-US/docs/Games/Techniques/Controls_Gamepad_API
-/// 
+///
 /// The buttons array contains the Xbox 360 button layout:
 /// buttons: [
 ///   'DPad-Up','DPad-Down','DPad-Left','DPad-Right',
@@ -48,4 +55,3 @@ const BUTTON_SOUTH_INDEX: usize = 10;
 const BUTTON_EAST_INDEX: usize = 11;
 const BUTTON_WEST_INDEX: usize = 12;
 const BUTTON_NORTH_INDEX: usize = 13;
-
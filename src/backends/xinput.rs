@@ -1,6 +1,8 @@
 use crate::backends::GamepadEngineBackend;
 use crate::types::*;
 
+use std::collections::BTreeMap;
+
 use winapi::{
     shared::{
         minwindef::DWORD,
@@ -9,13 +11,41 @@ use winapi::{
     um::xinput::*,
 };
 
-pub(crate) struct XInputBackend {
-    gamepads: Vec<GamepadState>,
+const XINPUT_GAMEPAD_NAME: &str = "XInput Controller";
+
+/// The `wButtons` bits that are mapped to a `Button`
+const XINPUT_KNOWN_BUTTONS: u16 = XINPUT_GAMEPAD_DPAD_UP
+    | XINPUT_GAMEPAD_DPAD_DOWN
+    | XINPUT_GAMEPAD_DPAD_LEFT
+    | XINPUT_GAMEPAD_DPAD_RIGHT
+    | XINPUT_GAMEPAD_START
+    | XINPUT_GAMEPAD_BACK
+    | XINPUT_GAMEPAD_LEFT_THUMB
+    | XINPUT_GAMEPAD_RIGHT_THUMB
+    | XINPUT_GAMEPAD_LEFT_SHOULDER
+    | XINPUT_GAMEPAD_RIGHT_SHOULDER
+    | XINPUT_GAMEPAD_A
+    | XINPUT_GAMEPAD_B
+    | XINPUT_GAMEPAD_X
+    | XINPUT_GAMEPAD_Y;
+
+/// XInput does not expose device ids, SDL identifies these gamepads by the "xinput" marker
+const XINPUT_GUID: Guid = Guid([
+    b'x', b'i', b'n', b'p', b'u', b't', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+]);
+
+/// Reads gamepads through the Windows XInput API, which supports up to four Xbox gamepads.
+pub struct XInputBackend {
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    gamepad_infos: BTreeMap<GamepadId, GamepadInfo>,
+    events: Vec<GamepadEvent>,
 }
 impl XInputBackend {
     pub fn new() -> Self {
         XInputBackend {
-            gamepads: Vec::new(),
+            gamepads: BTreeMap::new(),
+            gamepad_infos: BTreeMap::new(),
+            events: Vec::new(),
         }
     }
 
@@ -36,53 +66,84 @@ impl XInputBackend {
         }
     }
 }
+impl Default for XInputBackend {
+    fn default() -> Self {
+        XInputBackend::new()
+    }
+}
 
 impl GamepadEngineBackend for XInputBackend {
     fn update(&mut self) -> Result<(), GamepadError> {
-        let mut gamepads = Vec::new();
+        let mut gamepads = BTreeMap::new();
 
+        // XInput slots are fixed per controller, so the slot doubles as the gamepad id
         for i in 0..XUSER_MAX_COUNT {
             match self.get_input_state(i) {
-                Ok(state) => gamepads.push(state.to_gamepad()),
+                Ok(state) => {
+                    gamepads.insert(GamepadId(i as usize), state.to_gamepad());
+                }
                 Err(e) => {
                     match e.error_type {
                         ErrorType::GamepadNotConnected { slot: _ } => {} // We can ignore these for now, this will happen every frame where there is an empty gamepad slot
-                        ErrorType::Unknown => return Err(e),
+                        _ => return Err(e),
                     }
                 }
             }
         }
 
+        // XInput has no connection events, they are derived from the occupied slots
+        for id in gamepads.keys() {
+            if !self.gamepads.contains_key(id) {
+                self.events.push(GamepadEvent::Connected {
+                    id: *id,
+                    name: XINPUT_GAMEPAD_NAME.to_string(),
+                });
+            }
+        }
+        for id in self.gamepads.keys() {
+            if !gamepads.contains_key(id) {
+                self.events.push(GamepadEvent::Disconnected {
+                    id: *id,
+                    name: XINPUT_GAMEPAD_NAME.to_string(),
+                });
+            }
+        }
+
         // Enter the previous gamepads' states for the new gamepads
-        for i in 0..self.gamepads.len() {
-            if let Some(prev_gamepad) = self.gamepads.get(i) {
-                if let Some(new_gamepad) = gamepads.get_mut(i) {
-                    for (button, button_state) in prev_gamepad.buttons() {
-                        let state = new_gamepad
-                            .buttons
-                            .entry(button.clone())
-                            .or_insert(ButtonState::default());
-                        state.was_pressed = button_state.is_pressed;
-                    }
+        for (id, prev_gamepad) in self.gamepads.iter() {
+            if let Some(new_gamepad) = gamepads.get_mut(id) {
+                for (button, button_state) in prev_gamepad.buttons() {
+                    let state = new_gamepad
+                        .buttons
+                        .entry(*button)
+                        .or_insert_with(ButtonState::default);
+                    state.was_pressed = button_state.is_pressed;
                 }
             }
         }
 
+        self.gamepad_infos = gamepads
+            .keys()
+            .map(|id| {
+                let info = GamepadInfo::new(XINPUT_GAMEPAD_NAME, XINPUT_GUID, PowerInfo::Unknown);
+                (*id, info)
+            })
+            .collect();
         self.gamepads = gamepads;
 
         Ok(())
     }
 
     fn poll_events(&mut self) -> Vec<GamepadEvent> {
-        Vec::new()
+        std::mem::take(&mut self.events)
     }
 
-    fn gamepads(&self) -> &Vec<GamepadState> {
-        &self.gamepads
+    fn gamepad_info(&self, id: GamepadId) -> Option<&GamepadInfo> {
+        self.gamepad_infos.get(&id)
     }
 
-    fn gamepads_mut(&mut self) -> &mut Vec<GamepadState> {
-        &mut self.gamepads
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
     }
 }
 
@@ -131,8 +192,21 @@ impl XInputState {
     pub fn to_gamepad(self) -> GamepadState {
         let mut gamepad = GamepadState::new();
         let joysticks = &mut gamepad.joysticks;
-        joysticks.insert(Joystick::Left, JoystickState::new(self.left_stick_raw(), self.left_stick_normalized()));
-        joysticks.insert(Joystick::Right, JoystickState::new(self.right_stick_raw(), self.right_stick_normalized()));
+        // Deadzones are applied by the engine, so the sticks are reported without one
+        joysticks.insert(
+            Joystick::Left,
+            JoystickState::new(
+                self.left_stick_raw(),
+                XInputState::normalize_raw_stick_value(self.left_stick_raw(), 0),
+            ),
+        );
+        joysticks.insert(
+            Joystick::Right,
+            JoystickState::new(
+                self.right_stick_raw(),
+                XInputState::normalize_raw_stick_value(self.right_stick_raw(), 0),
+            ),
+        );
 
         let buttons = &mut gamepad.buttons;
         buttons.insert(Button::DPadNorth, ButtonState::new(self.arrow_up(), false));
@@ -157,7 +231,12 @@ impl XInputState {
         );
         buttons.insert(
             Button::LeftTrigger,
-            ButtonState::new(self.left_trigger_bool(), false),
+            ButtonState::analog(
+                self.left_trigger() as f32 / u8::MAX as f32,
+                self.left_trigger() as u16,
+                XInputState::TRIGGER_THRESHOLD as f32 / u8::MAX as f32,
+                false,
+            ),
         );
 
         buttons.insert(
@@ -166,7 +245,12 @@ impl XInputState {
         );
         buttons.insert(
             Button::RightTrigger,
-            ButtonState::new(self.right_trigger_bool(), false),
+            ButtonState::analog(
+                self.right_trigger() as f32 / u8::MAX as f32,
+                self.right_trigger() as u16,
+                XInputState::TRIGGER_THRESHOLD as f32 / u8::MAX as f32,
+                false,
+            ),
         );
 
         buttons.insert(
@@ -183,7 +267,30 @@ impl XInputState {
             ButtonState::new(self.select_button(), false),
         );
         buttons.insert(Button::Start, ButtonState::new(self.start_button(), false));
-        buttons.insert(Button::Menu, ButtonState::new(false, false));
+
+        // The raw codes are the bits of `wButtons`, bits without a name are passed through
+        for bit in 0..16 {
+            let code = 1u16 << bit;
+            let button_state = ButtonState::new(self.raw.Gamepad.wButtons & code != 0, false);
+
+            if code & XINPUT_KNOWN_BUTTONS == 0 {
+                gamepad
+                    .buttons
+                    .insert(Button::Other(code as u32), button_state.clone());
+            }
+            gamepad.raw_buttons.insert(code as u32, button_state);
+        }
+
+        // The raw axis codes follow the order of the fields in `XINPUT_GAMEPAD`
+        let (left_x, left_y) = self.left_stick_raw();
+        let (right_x, right_y) = self.right_stick_raw();
+        let raw_axes = &mut gamepad.raw_axes;
+        raw_axes.insert(0, self.left_trigger() as f32 / u8::MAX as f32);
+        raw_axes.insert(1, self.right_trigger() as f32 / u8::MAX as f32);
+        raw_axes.insert(2, left_x as f32 / i16::MAX as f32);
+        raw_axes.insert(3, left_y as f32 / i16::MAX as f32);
+        raw_axes.insert(4, right_x as f32 / i16::MAX as f32);
+        raw_axes.insert(5, right_y as f32 / i16::MAX as f32);
 
         gamepad
     }
@@ -0,0 +1,329 @@
+use crate::types::*;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Maps the raw values of a single axis to the range -1.0 to 1.0.
+///
+/// All values are in the device units reported by `JoystickState::raw_value`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisCalibration {
+    pub(crate) center: i16,
+    pub(crate) min: i16,
+    pub(crate) max: i16,
+}
+impl AxisCalibration {
+    /// Creates a calibration, `center` is clamped to the range `min..=max`
+    pub fn new(center: i16, min: i16, max: i16) -> Self {
+        let (min, max) = (min.min(max), min.max(max));
+
+        AxisCalibration {
+            center: center.clamp(min, max),
+            min,
+            max,
+        }
+    }
+
+    pub fn center(&self) -> i16 {
+        self.center
+    }
+
+    pub fn min(&self) -> i16 {
+        self.min
+    }
+
+    pub fn max(&self) -> i16 {
+        self.max
+    }
+
+    /// Normalizes a raw value, the center maps to 0.0 while `min` and `max` map to -1.0 and 1.0
+    pub fn apply(&self, raw_value: i16) -> f32 {
+        let offset = raw_value as f32 - self.center as f32;
+        let span = if offset < 0.0 {
+            self.center as f32 - self.min as f32
+        } else {
+            self.max as f32 - self.center as f32
+        };
+
+        if span > 0.0 {
+            (offset / span).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The calibration of both axes of a joystick.
+///
+/// Calibrations can be stored as text, they are written as `center,min,max;center,min,max`
+/// for the x and y axis through `Display` and read back through `FromStr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoystickCalibration {
+    pub(crate) x: AxisCalibration,
+    pub(crate) y: AxisCalibration,
+}
+impl JoystickCalibration {
+    pub fn new(x: AxisCalibration, y: AxisCalibration) -> Self {
+        JoystickCalibration { x, y }
+    }
+
+    pub fn x(&self) -> AxisCalibration {
+        self.x
+    }
+
+    pub fn y(&self) -> AxisCalibration {
+        self.y
+    }
+
+    pub fn apply(&self, raw_value: (i16, i16)) -> (f32, f32) {
+        (self.x.apply(raw_value.0), self.y.apply(raw_value.1))
+    }
+}
+impl fmt::Display for JoystickCalibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{};{},{},{}",
+            self.x.center, self.x.min, self.x.max, self.y.center, self.y.min, self.y.max
+        )
+    }
+}
+impl FromStr for JoystickCalibration {
+    type Err = GamepadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            GamepadError::new(
+                format!("Invalid joystick calibration: {}", s),
+                ErrorType::InvalidCalibration,
+            )
+        };
+
+        let axes = s
+            .trim()
+            .split(';')
+            .map(|axis| {
+                let values = axis
+                    .split(',')
+                    .map(|value| value.trim().parse::<i16>())
+                    .collect::<Result<Vec<i16>, _>>()
+                    .map_err(|_| invalid())?;
+
+                match values.as_slice() {
+                    [center, min, max] => Ok(AxisCalibration::new(*center, *min, *max)),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect::<Result<Vec<AxisCalibration>, GamepadError>>()?;
+
+        match axes.as_slice() {
+            [x, y] => Ok(JoystickCalibration::new(*x, *y)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Learns the calibration of every joystick of a gamepad from the samples it is given.
+///
+/// The first samples are averaged to find the center, so the sticks should be left at rest
+/// when a capture begins and then moved along their full range before it is finished.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CalibrationCapture {
+    joysticks: HashMap<Joystick, JoystickCapture>,
+}
+impl CalibrationCapture {
+    /// The number of samples averaged to find the center of a joystick
+    pub const CENTER_SAMPLE_COUNT: u32 = 8;
+
+    pub fn new() -> Self {
+        CalibrationCapture {
+            joysticks: HashMap::new(),
+        }
+    }
+
+    /// Records the raw value of every joystick of the gamepad
+    pub fn sample(&mut self, gamepad: &GamepadState) {
+        for (joystick, joystick_state) in gamepad.joysticks() {
+            self.sample_joystick(*joystick, joystick_state.raw_value);
+        }
+    }
+
+    pub fn sample_joystick(&mut self, joystick: Joystick, raw_value: (i16, i16)) {
+        self.joysticks
+            .entry(joystick)
+            .or_insert_with(|| JoystickCapture::new(raw_value))
+            .sample(raw_value);
+    }
+
+    /// The calibration learned so far, `None` for joysticks that were never sampled
+    pub fn calibration(&self, joystick: Joystick) -> Option<JoystickCalibration> {
+        self.joysticks
+            .get(&joystick)
+            .map(|capture| capture.calibration())
+    }
+
+    /// The calibrations learned for every sampled joystick
+    pub fn finish(&self) -> HashMap<Joystick, JoystickCalibration> {
+        self.joysticks
+            .iter()
+            .map(|(joystick, capture)| (*joystick, capture.calibration()))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct JoystickCapture {
+    center_sum: (i64, i64),
+    center_samples: u32,
+    min: (i16, i16),
+    max: (i16, i16),
+}
+impl JoystickCapture {
+    fn new(raw_value: (i16, i16)) -> Self {
+        JoystickCapture {
+            center_sum: (0, 0),
+            center_samples: 0,
+            min: raw_value,
+            max: raw_value,
+        }
+    }
+
+    fn sample(&mut self, raw_value: (i16, i16)) {
+        if self.center_samples < CalibrationCapture::CENTER_SAMPLE_COUNT {
+            self.center_sum.0 += raw_value.0 as i64;
+            self.center_sum.1 += raw_value.1 as i64;
+            self.center_samples += 1;
+        }
+
+        self.min = (self.min.0.min(raw_value.0), self.min.1.min(raw_value.1));
+        self.max = (self.max.0.max(raw_value.0), self.max.1.max(raw_value.1));
+    }
+
+    fn calibration(&self) -> JoystickCalibration {
+        let samples = self.center_samples.max(1) as i64;
+        let center = (
+            (self.center_sum.0 / samples) as i16,
+            (self.center_sum.1 / samples) as i16,
+        );
+
+        JoystickCalibration::new(
+            AxisCalibration::new(center.0, self.min.0, self.max.0),
+            AxisCalibration::new(center.1, self.min.1, self.max.1),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_uses_the_span_of_each_side() {
+        let calibration = AxisCalibration::new(100, -900, 1100);
+
+        assert_eq!(calibration.apply(100), 0.0);
+        assert_eq!(calibration.apply(-900), -1.0);
+        assert_eq!(calibration.apply(1100), 1.0);
+        assert_eq!(calibration.apply(-400), -0.5);
+        assert_eq!(calibration.apply(600), 0.5);
+        assert_eq!(calibration.apply(i16::MIN), -1.0);
+        assert_eq!(calibration.apply(i16::MAX), 1.0);
+    }
+
+    #[test]
+    fn new_sorts_the_range_and_clamps_the_center() {
+        let calibration = AxisCalibration::new(5000, 1000, -1000);
+
+        assert_eq!(calibration.min(), -1000);
+        assert_eq!(calibration.max(), 1000);
+        assert_eq!(calibration.center(), 1000);
+        assert_eq!(calibration.apply(1000), 0.0);
+        assert_eq!(calibration.apply(0), -0.5);
+        assert_eq!(AxisCalibration::new(0, 0, 0).apply(10), 0.0);
+    }
+
+    #[test]
+    fn capture_averages_the_first_samples_as_center() {
+        let mut capture = CalibrationCapture::new();
+        assert_eq!(capture.calibration(Joystick::Left), None);
+
+        for i in 0..CalibrationCapture::CENTER_SAMPLE_COUNT as i16 {
+            capture.sample_joystick(Joystick::Left, (10 + i, -10 - i));
+        }
+        // Later samples only widen the range
+        capture.sample_joystick(Joystick::Left, (30000, -30000));
+        capture.sample_joystick(Joystick::Left, (-25000, 28000));
+
+        let calibration = capture.calibration(Joystick::Left).unwrap();
+        assert_eq!(calibration.x(), AxisCalibration::new(13, -25000, 30000));
+        assert_eq!(calibration.y(), AxisCalibration::new(-13, -30000, 28000));
+        assert_eq!(capture.calibration(Joystick::Right), None);
+    }
+
+    #[test]
+    fn capture_samples_every_joystick() {
+        let mut gamepad = GamepadState::new();
+        gamepad.joysticks.insert(
+            Joystick::Right,
+            JoystickState::from_raw((5, -5), (-100, 100)),
+        );
+
+        let mut capture = CalibrationCapture::new();
+        capture.sample(&gamepad);
+
+        let calibrations = capture.finish();
+        assert_eq!(calibrations.len(), 1);
+        assert_eq!(
+            calibrations[&Joystick::Right],
+            JoystickCalibration::new(
+                AxisCalibration::new(5, 5, 5),
+                AxisCalibration::new(-5, -5, -5)
+            )
+        );
+    }
+
+    #[test]
+    fn display_and_from_str_round_trip() {
+        let calibration = JoystickCalibration::new(
+            AxisCalibration::new(-12, -32000, 31000),
+            AxisCalibration::new(40, -29000, 32767),
+        );
+
+        let text = calibration.to_string();
+        assert_eq!(text, "-12,-32000,31000;40,-29000,32767");
+        assert_eq!(text.parse::<JoystickCalibration>().unwrap(), calibration);
+        assert_eq!(
+            " 0, -10 ,10;1,-5,5 "
+                .parse::<JoystickCalibration>()
+                .unwrap(),
+            JoystickCalibration::new(
+                AxisCalibration::new(0, -10, 10),
+                AxisCalibration::new(1, -5, 5)
+            )
+        );
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_calibrations() {
+        for text in [
+            "",
+            "0,-1,1",
+            "0,-1,1;0,-1",
+            "0,-1,1;0,-1,x",
+            "0,-1,1;0,-1,1;0,-1,1",
+            "0,-1,40000;0,-1,1",
+        ] {
+            let error = text.parse::<JoystickCalibration>().unwrap_err();
+            assert!(
+                matches!(error.error_type, ErrorType::InvalidCalibration),
+                "{}",
+                text
+            );
+        }
+    }
+}
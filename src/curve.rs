@@ -0,0 +1,248 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Maps how far a joystick is tilted to how far the game sees it tilted.
+///
+/// Curves work on magnitudes from 0.0 to 1.0, the sign or direction of the input is kept.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// Raises the input to the given exponent, values above 1.0 give more precision near the center
+    Power(f32),
+    /// `(e^(k * x) - 1) / (e^k - 1)`, values of `k` above 0.0 give more precision near the center
+    Exponential(f32),
+    /// Slow near the center and near the edge, fast in between. The value is the steepness,
+    /// 1.0 is linear.
+    SCurve(f32),
+    /// Linear interpolation between `(input, output)` points, see `ResponseCurve::piecewise`
+    Piecewise(Vec<(f32, f32)>),
+    /// Can't be serialized, serializing it or any settings holding it fails with an error
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(Arc<dyn Fn(f32) -> f32 + Send + Sync>),
+}
+impl ResponseCurve {
+    /// Creates a piecewise linear curve through the given `(input, output)` points.
+    ///
+    /// Points are clamped to the range 0.0 to 1.0 and sorted by input, the curve starts
+    /// at `(0.0, 0.0)` and ends at `(1.0, 1.0)` unless points are given for those inputs.
+    pub fn piecewise(points: &[(f32, f32)]) -> Self {
+        let mut points = points
+            .iter()
+            .map(|(input, output)| (input.clamp(0.0, 1.0), output.clamp(0.0, 1.0)))
+            .collect::<Vec<(f32, f32)>>();
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        if points.first().map_or(true, |(input, _)| *input > 0.0) {
+            points.insert(0, (0.0, 0.0));
+        }
+        if points.last().map_or(true, |(input, _)| *input < 1.0) {
+            points.push((1.0, 1.0));
+        }
+
+        ResponseCurve::Piecewise(points)
+    }
+
+    /// Creates a curve from a function, its output is clamped to the range 0.0 to 1.0
+    pub fn custom<F: Fn(f32) -> f32 + Send + Sync + 'static>(curve: F) -> Self {
+        ResponseCurve::Custom(Arc::new(curve))
+    }
+
+    /// Evaluates the curve for a magnitude from 0.0 to 1.0
+    pub fn evaluate(&self, magnitude: f32) -> f32 {
+        let x = magnitude.clamp(0.0, 1.0);
+
+        let y = match self {
+            ResponseCurve::Linear => x,
+            ResponseCurve::Power(exponent) => x.powf(exponent.max(0.0)),
+            ResponseCurve::Exponential(k) => {
+                if k.abs() < f32::EPSILON {
+                    x
+                } else {
+                    (k * x).exp_m1() / k.exp_m1()
+                }
+            }
+            ResponseCurve::SCurve(steepness) => {
+                let a = x.powf(steepness.max(0.0));
+                let b = (1.0 - x).powf(steepness.max(0.0));
+                if a + b > 0.0 {
+                    a / (a + b)
+                } else {
+                    x
+                }
+            }
+            ResponseCurve::Piecewise(points) => interpolate(points, x),
+            ResponseCurve::Custom(curve) => curve(x),
+        };
+
+        if y.is_nan() {
+            0.0
+        } else {
+            y.clamp(0.0, 1.0)
+        }
+    }
+
+    /// Applies the curve to a single axis value from -1.0 to 1.0, keeping its sign
+    pub fn apply(&self, value: f32) -> f32 {
+        self.evaluate(value.abs()).copysign(value)
+    }
+
+    /// Applies the curve to the distance of a joystick from its center, keeping its direction
+    pub fn apply_radial(&self, value: (f32, f32)) -> (f32, f32) {
+        let length = (value.0 * value.0 + value.1 * value.1).sqrt();
+        if length == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let scale = self.evaluate(length) / length;
+        (value.0 * scale, value.1 * scale)
+    }
+}
+impl fmt::Debug for ResponseCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseCurve::Linear => write!(f, "Linear"),
+            ResponseCurve::Power(exponent) => f.debug_tuple("Power").field(exponent).finish(),
+            ResponseCurve::Exponential(k) => f.debug_tuple("Exponential").field(k).finish(),
+            ResponseCurve::SCurve(steepness) => f.debug_tuple("SCurve").field(steepness).finish(),
+            ResponseCurve::Piecewise(points) => f.debug_tuple("Piecewise").field(points).finish(),
+            ResponseCurve::Custom(_) => write!(f, "Custom(_)"),
+        }
+    }
+}
+
+fn interpolate(points: &[(f32, f32)], x: f32) -> f32 {
+    let mut previous = match points.first() {
+        Some(point) => *point,
+        None => return x,
+    };
+
+    for point in points.iter().skip(1) {
+        if x <= point.0 {
+            let span = point.0 - previous.0;
+            if span <= 0.0 {
+                return point.1;
+            }
+
+            return previous.1 + (x - previous.0) / span * (point.1 - previous.1);
+        }
+        previous = *point;
+    }
+
+    previous.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curves() -> Vec<ResponseCurve> {
+        vec![
+            ResponseCurve::Linear,
+            ResponseCurve::Power(2.0),
+            ResponseCurve::Power(0.5),
+            ResponseCurve::Exponential(3.0),
+            ResponseCurve::Exponential(-3.0),
+            ResponseCurve::Exponential(0.0),
+            ResponseCurve::SCurve(2.0),
+            ResponseCurve::piecewise(&[(0.5, 0.2)]),
+        ]
+    }
+
+    #[test]
+    fn curves_keep_their_endpoints() {
+        for curve in curves() {
+            assert!(curve.evaluate(0.0).abs() < 1e-6, "{:?}", curve);
+            assert!((curve.evaluate(1.0) - 1.0).abs() < 1e-6, "{:?}", curve);
+            // Inputs past the range are clamped
+            assert!(curve.evaluate(-0.5).abs() < 1e-6, "{:?}", curve);
+            assert!((curve.evaluate(2.0) - 1.0).abs() < 1e-6, "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for curve in curves() {
+            let mut previous = curve.evaluate(0.0);
+            for step in 1..=100 {
+                let value = curve.evaluate(step as f32 / 100.0);
+                assert!(value >= previous, "{:?} at {}", curve, step);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn curve_shapes() {
+        assert_eq!(ResponseCurve::Linear.evaluate(0.3), 0.3);
+        assert!((ResponseCurve::Power(2.0).evaluate(0.5) - 0.25).abs() < 1e-6);
+        assert!(ResponseCurve::Exponential(3.0).evaluate(0.5) < 0.5);
+        assert!(ResponseCurve::Exponential(-3.0).evaluate(0.5) > 0.5);
+        assert!((ResponseCurve::Exponential(0.0).evaluate(0.4) - 0.4).abs() < 1e-6);
+
+        // Symmetric around the center, slow at both ends
+        let s_curve = ResponseCurve::SCurve(2.0);
+        assert!((s_curve.evaluate(0.5) - 0.5).abs() < 1e-6);
+        assert!(s_curve.evaluate(0.1) < 0.1);
+        assert!(s_curve.evaluate(0.9) > 0.9);
+        assert!((ResponseCurve::SCurve(1.0).evaluate(0.3) - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn piecewise_inserts_the_endpoints() {
+        let curve = ResponseCurve::piecewise(&[(0.5, 0.2)]);
+        match &curve {
+            ResponseCurve::Piecewise(points) => {
+                assert_eq!(points, &vec![(0.0, 0.0), (0.5, 0.2), (1.0, 1.0)])
+            }
+            _ => panic!("{:?}", curve),
+        }
+        assert!((curve.evaluate(0.25) - 0.1).abs() < 1e-6);
+        assert!((curve.evaluate(0.75) - 0.6).abs() < 1e-6);
+
+        // Given endpoints are kept, points are sorted and clamped
+        let curve = ResponseCurve::piecewise(&[(1.0, 0.8), (-1.0, 0.1), (0.5, 0.5)]);
+        match &curve {
+            ResponseCurve::Piecewise(points) => {
+                assert_eq!(points, &vec![(0.0, 0.1), (0.5, 0.5), (1.0, 0.8)])
+            }
+            _ => panic!("{:?}", curve),
+        }
+        assert!((curve.evaluate(0.0) - 0.1).abs() < 1e-6);
+        assert!((curve.evaluate(1.0) - 0.8).abs() < 1e-6);
+
+        assert!((ResponseCurve::piecewise(&[]).evaluate(0.4) - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn apply_keeps_the_sign() {
+        let curve = ResponseCurve::Power(2.0);
+
+        assert!((curve.apply(-0.5) + 0.25).abs() < 1e-6);
+        assert!((curve.apply(0.5) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn apply_radial_keeps_the_direction() {
+        let curve = ResponseCurve::Power(2.0);
+        let (x, y) = curve.apply_radial((0.3, -0.4));
+
+        // The length 0.5 becomes 0.25 along the same direction
+        assert!(((x * x + y * y).sqrt() - 0.25).abs() < 1e-6);
+        assert!((x / y - 0.3 / -0.4).abs() < 1e-6);
+        assert!(x > 0.0 && y < 0.0);
+
+        assert_eq!(curve.apply_radial((0.0, 0.0)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn custom_curves_are_clamped() {
+        let curve = ResponseCurve::custom(|x| x * 2.0 - 0.5);
+
+        assert_eq!(curve.evaluate(0.0), 0.0);
+        assert_eq!(curve.evaluate(0.5), 0.5);
+        assert_eq!(curve.evaluate(1.0), 1.0);
+        assert_eq!(ResponseCurve::custom(|_| f32::NAN).evaluate(0.5), 0.0);
+    }
+}
@@ -0,0 +1,278 @@
+/// The shape of the area around the center of a joystick in which input is ignored.
+///
+/// See https://www.gamedeveloper.com/business/doing-thumbstick-dead-zones-right for a
+/// comparison of how each of them feels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeadzoneKind {
+    /// No deadzone, the value is only clamped to the outer radius
+    None,
+    /// Each axis is cut off on its own, this snaps to the cardinal directions
+    Axial,
+    /// The stick is cut off by its distance to the center, values past the inner radius
+    /// are passed through unchanged
+    Radial,
+    /// Like `Radial`, but the range from the inner to the outer radius is rescaled to
+    /// start at 0.0 so there is no jump when leaving the deadzone
+    ScaledRadial,
+    /// `ScaledRadial` followed by a sloped axial deadzone, which keeps the full range of
+    /// motion while making it easier to hold a cardinal direction
+    Hybrid,
+}
+
+/// A deadzone applied to the normalized value of a joystick.
+///
+/// `inner` is the radius below which the stick reads as centered, `outer` is the radius at
+/// which the stick reads as fully tilted. Both are in normalized units from 0.0 to 1.0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Deadzone {
+    pub(crate) kind: DeadzoneKind,
+    pub(crate) inner: f32,
+    pub(crate) outer: f32,
+}
+impl Deadzone {
+    /// The inner radius used by default, this matches the XInput recommended deadzone
+    pub const DEFAULT_INNER_RADIUS: f32 = 0.24;
+
+    /// The outer radius used by default
+    pub const DEFAULT_OUTER_RADIUS: f32 = 1.0;
+
+    /// Creates a deadzone, both radii are clamped to the range 0.0 to 1.0
+    /// and the outer radius is always larger than the inner radius. A NaN radius is
+    /// replaced by its default.
+    pub fn new(kind: DeadzoneKind, inner: f32, outer: f32) -> Self {
+        let inner = if inner.is_nan() {
+            Deadzone::DEFAULT_INNER_RADIUS
+        } else {
+            inner.clamp(0.0, 1.0 - f32::EPSILON)
+        };
+        let outer = if outer.is_nan() {
+            Deadzone::DEFAULT_OUTER_RADIUS
+        } else {
+            outer
+        };
+
+        Deadzone {
+            kind,
+            inner,
+            outer: outer.clamp(inner + f32::EPSILON, 1.0),
+        }
+    }
+
+    pub fn none() -> Self {
+        Deadzone::new(DeadzoneKind::None, 0.0, 1.0)
+    }
+
+    pub fn axial(inner: f32, outer: f32) -> Self {
+        Deadzone::new(DeadzoneKind::Axial, inner, outer)
+    }
+
+    pub fn radial(inner: f32, outer: f32) -> Self {
+        Deadzone::new(DeadzoneKind::Radial, inner, outer)
+    }
+
+    pub fn scaled_radial(inner: f32, outer: f32) -> Self {
+        Deadzone::new(DeadzoneKind::ScaledRadial, inner, outer)
+    }
+
+    pub fn hybrid(inner: f32, outer: f32) -> Self {
+        Deadzone::new(DeadzoneKind::Hybrid, inner, outer)
+    }
+
+    pub fn kind(&self) -> DeadzoneKind {
+        self.kind
+    }
+
+    pub fn inner(&self) -> f32 {
+        self.inner
+    }
+
+    pub fn outer(&self) -> f32 {
+        self.outer
+    }
+
+    /// Applies the deadzone to a normalized joystick value.
+    ///
+    /// The result always lies within the unit circle.
+    pub fn apply(&self, value: (f32, f32)) -> (f32, f32) {
+        let value = (value.0.clamp(-1.0, 1.0), value.1.clamp(-1.0, 1.0));
+
+        let result = match self.kind {
+            DeadzoneKind::None => (value.0 / self.outer, value.1 / self.outer),
+            DeadzoneKind::Axial => (
+                self.apply_axial(value.0, self.inner),
+                self.apply_axial(value.1, self.inner),
+            ),
+            DeadzoneKind::Radial => {
+                if length(value) < self.inner {
+                    (0.0, 0.0)
+                } else {
+                    (value.0 / self.outer, value.1 / self.outer)
+                }
+            }
+            DeadzoneKind::ScaledRadial => self.apply_scaled_radial(value),
+            DeadzoneKind::Hybrid => {
+                let scaled = self.apply_scaled_radial(value);
+
+                (
+                    rescale(scaled.0, self.inner * scaled.1.abs(), 1.0),
+                    rescale(scaled.1, self.inner * scaled.0.abs(), 1.0),
+                )
+            }
+        };
+
+        clamp_to_unit_circle(result)
+    }
+
+    fn apply_axial(&self, value: f32, inner: f32) -> f32 {
+        if value.abs() < inner {
+            0.0
+        } else {
+            (value / self.outer).clamp(-1.0, 1.0)
+        }
+    }
+
+    fn apply_scaled_radial(&self, value: (f32, f32)) -> (f32, f32) {
+        let length = length(value);
+        if length < self.inner || length == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let scale = rescale(length, self.inner, self.outer) / length;
+        (value.0 * scale, value.1 * scale)
+    }
+}
+impl Default for Deadzone {
+    fn default() -> Deadzone {
+        Deadzone::scaled_radial(
+            Deadzone::DEFAULT_INNER_RADIUS,
+            Deadzone::DEFAULT_OUTER_RADIUS,
+        )
+    }
+}
+
+fn length(value: (f32, f32)) -> f32 {
+    (value.0 * value.0 + value.1 * value.1).sqrt()
+}
+
+/// Maps the magnitude of `value` from the range `inner..outer` to `0.0..1.0`, keeping its sign
+fn rescale(value: f32, inner: f32, outer: f32) -> f32 {
+    let magnitude = value.abs();
+    if magnitude < inner {
+        return 0.0;
+    }
+
+    let scaled = if outer > inner {
+        ((magnitude - inner) / (outer - inner)).min(1.0)
+    } else {
+        1.0
+    };
+
+    scaled.copysign(value)
+}
+
+fn clamp_to_unit_circle(value: (f32, f32)) -> (f32, f32) {
+    let length = length(value);
+    if length > 1.0 {
+        (value.0 / length, value.1 / length)
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-5 && (actual.1 - expected.1).abs() < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn outer_radius_is_larger_than_inner_radius() {
+        let deadzone = Deadzone::new(DeadzoneKind::ScaledRadial, 0.0, 0.0);
+        assert!(deadzone.outer() > deadzone.inner());
+
+        let deadzone = Deadzone::new(DeadzoneKind::Radial, 1.0, 0.5);
+        assert!(deadzone.outer() > deadzone.inner());
+        assert!(deadzone.outer() <= 1.0);
+    }
+
+    #[test]
+    fn nan_radii_are_replaced_by_the_defaults() {
+        let deadzone = Deadzone::new(DeadzoneKind::Radial, f32::NAN, f32::NAN);
+        assert_eq!(deadzone.inner(), Deadzone::DEFAULT_INNER_RADIUS);
+        assert_eq!(deadzone.outer(), Deadzone::DEFAULT_OUTER_RADIUS);
+
+        let deadzone = Deadzone::new(DeadzoneKind::Radial, 0.1, f32::NAN);
+        assert_eq!(deadzone.inner(), 0.1);
+        assert_eq!(deadzone.outer(), Deadzone::DEFAULT_OUTER_RADIUS);
+        assert!(deadzone.apply((0.5, 0.0)).0.is_finite());
+    }
+
+    #[test]
+    fn zero_span_stays_finite() {
+        let kinds = [
+            DeadzoneKind::None,
+            DeadzoneKind::Axial,
+            DeadzoneKind::Radial,
+            DeadzoneKind::ScaledRadial,
+            DeadzoneKind::Hybrid,
+        ];
+        let values = [(0.0, 0.0), (0.5, -0.5), (1.0, 0.0), (-0.3, 0.9)];
+
+        for kind in kinds.iter() {
+            for inner in [0.0, 0.5, 1.0].iter() {
+                let deadzone = Deadzone::new(*kind, *inner, *inner);
+                for value in values.iter() {
+                    let (x, y) = deadzone.apply(*value);
+                    assert!(x.is_finite() && y.is_finite(), "{:?} {:?}", deadzone, value);
+                    assert!(length((x, y)) <= 1.0 + 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn radial_passes_values_past_inner_radius() {
+        let deadzone = Deadzone::radial(0.2, 1.0);
+
+        assert_close(deadzone.apply((0.1, 0.1)), (0.0, 0.0));
+        assert_close(deadzone.apply((0.3, 0.0)), (0.3, 0.0));
+        assert_close(deadzone.apply((0.0, -0.8)), (0.0, -0.8));
+    }
+
+    #[test]
+    fn axial_cuts_each_axis() {
+        let deadzone = Deadzone::axial(0.2, 1.0);
+
+        assert_close(deadzone.apply((0.1, 0.5)), (0.0, 0.5));
+        assert_close(deadzone.apply((-0.5, 0.15)), (-0.5, 0.0));
+        assert_close(deadzone.apply((0.19, -0.19)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn scaled_radial_rescales_from_inner_to_outer() {
+        let deadzone = Deadzone::scaled_radial(0.2, 0.8);
+
+        assert_close(deadzone.apply((0.1, 0.0)), (0.0, 0.0));
+        assert_close(deadzone.apply((0.5, 0.0)), (0.5, 0.0));
+        assert_close(deadzone.apply((0.0, -0.8)), (0.0, -1.0));
+        assert_close(deadzone.apply((0.9, 0.0)), (1.0, 0.0));
+        assert_close(deadzone.apply((0.3, 0.4)), (0.3, 0.4));
+    }
+
+    #[test]
+    fn output_stays_in_unit_circle() {
+        let deadzone = Deadzone::none();
+        let (x, y) = deadzone.apply((1.0, 1.0));
+
+        assert!((length((x, y)) - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::types::*;
+
+/// The direction a hat switch, such as a D-pad, is pushed in
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HatDirection {
+    #[default]
+    Centered,
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+impl HatDirection {
+    /// Creates the direction from the state of four direction buttons,
+    /// opposing buttons held at the same time cancel each other out
+    pub fn from_buttons(north: bool, south: bool, west: bool, east: bool) -> Self {
+        let x = east as i8 - west as i8;
+        let y = north as i8 - south as i8;
+
+        HatDirection::from_signs(x, y)
+    }
+
+    /// Creates the direction from a pair of axes, positive values are to the right (x-axis)
+    /// or up (y-axis). Values within `threshold` of the center count as centered.
+    pub fn from_axes(x: f32, y: f32, threshold: f32) -> Self {
+        let sign = |value: f32| {
+            if value > threshold {
+                1
+            } else if value < -threshold {
+                -1
+            } else {
+                0
+            }
+        };
+
+        HatDirection::from_signs(sign(x), sign(y))
+    }
+
+    fn from_signs(x: i8, y: i8) -> Self {
+        match (x, y) {
+            (0, 1) => HatDirection::North,
+            (1, 1) => HatDirection::NorthEast,
+            (1, 0) => HatDirection::East,
+            (1, -1) => HatDirection::SouthEast,
+            (0, -1) => HatDirection::South,
+            (-1, -1) => HatDirection::SouthWest,
+            (-1, 0) => HatDirection::West,
+            (-1, 1) => HatDirection::NorthWest,
+            _ => HatDirection::Centered,
+        }
+    }
+
+    /// The direction as a pair of axes from -1 to 1, positive values are to the right
+    /// (x-axis) or up (y-axis)
+    pub fn to_axes(&self) -> (i8, i8) {
+        match self {
+            HatDirection::Centered => (0, 0),
+            HatDirection::North => (0, 1),
+            HatDirection::NorthEast => (1, 1),
+            HatDirection::East => (1, 0),
+            HatDirection::SouthEast => (1, -1),
+            HatDirection::South => (0, -1),
+            HatDirection::SouthWest => (-1, -1),
+            HatDirection::West => (-1, 0),
+            HatDirection::NorthWest => (-1, 1),
+        }
+    }
+
+    pub fn is_north(&self) -> bool {
+        self.to_axes().1 > 0
+    }
+
+    pub fn is_south(&self) -> bool {
+        self.to_axes().1 < 0
+    }
+
+    pub fn is_west(&self) -> bool {
+        self.to_axes().0 < 0
+    }
+
+    pub fn is_east(&self) -> bool {
+        self.to_axes().0 > 0
+    }
+}
+
+/// A single axis of a `JoystickDevice` together with the range the device declares for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceAxis {
+    pub(crate) raw_value: i32,
+    pub(crate) min: i32,
+    pub(crate) max: i32,
+}
+impl DeviceAxis {
+    pub fn new(raw_value: i32, min: i32, max: i32) -> Self {
+        DeviceAxis {
+            raw_value,
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    /// The value in device units as reported by the backend
+    pub fn raw_value(&self) -> i32 {
+        self.raw_value
+    }
+
+    /// The inclusive range the device reports values in
+    pub fn range(&self) -> (i32, i32) {
+        (self.min, self.max)
+    }
+
+    /// The value scaled from the declared range to -1.0 to 1.0, as suited for sticks and wheels
+    pub fn value(&self) -> f32 {
+        self.unipolar_value() * 2.0 - 1.0
+    }
+
+    /// The value scaled from the declared range to 0.0 to 1.0, as suited for pedals and throttles
+    pub fn unipolar_value(&self) -> f32 {
+        let span = self.max as f64 - self.min as f64;
+        if span <= 0.0 {
+            return 0.0;
+        }
+
+        ((self.raw_value as f64 - self.min as f64) / span).clamp(0.0, 1.0) as f32
+    }
+}
+
+/// A device of any shape, such as a racing wheel, flight stick or pedal set, read as
+/// numbered axes, buttons and hats.
+///
+/// Elements are numbered in the order the backend enumerates them, which follows the
+/// device's own element codes.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoystickDevice {
+    pub(crate) axes: Vec<DeviceAxis>,
+    pub(crate) buttons: Vec<ButtonState>,
+    pub(crate) hats: Vec<HatDirection>,
+}
+impl JoystickDevice {
+    pub fn new(axes: Vec<DeviceAxis>, buttons: Vec<ButtonState>, hats: Vec<HatDirection>) -> Self {
+        JoystickDevice {
+            axes,
+            buttons,
+            hats,
+        }
+    }
+
+    pub fn axes(&self) -> &[DeviceAxis] {
+        &self.axes
+    }
+
+    pub fn axes_mut(&mut self) -> &mut Vec<DeviceAxis> {
+        &mut self.axes
+    }
+
+    pub fn buttons(&self) -> &[ButtonState] {
+        &self.buttons
+    }
+
+    pub fn buttons_mut(&mut self) -> &mut Vec<ButtonState> {
+        &mut self.buttons
+    }
+
+    pub fn hats(&self) -> &[HatDirection] {
+        &self.hats
+    }
+
+    pub fn hats_mut(&mut self) -> &mut Vec<HatDirection> {
+        &mut self.hats
+    }
+
+    pub fn axis(&self, index: usize) -> Option<&DeviceAxis> {
+        self.axes.get(index)
+    }
+
+    pub fn button(&self, index: usize) -> Option<&ButtonState> {
+        self.buttons.get(index)
+    }
+
+    /// The direction of the hat, centered if the device has no such hat
+    pub fn hat(&self, index: usize) -> HatDirection {
+        self.hats.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn is_pressed(&self, index: usize) -> bool {
+        self.buttons
+            .get(index)
+            .is_some_and(|button_state| button_state.is_pressed)
+    }
+
+    pub fn is_just_pressed(&self, index: usize) -> bool {
+        self.buttons
+            .get(index)
+            .is_some_and(|button_state| button_state.is_just_pressed())
+    }
+
+    pub fn is_just_released(&self, index: usize) -> bool {
+        self.buttons
+            .get(index)
+            .is_some_and(|button_state| button_state.is_just_released())
+    }
+
+    /// Fills in `was_pressed` from the state of the device on the previous update
+    pub(crate) fn process(&self, previous: Option<&JoystickDevice>) -> JoystickDevice {
+        let mut device = self.clone();
+
+        for (index, button_state) in device.buttons.iter_mut().enumerate() {
+            button_state.was_pressed = previous.is_some_and(|previous| previous.is_pressed(index));
+        }
+
+        device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRECTIONS: [HatDirection; 9] = [
+        HatDirection::Centered,
+        HatDirection::North,
+        HatDirection::NorthEast,
+        HatDirection::East,
+        HatDirection::SouthEast,
+        HatDirection::South,
+        HatDirection::SouthWest,
+        HatDirection::West,
+        HatDirection::NorthWest,
+    ];
+
+    #[test]
+    fn hat_directions_round_trip_through_axes() {
+        for direction in DIRECTIONS.iter() {
+            let (x, y) = direction.to_axes();
+
+            assert_eq!(HatDirection::from_axes(x as f32, y as f32, 0.5), *direction);
+            assert_eq!(
+                HatDirection::from_buttons(y > 0, y < 0, x < 0, x > 0),
+                *direction
+            );
+            assert_eq!(direction.is_north(), y > 0);
+            assert_eq!(direction.is_south(), y < 0);
+            assert_eq!(direction.is_west(), x < 0);
+            assert_eq!(direction.is_east(), x > 0);
+        }
+    }
+
+    #[test]
+    fn hat_directions_cancel_and_ignore_small_values() {
+        assert_eq!(
+            HatDirection::from_buttons(true, true, false, true),
+            HatDirection::East
+        );
+        assert_eq!(
+            HatDirection::from_buttons(true, true, true, true),
+            HatDirection::Centered
+        );
+        assert_eq!(HatDirection::from_axes(0.4, -0.6, 0.5), HatDirection::South);
+        assert_eq!(
+            HatDirection::from_axes(0.5, 0.5, 0.5),
+            HatDirection::Centered
+        );
+    }
+
+    #[test]
+    fn device_axes_scale_their_range() {
+        let axis = DeviceAxis::new(192, 255, 0);
+        assert_eq!(axis.range(), (0, 255));
+        assert!((axis.unipolar_value() - 192.0 / 255.0).abs() < 1e-6);
+        assert!((axis.value() - (2.0 * 192.0 / 255.0 - 1.0)).abs() < 1e-6);
+
+        assert_eq!(DeviceAxis::new(-10, 0, 255).value(), -1.0);
+        assert_eq!(DeviceAxis::new(300, 0, 255).value(), 1.0);
+        assert_eq!(DeviceAxis::new(5, 5, 5).unipolar_value(), 0.0);
+    }
+
+    #[test]
+    fn elements_are_read_by_index() {
+        let device = JoystickDevice::new(
+            vec![DeviceAxis::new(0, -1, 1)],
+            vec![
+                ButtonState::new(false, false),
+                ButtonState::new(true, false),
+            ],
+            vec![HatDirection::West],
+        );
+
+        assert_eq!(device.axis(0), Some(&DeviceAxis::new(0, -1, 1)));
+        assert_eq!(device.axis(1), None);
+        assert!(!device.is_pressed(0));
+        assert!(device.is_pressed(1));
+        assert!(!device.is_pressed(2));
+        assert!(device.button(2).is_none());
+        assert_eq!(device.hat(0), HatDirection::West);
+        assert_eq!(device.hat(1), HatDirection::Centered);
+    }
+
+    #[test]
+    fn process_tracks_the_previous_update() {
+        let first = JoystickDevice::new(
+            Vec::new(),
+            vec![
+                ButtonState::new(true, false),
+                ButtonState::new(false, false),
+            ],
+            Vec::new(),
+        )
+        .process(None);
+        assert!(first.is_just_pressed(0));
+        assert!(!first.is_just_released(1));
+
+        let second = JoystickDevice::new(
+            Vec::new(),
+            vec![
+                ButtonState::new(false, false),
+                ButtonState::new(true, false),
+                ButtonState::new(true, false),
+            ],
+            Vec::new(),
+        )
+        .process(Some(&first));
+        assert!(second.is_just_released(0));
+        assert!(second.is_just_pressed(1));
+        // Buttons the previous update didn't have were not pressed
+        assert!(second.is_just_pressed(2));
+
+        let third = second.process(Some(&second));
+        assert!(third.is_pressed(1));
+        assert!(!third.is_just_pressed(1));
+        assert!(!third.is_just_released(0));
+    }
+}
@@ -0,0 +1,399 @@
+use std::time::Duration;
+
+/// The shape of a haptic effect over time.
+///
+/// The motors ramp up from zero during `attack`, hold their magnitude during `sustain`
+/// and ramp back down to zero during `decay`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HapticEnvelope {
+    pub(crate) attack: Duration,
+    pub(crate) sustain: Duration,
+    pub(crate) decay: Duration,
+}
+impl HapticEnvelope {
+    pub fn new(attack: Duration, sustain: Duration, decay: Duration) -> Self {
+        HapticEnvelope {
+            attack,
+            sustain,
+            decay,
+        }
+    }
+
+    /// An envelope that holds the magnitude for the whole duration
+    pub fn constant(duration: Duration) -> Self {
+        HapticEnvelope::new(Duration::ZERO, duration, Duration::ZERO)
+    }
+
+    pub fn attack(&self) -> Duration {
+        self.attack
+    }
+
+    pub fn sustain(&self) -> Duration {
+        self.sustain
+    }
+
+    pub fn decay(&self) -> Duration {
+        self.decay
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.attack + self.sustain + self.decay
+    }
+
+    /// The level from 0.0 to 1.0 the envelope has reached after `elapsed`
+    pub fn level_at(&self, elapsed: Duration) -> f32 {
+        if elapsed < self.attack {
+            return elapsed.as_secs_f32() / self.attack.as_secs_f32();
+        }
+
+        let elapsed = elapsed - self.attack;
+        if elapsed < self.sustain {
+            return 1.0;
+        }
+
+        let elapsed = elapsed - self.sustain;
+        if elapsed < self.decay {
+            1.0 - elapsed.as_secs_f32() / self.decay.as_secs_f32()
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HapticRepeat {
+    Once,
+    Times(u32),
+    /// Repeats until the effect is stopped
+    Forever,
+}
+
+/// A timed rumble pattern that can be played through `GamepadEngine::play_haptic`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HapticEffect {
+    pub(crate) strong: f32,
+    pub(crate) weak: f32,
+    pub(crate) envelope: HapticEnvelope,
+    pub(crate) repeat: HapticRepeat,
+    pub(crate) interval: Duration,
+    pub(crate) priority: u8,
+}
+impl HapticEffect {
+    /// Creates an effect that plays once with the lowest priority.
+    ///
+    /// `strong` and `weak` are the peak magnitudes of the low and high frequency motors,
+    /// they are clamped to the range 0.0 to 1.0.
+    pub fn new(strong: f32, weak: f32, envelope: HapticEnvelope) -> Self {
+        HapticEffect {
+            strong: strong.clamp(0.0, 1.0),
+            weak: weak.clamp(0.0, 1.0),
+            envelope,
+            repeat: HapticRepeat::Once,
+            interval: Duration::ZERO,
+            priority: 0,
+        }
+    }
+
+    /// Repeats the envelope, waiting for `interval` between the end of one
+    /// repetition and the start of the next
+    pub fn with_repeat(mut self, repeat: HapticRepeat, interval: Duration) -> Self {
+        self.repeat = repeat;
+        self.interval = interval;
+        self
+    }
+
+    /// While an effect is playing, effects with a lower priority are muted
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn strong(&self) -> f32 {
+        self.strong
+    }
+
+    pub fn weak(&self) -> f32 {
+        self.weak
+    }
+
+    pub fn envelope(&self) -> HapticEnvelope {
+        self.envelope
+    }
+
+    pub fn repeat(&self) -> HapticRepeat {
+        self.repeat
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// The time from the start of the effect until it has finished, `None` if it repeats forever
+    pub fn total_duration(&self) -> Option<Duration> {
+        let period = self.envelope.duration() + self.interval;
+
+        match self.repeat {
+            HapticRepeat::Once => Some(self.envelope.duration()),
+            HapticRepeat::Times(0) => Some(Duration::ZERO),
+            HapticRepeat::Times(times) => Some(period * (times - 1) + self.envelope.duration()),
+            HapticRepeat::Forever => None,
+        }
+    }
+
+    fn sample(&self, elapsed: Duration) -> EffectSample {
+        if let Some(total_duration) = self.total_duration() {
+            if elapsed >= total_duration {
+                return EffectSample::Finished;
+            }
+        }
+
+        let period = (self.envelope.duration() + self.interval).as_nanos();
+        if period == 0 {
+            return EffectSample::Finished;
+        }
+
+        let offset = Duration::from_nanos((elapsed.as_nanos() % period) as u64);
+        if offset < self.envelope.duration() {
+            EffectSample::Playing(self.envelope.level_at(offset))
+        } else {
+            EffectSample::Waiting
+        }
+    }
+}
+
+enum EffectSample {
+    Playing(f32),
+    /// Between two repetitions
+    Waiting,
+    Finished,
+}
+
+/// Identifies an effect played through a `HapticMixer`
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HapticHandle(pub(crate) u64);
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PlayingEffect {
+    handle: HapticHandle,
+    effect: HapticEffect,
+    elapsed: Duration,
+}
+
+/// Plays any number of haptic effects at once and mixes them into a single pair of
+/// motor magnitudes.
+///
+/// Only the effects with the highest priority among the effects that are currently
+/// playing are heard, their magnitudes are combined as `1 - (1 - a) * (1 - b) * ...`
+/// so concurrent effects add up without exceeding 1.0. Time only moves forward through
+/// `advance`, which makes the output fully deterministic.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HapticMixer {
+    effects: Vec<PlayingEffect>,
+    next_handle: u64,
+    motors: (f32, f32),
+}
+impl HapticMixer {
+    pub fn new() -> Self {
+        HapticMixer {
+            effects: Vec::new(),
+            next_handle: 0,
+            motors: (0.0, 0.0),
+        }
+    }
+
+    /// Starts playing the effect, it is mixed in from the next call to `advance`
+    pub fn play(&mut self, effect: HapticEffect) -> HapticHandle {
+        let handle = HapticHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.effects.push(PlayingEffect {
+            handle,
+            effect,
+            elapsed: Duration::ZERO,
+        });
+
+        handle
+    }
+
+    /// Stops the effect, returns false if it had already finished
+    pub fn stop(&mut self, handle: HapticHandle) -> bool {
+        let count = self.effects.len();
+        self.effects.retain(|playing| playing.handle != handle);
+
+        self.effects.len() != count
+    }
+
+    pub fn stop_all(&mut self) {
+        self.effects.clear();
+    }
+
+    pub fn is_playing(&self, handle: HapticHandle) -> bool {
+        self.effects.iter().any(|playing| playing.handle == handle)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// The strong and weak motor magnitudes computed by the last call to `advance`
+    pub fn motors(&self) -> (f32, f32) {
+        self.motors
+    }
+
+    /// Mixes the effects at their current position, then moves them forward by `delta`.
+    ///
+    /// Effects that have finished are removed, the mixed motor magnitudes are returned.
+    pub fn advance(&mut self, delta: Duration) -> (f32, f32) {
+        let samples = self
+            .effects
+            .iter()
+            .map(|playing| (playing.effect, playing.effect.sample(playing.elapsed)))
+            .collect::<Vec<(HapticEffect, EffectSample)>>();
+
+        let priority = samples
+            .iter()
+            .filter(|(_, sample)| matches!(sample, EffectSample::Playing(_)))
+            .map(|(effect, _)| effect.priority)
+            .max();
+
+        let mut silence = (1.0, 1.0);
+        for (effect, sample) in samples.iter() {
+            if let EffectSample::Playing(level) = sample {
+                if Some(effect.priority) == priority {
+                    silence.0 *= 1.0 - effect.strong * level;
+                    silence.1 *= 1.0 - effect.weak * level;
+                }
+            }
+        }
+        self.motors = (1.0 - silence.0, 1.0 - silence.1);
+
+        for playing in self.effects.iter_mut() {
+            playing.elapsed += delta;
+        }
+        self.effects.retain(|playing| {
+            !matches!(
+                playing.effect.sample(playing.elapsed),
+                EffectSample::Finished
+            )
+        });
+
+        self.motors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-5 && (actual.1 - expected.1).abs() < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn envelope_levels() {
+        let envelope = HapticEnvelope::new(ms(100), ms(200), ms(100));
+
+        assert_eq!(envelope.duration(), ms(400));
+        assert!((envelope.level_at(ms(0))).abs() < 1e-5);
+        assert!((envelope.level_at(ms(50)) - 0.5).abs() < 1e-5);
+        assert!((envelope.level_at(ms(100)) - 1.0).abs() < 1e-5);
+        assert!((envelope.level_at(ms(299)) - 1.0).abs() < 1e-5);
+        assert!((envelope.level_at(ms(350)) - 0.5).abs() < 1e-5);
+        assert!((envelope.level_at(ms(400))).abs() < 1e-5);
+    }
+
+    #[test]
+    fn repeat_waits_for_interval() {
+        let effect = HapticEffect::new(1.0, 0.5, HapticEnvelope::constant(ms(100)))
+            .with_repeat(HapticRepeat::Times(3), ms(50));
+        assert_eq!(effect.total_duration(), Some(ms(400)));
+
+        let mut mixer = HapticMixer::new();
+        let handle = mixer.play(effect);
+
+        let mut output = Vec::new();
+        while mixer.is_playing(handle) {
+            output.push(mixer.advance(ms(10)));
+        }
+
+        assert_eq!(output.len(), 40);
+        for (step, motors) in output.iter().enumerate() {
+            let offset = step as u64 * 10 % 150;
+            if offset < 100 {
+                assert_close(*motors, (1.0, 0.5));
+            } else {
+                assert_close(*motors, (0.0, 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn forever_repeats_until_stopped() {
+        let effect = HapticEffect::new(1.0, 1.0, HapticEnvelope::constant(ms(10)))
+            .with_repeat(HapticRepeat::Forever, ms(10));
+        assert_eq!(effect.total_duration(), None);
+
+        let mut mixer = HapticMixer::new();
+        let handle = mixer.play(effect);
+        for _ in 0..100 {
+            mixer.advance(ms(10));
+        }
+
+        assert!(mixer.is_playing(handle));
+        assert!(mixer.stop(handle));
+        assert!(!mixer.stop(handle));
+        assert!(mixer.is_empty());
+    }
+
+    #[test]
+    fn higher_priority_mutes_lower_priority() {
+        let mut mixer = HapticMixer::new();
+        mixer.play(HapticEffect::new(
+            0.5,
+            0.5,
+            HapticEnvelope::constant(ms(100)),
+        ));
+        mixer.play(HapticEffect::new(0.2, 0.0, HapticEnvelope::constant(ms(20))).with_priority(1));
+
+        assert_close(mixer.advance(ms(10)), (0.2, 0.0));
+        assert_close(mixer.advance(ms(10)), (0.2, 0.0));
+        assert_close(mixer.advance(ms(10)), (0.5, 0.5));
+    }
+
+    #[test]
+    fn concurrent_effects_are_combined() {
+        let mut mixer = HapticMixer::new();
+        mixer.play(HapticEffect::new(
+            0.5,
+            0.2,
+            HapticEnvelope::constant(ms(100)),
+        ));
+        mixer.play(HapticEffect::new(
+            0.5,
+            0.4,
+            HapticEnvelope::constant(ms(100)),
+        ));
+
+        assert_close(mixer.advance(ms(10)), (0.75, 0.52));
+        assert_close(mixer.motors(), (0.75, 0.52));
+    }
+}
@@ -1,40 +1,625 @@
+mod action;
 mod backends;
+mod calibration;
+mod curve;
+mod deadzone;
+mod device;
+mod haptics;
+mod mapping;
+mod motion;
+mod settings;
+mod sony;
+mod switch;
+mod touchpad;
 mod types;
 
+#[cfg(test)]
+mod test_support;
+
+pub use action::*;
+pub use calibration::*;
+pub use curve::*;
+pub use deadzone::*;
+pub use device::*;
+pub use haptics::*;
+pub use mapping::*;
+pub use motion::*;
+pub use settings::*;
+pub use sony::*;
+pub use switch::*;
+pub use touchpad::*;
 pub use types::*;
 
-#[cfg(not(any(target_family = "wasm", target_os = "android")))]
-use backends::gilrs::GilrsBackend as Backend;
+pub use backends::dummy::{DummyBackend, RumbleCommand, RumbleRecording};
+pub use backends::{BackendKind, GamepadEngineBackend};
+
+#[cfg(target_os = "linux")]
+pub use backends::evdev::{AbsInfo, EvdevBackend, EvdevDeviceInfo, InputEvent};
+#[cfg(target_os = "linux")]
+pub use backends::hidraw::HidrawBackend;
+#[cfg(target_os = "linux")]
+pub use backends::joydev::{JoydevBackend, JoydevMapping, JsEvent};
+#[cfg(target_family = "wasm")]
+pub use backends::wasm::WasmBackend;
+#[cfg(windows)]
+pub use backends::xinput::XInputBackend;
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
 
-#[cfg(any(target_family = "wasm", target_os = "android"))]
-use backends::dummy::DummyBackend as Backend;
+/// How long each rumble request sent for haptic effects lasts, requests are renewed
+/// on every update while effects are playing so rumble stops soon after updates stop
+const HAPTIC_RUMBLE_DURATION: Duration = Duration::from_millis(250);
+
+/// The longest time between two rumble requests for the same haptic output
+const HAPTIC_RUMBLE_REFRESH: Duration = Duration::from_millis(100);
 
 pub struct GamepadEngine {
-    backend: Box<dyn crate::backends::GamepadEngineBackend>,
+    backend: Box<dyn GamepadEngineBackend + Send + Sync>,
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    joystick_devices: BTreeMap<GamepadId, JoystickDevice>,
+    mappings: MappingDatabase,
+    default_settings: GamepadSettings,
+    settings: HashMap<GamepadId, GamepadSettings>,
+    calibration_captures: HashMap<GamepadId, CalibrationCapture>,
+    gyro_calibrators: HashMap<GamepadId, GyroCalibrator>,
+    gesture_recognizers: HashMap<GamepadId, TouchGestureRecognizer>,
+    binding_capture: Option<BindingCapture>,
+    captured_binding: Option<(GamepadId, Binding)>,
+    haptic_mixers: BTreeMap<GamepadId, HapticMixer>,
+    haptic_rumble: BTreeMap<GamepadId, ((f32, f32), Duration)>,
+    last_update: Option<Instant>,
+    /// The sum of every update delta, timers are measured on this clock so they follow
+    /// the deltas passed to `update_with_delta`
+    time: Duration,
+    events: Vec<GamepadEvent>,
 }
 impl GamepadEngine {
-    /// Instantiates gamepad engine, begins polling for input
+    /// Instantiates gamepad engine, begins polling for input.
+    ///
+    /// Mappings in the `SDL_GAMECONTROLLERCONFIG` environment variable are loaded, see `mappings_mut`.
     pub fn new() -> Self {
+        GamepadEngine::from_backend(BackendKind::Default.create().unwrap())
+    }
+
+    /// Instantiates gamepad engine reading input from the given backend, such as a
+    /// `DummyBackend` in tests or an input source implemented by the application. The
+    /// backend must be `Send + Sync`, which makes the engine `Send + Sync` as well.
+    ///
+    /// Mappings in the `SDL_GAMECONTROLLERCONFIG` environment variable are loaded, see `mappings_mut`.
+    pub fn with_backend(backend: Box<dyn GamepadEngineBackend + Send + Sync>) -> Self {
+        GamepadEngine::from_backend(backend)
+    }
+
+    /// Instantiates gamepad engine with one of the built in backends, fails with
+    /// `ErrorType::Unsupported` when the backend is not available on this platform.
+    ///
+    /// Mappings in the `SDL_GAMECONTROLLERCONFIG` environment variable are loaded, see `mappings_mut`.
+    pub fn with_backend_kind(kind: BackendKind) -> Result<Self, GamepadError> {
+        Ok(GamepadEngine::from_backend(kind.create()?))
+    }
+
+    /// The backend the engine reads input from
+    pub fn backend(&self) -> &dyn GamepadEngineBackend {
+        &*self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut dyn GamepadEngineBackend {
+        &mut *self.backend
+    }
+
+    /// Drops the engine state and returns the backend it read input from
+    pub fn into_backend(self) -> Box<dyn GamepadEngineBackend + Send + Sync> {
+        self.backend
+    }
+
+    fn from_backend(backend: Box<dyn GamepadEngineBackend + Send + Sync>) -> Self {
+        let mut mappings = MappingDatabase::new();
+        mappings.add_mappings_from_env();
+
         GamepadEngine {
-            backend: Box::new(Backend::new()),
+            backend,
+            gamepads: BTreeMap::new(),
+            joystick_devices: BTreeMap::new(),
+            mappings,
+            default_settings: GamepadSettings::new(),
+            settings: HashMap::new(),
+            calibration_captures: HashMap::new(),
+            gyro_calibrators: HashMap::new(),
+            gesture_recognizers: HashMap::new(),
+            binding_capture: None,
+            captured_binding: None,
+            haptic_mixers: BTreeMap::new(),
+            haptic_rumble: BTreeMap::new(),
+            last_update: None,
+            time: Duration::ZERO,
+            events: Vec::new(),
         }
     }
 
-    /// Polls for input and updates all gamepad states
+    /// Polls for input and updates all gamepad states, haptic effects and touch gestures
+    /// are advanced by the time since the previous call to `update`
     pub fn update(&mut self) -> Result<(), GamepadError> {
+        let now = Instant::now();
+        let delta = self
+            .last_update
+            .map_or(Duration::ZERO, |last_update| now - last_update);
+        self.last_update = Some(now);
+
+        self.update_with_delta(delta)
+    }
+
+    /// Polls for input and updates all gamepad states like `update`, advancing haptic
+    /// effects and touch gestures by `delta` instead of the time measured by the engine.
+    ///
+    /// This makes the engine deterministic, such as in tests or fixed timestep loops.
+    pub fn update_with_delta(&mut self, delta: Duration) -> Result<(), GamepadError> {
+        self.events.clear();
         self.backend.update()?;
+        self.events.extend(self.backend.poll_events());
+        self.time += delta;
+
+        let backend_gamepads = self.mapped_gamepads();
+
+        for (id, capture) in self.calibration_captures.iter_mut() {
+            if let Some(gamepad) = backend_gamepads.get(id) {
+                capture.sample(gamepad);
+            }
+        }
+
+        // The backend states are left untouched, so settings are always applied to the
+        // input exactly as the backend reported it
+        let mut gamepads = BTreeMap::new();
+        for (id, gamepad) in backend_gamepads.iter() {
+            let settings = self.settings.get(id).unwrap_or(&self.default_settings);
+            let mut gamepad = settings.process(gamepad, self.gamepads.get(id));
+
+            if let Some(motion) = gamepad.motion.as_mut() {
+                *motion = self
+                    .gyro_calibrators
+                    .entry(*id)
+                    .or_default()
+                    .process(motion);
+            }
+
+            if let Some(touchpad) = gamepad.touchpad.as_mut() {
+                touchpad.gestures = self
+                    .gesture_recognizers
+                    .entry(*id)
+                    .or_default()
+                    .process(touchpad, delta);
+            }
+
+            gamepads.insert(*id, gamepad);
+        }
+
+        let previous_gamepads = std::mem::replace(&mut self.gamepads, gamepads);
+        self.push_input_events(&previous_gamepads);
+
+        if let Some(capture) = self.binding_capture.as_mut() {
+            let captured = self
+                .gamepads
+                .iter()
+                .find_map(|(id, gamepad)| Some((*id, capture.sample(*id, gamepad)?)));
+
+            if captured.is_some() {
+                self.binding_capture = None;
+                self.captured_binding = captured;
+            }
+        }
+
+        let gamepads = &self.gamepads;
+        self.gesture_recognizers
+            .retain(|id, _| gamepads.contains_key(id));
+
+        self.joystick_devices = self
+            .backend
+            .joystick_devices()
+            .iter()
+            .map(|(id, device)| (*id, device.process(self.joystick_devices.get(id))))
+            .collect();
+
+        self.update_haptics(delta);
 
         Ok(())
     }
 
-    pub fn gamepads(&self) -> &Vec<GamepadState> {
-        self.backend.gamepads()
+    /// The gamepads reported by the backend, with the buttons and joysticks of every
+    /// device that has a mapping read through that mapping instead
+    fn mapped_gamepads(&self) -> BTreeMap<GamepadId, GamepadState> {
+        let mut gamepads = self.backend.gamepads().clone();
+
+        for (id, device) in self.backend.joystick_devices() {
+            let mapping = self
+                .backend
+                .gamepad_info(*id)
+                .and_then(|info| self.mappings.mapping(info.guid()));
+
+            if let Some(mapping) = mapping {
+                let mapped = mapping.apply(device);
+                let gamepad = gamepads.entry(*id).or_default();
+                gamepad.buttons = mapped.buttons;
+                gamepad.joysticks = mapped.joysticks;
+            }
+        }
+
+        gamepads
+    }
+
+    /// Advances every haptic mixer and sends the mixed motor magnitudes to the backend
+    fn update_haptics(&mut self, delta: Duration) {
+        let now = self.time;
+        let gamepads = &self.gamepads;
+        self.haptic_mixers.retain(|id, _| gamepads.contains_key(id));
+        self.haptic_rumble.retain(|id, _| gamepads.contains_key(id));
+
+        for (id, mixer) in self.haptic_mixers.iter_mut() {
+            let motors = mixer.advance(delta);
+            let (previous_motors, sent) = self
+                .haptic_rumble
+                .get(id)
+                .copied()
+                .unwrap_or(((0.0, 0.0), now));
+
+            let silent = motors == (0.0, 0.0);
+            if silent && previous_motors == (0.0, 0.0) {
+                continue;
+            }
+            if !silent && previous_motors == motors && now - sent < HAPTIC_RUMBLE_REFRESH {
+                continue;
+            }
+            self.haptic_rumble.insert(*id, (motors, now));
+
+            // Gamepads without force feedback still advance their effects, so the mixed
+            // output stays available through `haptic_mixer`
+            let _ = if silent {
+                self.backend.stop_rumble(*id)
+            } else {
+                self.backend
+                    .set_rumble(*id, motors.0, motors.1, HAPTIC_RUMBLE_DURATION)
+            };
+        }
+    }
+
+    /// Returns the events produced by the last call to `update`, leaving none behind.
+    ///
+    /// Button and axis events are derived from the gamepad states, so a `ButtonPressed`
+    /// event is sent exactly when `is_just_pressed` becomes true for that button.
+    pub fn poll_events(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn push_input_events(&mut self, previous_gamepads: &BTreeMap<GamepadId, GamepadState>) {
+        let default_gamepad = GamepadState::new();
+
+        for (id, gamepad) in self.gamepads.iter() {
+            let id = *id;
+            let previous = previous_gamepads.get(&id).unwrap_or(&default_gamepad);
+
+            for (button, button_state) in gamepad.buttons() {
+                let button = *button;
+
+                if button_state.is_just_pressed() {
+                    self.events.push(GamepadEvent::ButtonPressed { id, button });
+                } else if button_state.is_just_released() {
+                    self.events
+                        .push(GamepadEvent::ButtonReleased { id, button });
+                }
+
+                if button_state.value != previous.button_value(button) {
+                    self.events.push(GamepadEvent::ButtonChanged {
+                        id,
+                        button,
+                        value: button_state.value,
+                    });
+                }
+            }
+
+            for (joystick, joystick_state) in gamepad.joysticks() {
+                let value = joystick_state.normalized_value;
+
+                if value != previous.joystick(*joystick) {
+                    self.events.push(GamepadEvent::AxisChanged {
+                        id,
+                        joystick: *joystick,
+                        value,
+                    });
+                }
+            }
+        }
+    }
+
+    /// The SDL mappings used to read devices as gamepads, a device with a mapping for its
+    /// GUID is read through the mapping instead of the backend's own mapping
+    pub fn mappings(&self) -> &MappingDatabase {
+        &self.mappings
+    }
+
+    /// Mappings can be added at any time, they take effect on the next call to `update`
+    pub fn mappings_mut(&mut self) -> &mut MappingDatabase {
+        &mut self.mappings
+    }
+
+    /// The press threshold of the default settings, see `GamepadSettings::press_threshold`
+    pub fn press_threshold(&self) -> f32 {
+        self.default_settings.press_threshold()
+    }
+
+    /// Sets the press threshold of the default settings, see `GamepadSettings::set_press_threshold`
+    pub fn set_press_threshold(&mut self, threshold: f32) {
+        self.default_settings.set_press_threshold(threshold);
     }
 
-    pub fn gamepads_mut(&mut self) -> &mut Vec<GamepadState> {
-        self.backend.gamepads_mut()
+    /// The settings used for gamepads that have no settings of their own
+    pub fn default_settings(&self) -> &GamepadSettings {
+        &self.default_settings
+    }
+
+    pub fn default_settings_mut(&mut self) -> &mut GamepadSettings {
+        &mut self.default_settings
+    }
+
+    /// The settings used for the gamepad, these are the default settings unless
+    /// they have been changed through `settings_mut`
+    pub fn settings(&self, id: GamepadId) -> &GamepadSettings {
+        self.settings.get(&id).unwrap_or(&self.default_settings)
+    }
+
+    /// The settings used for the gamepad, starting out as a copy of the default settings.
+    ///
+    /// Changes take effect on the next call to `update`.
+    pub fn settings_mut(&mut self, id: GamepadId) -> &mut GamepadSettings {
+        let default_settings = &self.default_settings;
+        self.settings
+            .entry(id)
+            .or_insert_with(|| default_settings.clone())
+    }
+
+    /// Removes the settings of the gamepad, so it uses the default settings again
+    pub fn reset_settings(&mut self, id: GamepadId) {
+        self.settings.remove(&id);
+    }
+
+    /// Starts learning the calibration of the gamepad's joysticks from the raw values
+    /// reported on each `update`, see `CalibrationCapture` for how the sticks should be moved.
+    pub fn begin_calibration(&mut self, id: GamepadId) {
+        self.calibration_captures
+            .insert(id, CalibrationCapture::new());
+    }
+
+    pub fn is_calibrating(&self, id: GamepadId) -> bool {
+        self.calibration_captures.contains_key(&id)
+    }
+
+    /// Stops a calibration without changing the gamepad's settings
+    pub fn cancel_calibration(&mut self, id: GamepadId) {
+        self.calibration_captures.remove(&id);
+    }
+
+    /// Stops a calibration and stores the learned calibrations in the gamepad's settings.
+    ///
+    /// The calibrations are returned so they can be saved and restored later through
+    /// `GamepadSettings::set_calibration`, `None` if no calibration was in progress.
+    pub fn finish_calibration(
+        &mut self,
+        id: GamepadId,
+    ) -> Option<HashMap<Joystick, JoystickCalibration>> {
+        let calibrations = self.calibration_captures.remove(&id)?.finish();
+
+        let settings = self.settings_mut(id);
+        for (joystick, calibration) in calibrations.iter() {
+            settings.set_calibration(*joystick, Some(*calibration));
+        }
+
+        Some(calibrations)
+    }
+
+    /// The calibrator removing the gyroscope bias of the gamepad, `None` if the gamepad
+    /// never reported motion and `gyro_calibrator_mut` was never called for it
+    pub fn gyro_calibrator(&self, id: GamepadId) -> Option<&GyroCalibrator> {
+        self.gyro_calibrators.get(&id)
+    }
+
+    /// The calibrator removing the gyroscope bias of the gamepad, created with
+    /// auto-calibration turned on if there is none yet
+    pub fn gyro_calibrator_mut(&mut self, id: GamepadId) -> &mut GyroCalibrator {
+        self.gyro_calibrators.entry(id).or_default()
+    }
+
+    /// Starts learning the gyroscope bias of the gamepad from the readings reported on
+    /// each `update`, the gamepad should be put down until the capture is finished
+    pub fn begin_gyro_calibration(&mut self, id: GamepadId) {
+        self.gyro_calibrator_mut(id).begin_capture();
+    }
+
+    /// Stops a gyroscope calibration and uses the learned bias from the next `update` on.
+    ///
+    /// The bias is returned so it can be saved and restored later through
+    /// `GyroCalibrator::set_bias`, `None` if no calibration was in progress.
+    pub fn finish_gyro_calibration(&mut self, id: GamepadId) -> Option<(f32, f32, f32)> {
+        self.gyro_calibrators.get_mut(&id)?.finish_capture()
+    }
+
+    /// Starts waiting for the next input of the gamepad, or of any gamepad for `None`,
+    /// see `BindingCapture` for which inputs are captured.
+    ///
+    /// Inputs held right now are ignored. The result is read through `captured_binding`.
+    pub fn begin_binding_capture(&mut self, gamepad: Option<GamepadId>) {
+        let mut capture = BindingCapture::new(gamepad);
+        for (id, gamepad) in self.gamepads.iter() {
+            capture.sample(*id, gamepad);
+        }
+
+        self.binding_capture = Some(capture);
+        self.captured_binding = None;
+    }
+
+    pub fn is_capturing_binding(&self) -> bool {
+        self.binding_capture.is_some()
+    }
+
+    pub fn cancel_binding_capture(&mut self) {
+        self.binding_capture = None;
+    }
+
+    /// The input captured since `begin_binding_capture` and the gamepad it came from,
+    /// returned once. The binding can be stored with `ActionMap::bind`.
+    pub fn captured_binding(&mut self) -> Option<(GamepadId, Binding)> {
+        self.captured_binding.take()
+    }
+
+    /// Whether the action is active on the player's gamepad, false if it is no longer connected.
+    ///
+    /// Players are identified by the id of their gamepad, which stays the same while it
+    /// is connected.
+    pub fn action_pressed<A: Eq + Hash>(
+        &self,
+        actions: &ActionMap<A>,
+        action: &A,
+        player: GamepadId,
+    ) -> bool {
+        self.gamepad(player)
+            .is_some_and(|gamepad| actions.pressed(action, gamepad))
+    }
+
+    /// Whether the action became active on the player's gamepad on the last update
+    pub fn action_just_pressed<A: Eq + Hash>(
+        &self,
+        actions: &ActionMap<A>,
+        action: &A,
+        player: GamepadId,
+    ) -> bool {
+        self.gamepad(player)
+            .is_some_and(|gamepad| actions.just_pressed(action, gamepad))
+    }
+
+    /// Whether the action stopped being active on the player's gamepad on the last update
+    pub fn action_just_released<A: Eq + Hash>(
+        &self,
+        actions: &ActionMap<A>,
+        action: &A,
+        player: GamepadId,
+    ) -> bool {
+        self.gamepad(player)
+            .is_some_and(|gamepad| actions.just_released(action, gamepad))
+    }
+
+    /// The value of the action on the player's gamepad, 0.0 if it is no longer connected
+    pub fn action_value<A: Eq + Hash>(
+        &self,
+        actions: &ActionMap<A>,
+        action: &A,
+        player: GamepadId,
+    ) -> f32 {
+        self.gamepad(player)
+            .map_or(0.0, |gamepad| actions.value(action, gamepad))
+    }
+
+    /// Rumbles the gamepad for the given duration, replacing the rumble that is playing.
+    ///
+    /// `strong` and `weak` are the magnitudes of the low and high frequency motors,
+    /// they are clamped to the range 0.0 to 1.0.
+    pub fn set_rumble(
+        &mut self,
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    ) -> Result<(), GamepadError> {
+        self.backend
+            .set_rumble(id, strong.clamp(0.0, 1.0), weak.clamp(0.0, 1.0), duration)
+    }
+
+    pub fn stop_rumble(&mut self, id: GamepadId) -> Result<(), GamepadError> {
+        self.backend.stop_rumble(id)
+    }
+
+    /// Sets the color of the gamepad's light, such as the light bar of Playstation gamepads
+    pub fn set_light(&mut self, id: GamepadId, rgb: (u8, u8, u8)) -> Result<(), GamepadError> {
+        self.backend.set_light(id, rgb)
+    }
+
+    /// Sets the resistance of an adaptive trigger, such as the triggers of the DualSense
+    pub fn set_trigger_effect(
+        &mut self,
+        id: GamepadId,
+        side: TriggerSide,
+        effect: TriggerEffect,
+    ) -> Result<(), GamepadError> {
+        self.backend.set_trigger_effect(id, side, effect)
+    }
+
+    /// Plays a haptic effect on the gamepad, mixed with the other effects playing on it.
+    ///
+    /// Effects advance on every `update`, see `HapticMixer` for how they are mixed.
+    pub fn play_haptic(&mut self, id: GamepadId, effect: HapticEffect) -> HapticHandle {
+        self.haptic_mixers.entry(id).or_default().play(effect)
+    }
+
+    /// Stops a haptic effect, returns false if it had already finished
+    pub fn stop_haptic(&mut self, id: GamepadId, handle: HapticHandle) -> bool {
+        match self.haptic_mixers.get_mut(&id) {
+            Some(mixer) => mixer.stop(handle),
+            None => false,
+        }
+    }
+
+    pub fn stop_all_haptics(&mut self, id: GamepadId) {
+        if let Some(mixer) = self.haptic_mixers.get_mut(&id) {
+            mixer.stop_all();
+        }
+    }
+
+    /// The mixer of the gamepad's haptic effects, `None` if no effect was ever played on it
+    pub fn haptic_mixer(&self, id: GamepadId) -> Option<&HapticMixer> {
+        self.haptic_mixers.get(&id)
+    }
+
+    /// All connected gamepads, ordered by their id
+    pub fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
+    }
+
+    pub fn gamepads_mut(&mut self) -> &mut BTreeMap<GamepadId, GamepadState> {
+        &mut self.gamepads
+    }
+
+    /// The state of the gamepad with the given id, `None` if it is no longer connected
+    pub fn gamepad(&self, id: GamepadId) -> Option<&GamepadState> {
+        self.gamepads.get(&id)
+    }
+
+    pub fn gamepad_mut(&mut self, id: GamepadId) -> Option<&mut GamepadState> {
+        self.gamepads.get_mut(&id)
+    }
+
+    /// Describes the device behind the gamepad, such as its name, GUID and battery status
+    pub fn gamepad_info(&self, id: GamepadId) -> Option<&GamepadInfo> {
+        self.backend.gamepad_info(id)
+    }
+
+    /// All connected devices read as plain axes, buttons and hats, ordered by their id.
+    ///
+    /// Racing wheels, flight sticks and pedals are read through these, a device may also
+    /// be listed in `gamepads` under the same id.
+    pub fn joystick_devices(&self) -> &BTreeMap<GamepadId, JoystickDevice> {
+        &self.joystick_devices
+    }
+
+    /// The state of the joystick device with the given id, `None` if it is no longer connected
+    pub fn joystick_device(&self, id: GamepadId) -> Option<&JoystickDevice> {
+        self.joystick_devices.get(&id)
+    }
+
+    /// Iterates over the connected gamepads and their ids, ordered by id
+    pub fn iter(&self) -> impl Iterator<Item = (GamepadId, &GamepadState)> {
+        self.gamepads.iter().map(|(id, gamepad)| (*id, gamepad))
     }
 }
 
-unsafe impl Send for GamepadEngine {}
-unsafe impl Sync for GamepadEngine {}
+impl Default for GamepadEngine {
+    fn default() -> Self {
+        GamepadEngine::new()
+    }
+}
@@ -0,0 +1,783 @@
+use crate::device::*;
+use crate::types::*;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The environment variable SDL reads additional mappings from
+pub const SDL_MAPPING_ENV_VAR: &str = "SDL_GAMECONTROLLERCONFIG";
+
+/// Mapped stick values are scaled to 16 bit units, the same range gilrs sticks are reported in
+const MAPPED_AXIS_RANGE: (i16, i16) = (-i16::MAX, i16::MAX);
+
+/// The part of an axis that is used by a mapping element
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AxisRange {
+    Full,
+    /// Only the half above the center, written as `+` before the axis
+    Positive,
+    /// Only the half below the center, written as `-` before the axis
+    Negative,
+}
+impl AxisRange {
+    fn prefix(&self) -> &'static str {
+        match self {
+            AxisRange::Full => "",
+            AxisRange::Positive => "+",
+            AxisRange::Negative => "-",
+        }
+    }
+}
+
+/// An element of a `JoystickDevice`, as written on the right hand side of a mapping
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MappingInput {
+    /// `b0` is the first button of the device
+    Button(usize),
+    /// `a0` is the first axis of the device, a `~` after the index inverts it
+    Axis {
+        index: usize,
+        range: AxisRange,
+        inverted: bool,
+    },
+    /// `h0.1` is the first hat of the device pushed up, the number after the dot is
+    /// 1 for up, 2 for right, 4 for down and 8 for left
+    Hat {
+        index: usize,
+        direction: HatDirection,
+    },
+}
+impl MappingInput {
+    /// The value of the element from 0.0 to 1.0, or from -1.0 to 1.0 for full axes
+    fn value(&self, device: &JoystickDevice) -> f32 {
+        match self {
+            MappingInput::Button(index) => device.button(*index).map_or(0.0, |button| button.value),
+            MappingInput::Axis {
+                index,
+                range,
+                inverted,
+            } => {
+                let mut value = device.axis(*index).map_or(0.0, |axis| axis.value());
+                if *inverted {
+                    value = -value;
+                }
+
+                match range {
+                    AxisRange::Full => value,
+                    AxisRange::Positive => value.max(0.0),
+                    AxisRange::Negative => (-value).max(0.0),
+                }
+            }
+            MappingInput::Hat { index, direction } => {
+                let (x, y) = device.hat(*index).to_axes();
+                let (dx, dy) = direction.to_axes();
+
+                ((dx != 0 && dx == x) || (dy != 0 && dy == y)) as u8 as f32
+            }
+        }
+    }
+
+    fn is_full_axis(&self) -> bool {
+        matches!(
+            self,
+            MappingInput::Axis {
+                range: AxisRange::Full,
+                ..
+            }
+        )
+    }
+}
+impl fmt::Display for MappingInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MappingInput::Button(index) => write!(f, "b{}", index),
+            MappingInput::Axis {
+                index,
+                range,
+                inverted,
+            } => write!(
+                f,
+                "{}a{}{}",
+                range.prefix(),
+                index,
+                if *inverted { "~" } else { "" }
+            ),
+            MappingInput::Hat { index, direction } => {
+                let mask = match direction {
+                    HatDirection::North => 1,
+                    HatDirection::East => 2,
+                    HatDirection::South => 4,
+                    HatDirection::West => 8,
+                    _ => 0,
+                };
+
+                write!(f, "h{}.{}", index, mask)
+            }
+        }
+    }
+}
+impl FromStr for MappingInput {
+    type Err = GamepadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            GamepadError::new(
+                format!("Invalid mapping input: {}", s),
+                ErrorType::InvalidMapping,
+            )
+        };
+        let index = |index: &str| index.parse::<usize>().map_err(|_| invalid());
+
+        let (range, input) = split_range(s.trim());
+
+        if let Some(button) = input.strip_prefix('b') {
+            if range != AxisRange::Full {
+                return Err(invalid());
+            }
+
+            return Ok(MappingInput::Button(index(button)?));
+        }
+
+        if let Some(axis) = input.strip_prefix('a') {
+            let (axis, inverted) = match axis.strip_suffix('~') {
+                Some(axis) => (axis, true),
+                None => (axis, false),
+            };
+
+            return Ok(MappingInput::Axis {
+                index: index(axis)?,
+                range,
+                inverted,
+            });
+        }
+
+        if let Some(hat) = input.strip_prefix('h') {
+            let (hat, mask) = hat.split_once('.').ok_or_else(invalid)?;
+            let direction = match mask {
+                "1" => HatDirection::North,
+                "2" => HatDirection::East,
+                "4" => HatDirection::South,
+                "8" => HatDirection::West,
+                _ => return Err(invalid()),
+            };
+
+            if range != AxisRange::Full {
+                return Err(invalid());
+            }
+
+            return Ok(MappingInput::Hat {
+                index: index(hat)?,
+                direction,
+            });
+        }
+
+        Err(invalid())
+    }
+}
+
+/// The input of a gamepad a mapping element is mapped to, as written on the left hand side
+/// of a mapping
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MappingOutput {
+    Button(Button),
+    /// Stick and trigger axes, the D-pad axes are mapped through its buttons instead
+    Axis {
+        axis: Axis,
+        range: AxisRange,
+    },
+}
+
+/// The SDL names of the mapped buttons
+const SDL_BUTTONS: [(&str, Button); 21] = [
+    ("a", Button::South),
+    ("b", Button::East),
+    ("x", Button::West),
+    ("y", Button::North),
+    ("back", Button::Select),
+    ("guide", Button::Guide),
+    ("start", Button::Start),
+    ("leftstick", Button::LeftStick),
+    ("rightstick", Button::RightStick),
+    ("leftshoulder", Button::LeftShoulder),
+    ("rightshoulder", Button::RightShoulder),
+    ("dpup", Button::DPadNorth),
+    ("dpdown", Button::DPadSouth),
+    ("dpleft", Button::DPadWest),
+    ("dpright", Button::DPadEast),
+    ("misc1", Button::Misc),
+    ("paddle1", Button::Paddle1),
+    ("paddle2", Button::Paddle2),
+    ("paddle3", Button::Paddle3),
+    ("paddle4", Button::Paddle4),
+    ("touchpad", Button::TouchpadClick),
+];
+
+/// The SDL names of the mapped axes
+const SDL_AXES: [(&str, Axis); 6] = [
+    ("leftx", Axis::LeftStickX),
+    ("lefty", Axis::LeftStickY),
+    ("rightx", Axis::RightStickX),
+    ("righty", Axis::RightStickY),
+    ("lefttrigger", Axis::LeftTrigger),
+    ("righttrigger", Axis::RightTrigger),
+];
+
+impl MappingOutput {
+    /// Reads an SDL element name, `None` for names this crate has no input for
+    fn from_sdl_name(name: &str) -> Option<Self> {
+        let (range, name) = split_range(name);
+
+        if let Some((_, button)) = SDL_BUTTONS.iter().find(|(sdl_name, _)| *sdl_name == name) {
+            return match range {
+                AxisRange::Full => Some(MappingOutput::Button(*button)),
+                _ => None,
+            };
+        }
+
+        SDL_AXES
+            .iter()
+            .find(|(sdl_name, _)| *sdl_name == name)
+            .map(|(_, axis)| MappingOutput::Axis { axis: *axis, range })
+    }
+
+    fn sdl_name(&self) -> String {
+        match self {
+            MappingOutput::Button(button) => SDL_BUTTONS
+                .iter()
+                .find(|(_, sdl_button)| sdl_button == button)
+                .map_or(String::new(), |(name, _)| name.to_string()),
+            MappingOutput::Axis { axis, range } => SDL_AXES
+                .iter()
+                .find(|(_, sdl_axis)| sdl_axis == axis)
+                .map_or(String::new(), |(name, _)| {
+                    format!("{}{}", range.prefix(), name)
+                }),
+        }
+    }
+}
+
+fn split_range(s: &str) -> (AxisRange, &str) {
+    if let Some(s) = s.strip_prefix('+') {
+        (AxisRange::Positive, s)
+    } else if let Some(s) = s.strip_prefix('-') {
+        (AxisRange::Negative, s)
+    } else {
+        (AxisRange::Full, s)
+    }
+}
+
+/// The name SDL uses for the platform this crate was built for
+fn sdl_platform() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "Windows"
+    } else if cfg!(target_os = "macos") {
+        "Mac OS X"
+    } else if cfg!(target_os = "ios") {
+        "iOS"
+    } else if cfg!(target_os = "android") {
+        "Android"
+    } else {
+        "Linux"
+    }
+}
+
+/// Maps the elements of a `JoystickDevice` to the buttons and joysticks of a gamepad.
+///
+/// Mappings are read from and written as SDL GameControllerDB lines through `FromStr`
+/// and `Display`, such as `030000005e0400008e02000014010000,Xbox 360,a:b0,b:b1,leftx:a0,`.
+/// Elements this crate has no input for are skipped when a mapping is read.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadMapping {
+    pub(crate) guid: Guid,
+    pub(crate) name: String,
+    pub(crate) platform: Option<String>,
+    pub(crate) elements: Vec<(MappingOutput, MappingInput)>,
+}
+impl GamepadMapping {
+    pub fn new<T: Into<String>>(guid: Guid, name: T) -> Self {
+        GamepadMapping {
+            guid,
+            name: name.into(),
+            platform: None,
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn guid(&self) -> Guid {
+        self.guid
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The SDL name of the platform the mapping is meant for, `None` if it applies to
+    /// every platform
+    pub fn platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+
+    pub fn elements(&self) -> &[(MappingOutput, MappingInput)] {
+        &self.elements
+    }
+
+    pub fn elements_mut(&mut self) -> &mut Vec<(MappingOutput, MappingInput)> {
+        &mut self.elements
+    }
+
+    /// Whether the mapping is meant for the platform this crate was built for
+    pub fn is_for_current_platform(&self) -> bool {
+        self.platform
+            .as_deref()
+            .map_or(true, |platform| platform == sdl_platform())
+    }
+
+    /// Reads the buttons and joysticks of the gamepad from the device.
+    ///
+    /// Stick values are reported with positive values up, as everywhere in this crate,
+    /// while SDL mappings treat down as positive.
+    pub fn apply(&self, device: &JoystickDevice) -> GamepadState {
+        let mut gamepad = GamepadState::new();
+        let mut axes: HashMap<Axis, f32> = HashMap::new();
+
+        for (output, input) in self.elements.iter() {
+            let value = input.value(device);
+
+            match output {
+                MappingOutput::Button(button) => {
+                    let value = if input.is_full_axis() {
+                        (value + 1.0) / 2.0
+                    } else {
+                        value
+                    };
+
+                    // A button mapped from several elements is pressed by any of them
+                    let value = value.max(gamepad.button_value(*button));
+                    gamepad.buttons.insert(
+                        *button,
+                        ButtonState::analog(
+                            value,
+                            (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u16,
+                            ButtonState::DEFAULT_PRESS_THRESHOLD,
+                            false,
+                        ),
+                    );
+                }
+                MappingOutput::Axis { axis, range } => {
+                    let value = match (axis, range) {
+                        (Axis::LeftTrigger | Axis::RightTrigger, _) if input.is_full_axis() => {
+                            (value + 1.0) / 2.0
+                        }
+                        (_, AxisRange::Negative) => -value.abs(),
+                        (_, AxisRange::Positive) => value.abs(),
+                        (_, AxisRange::Full) => value,
+                    };
+
+                    *axes.entry(*axis).or_insert(0.0) += value;
+                }
+            }
+        }
+
+        for (axis, button) in [
+            (Axis::LeftTrigger, Button::LeftTrigger),
+            (Axis::RightTrigger, Button::RightTrigger),
+        ] {
+            if let Some(value) = axes.get(&axis) {
+                let value = value.clamp(0.0, 1.0);
+                gamepad.buttons.insert(
+                    button,
+                    ButtonState::analog(
+                        value,
+                        (value * u8::MAX as f32).round() as u16,
+                        ButtonState::DEFAULT_PRESS_THRESHOLD,
+                        false,
+                    ),
+                );
+            }
+        }
+
+        for (joystick, x_axis, y_axis) in [
+            (Joystick::Left, Axis::LeftStickX, Axis::LeftStickY),
+            (Joystick::Right, Axis::RightStickX, Axis::RightStickY),
+        ] {
+            if !axes.contains_key(&x_axis) && !axes.contains_key(&y_axis) {
+                continue;
+            }
+
+            let to_raw = |value: f32| (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            let x = axes.get(&x_axis).copied().unwrap_or(0.0);
+            let y = -axes.get(&y_axis).copied().unwrap_or(0.0);
+
+            gamepad.joysticks.insert(
+                joystick,
+                JoystickState::from_raw((to_raw(x), to_raw(y)), MAPPED_AXIS_RANGE),
+            );
+        }
+
+        gamepad
+    }
+}
+impl fmt::Display for GamepadMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},", self.guid, self.name)?;
+
+        for (output, input) in self.elements.iter() {
+            write!(f, "{}:{},", output.sdl_name(), input)?;
+        }
+
+        if let Some(platform) = self.platform.as_ref() {
+            write!(f, "platform:{},", platform)?;
+        }
+
+        Ok(())
+    }
+}
+impl FromStr for GamepadMapping {
+    type Err = GamepadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            GamepadError::new(
+                format!("Invalid gamepad mapping: {}", s),
+                ErrorType::InvalidMapping,
+            )
+        };
+
+        let mut fields = s.trim().split(',');
+        let guid = fields
+            .next()
+            .ok_or_else(invalid)?
+            .parse::<Guid>()
+            .map_err(|_| invalid())?;
+        let name = fields.next().ok_or_else(invalid)?;
+
+        let mut mapping = GamepadMapping::new(guid, name.trim());
+        for field in fields.map(str::trim).filter(|field| !field.is_empty()) {
+            let (output, input) = field.split_once(':').ok_or_else(invalid)?;
+
+            if output == "platform" {
+                mapping.platform = Some(input.to_string());
+                continue;
+            }
+
+            if let Some(output) = MappingOutput::from_sdl_name(output) {
+                mapping.elements.push((output, input.parse()?));
+            }
+        }
+
+        Ok(mapping)
+    }
+}
+
+/// A collection of mappings keyed by the GUID of the devices they apply to, such as the
+/// contents of SDL's `gamecontrollerdb.txt`.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MappingDatabase {
+    mappings: HashMap<Guid, GamepadMapping>,
+}
+impl MappingDatabase {
+    pub fn new() -> Self {
+        MappingDatabase {
+            mappings: HashMap::new(),
+        }
+    }
+
+    /// Adds the mapping, replacing the mapping for the same GUID
+    pub fn add_mapping(&mut self, mapping: GamepadMapping) {
+        self.mappings.insert(mapping.guid, mapping);
+    }
+
+    pub fn remove_mapping(&mut self, guid: Guid) -> Option<GamepadMapping> {
+        self.mappings.remove(&guid)
+    }
+
+    /// Adds every mapping in the text, one per line.
+    ///
+    /// Like SDL, empty lines, comments starting with `#`, invalid lines and mappings
+    /// meant for other platforms are skipped. Returns the number of mappings added.
+    pub fn add_mappings(&mut self, mappings: &str) -> usize {
+        let mut count = 0;
+
+        for line in mappings.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Ok(mapping) = line.parse::<GamepadMapping>() {
+                if mapping.is_for_current_platform() {
+                    self.add_mapping(mapping);
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    /// Adds every mapping in the file, see `add_mappings`
+    pub fn add_mappings_from_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<usize, GamepadError> {
+        let path = path.as_ref();
+        let mappings = std::fs::read_to_string(path).map_err(|e| {
+            GamepadError::new(
+                format!("Failed to read mappings from {}: {}", path.display(), e),
+                ErrorType::Io,
+            )
+        })?;
+
+        Ok(self.add_mappings(&mappings))
+    }
+
+    /// Adds every mapping in the `SDL_GAMECONTROLLERCONFIG` environment variable,
+    /// see `add_mappings`. Returns 0 if the variable is not set.
+    pub fn add_mappings_from_env(&mut self) -> usize {
+        match std::env::var(SDL_MAPPING_ENV_VAR) {
+            Ok(mappings) => self.add_mappings(&mappings),
+            Err(_) => 0,
+        }
+    }
+
+    pub fn mapping(&self, guid: Guid) -> Option<&GamepadMapping> {
+        self.mappings.get(&guid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GamepadMapping> {
+        self.mappings.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mappings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XBOX_360: &str = "030000005e0400008e02000014010000,Xbox 360 Controller,a:b0,b:b1,\
+        back:b6,dpdown:h0.4,dpleft:h0.8,dpright:h0.2,dpup:h0.1,guide:b8,leftshoulder:b4,\
+        leftstick:b9,lefttrigger:a2,leftx:a0,lefty:a1,rightshoulder:b5,rightstick:b10,\
+        righttrigger:a5,rightx:a3,righty:a4,start:b7,x:b2,y:b3,platform:Linux,";
+
+    fn axis(index: usize, range: AxisRange, inverted: bool) -> MappingInput {
+        MappingInput::Axis {
+            index,
+            range,
+            inverted,
+        }
+    }
+
+    fn device(
+        axes: &[f32],
+        pressed: &[usize],
+        buttons: usize,
+        hat: HatDirection,
+    ) -> JoystickDevice {
+        JoystickDevice::new(
+            axes.iter()
+                .map(|value| DeviceAxis::new((value * 32767.0) as i32, -32767, 32767))
+                .collect(),
+            (0..buttons)
+                .map(|index| ButtonState::new(pressed.contains(&index), false))
+                .collect(),
+            vec![hat],
+        )
+    }
+
+    #[test]
+    fn parses_gamecontrollerdb_line() {
+        let mapping = XBOX_360.parse::<GamepadMapping>().unwrap();
+
+        assert_eq!(
+            mapping.guid().to_string(),
+            "030000005e0400008e02000014010000"
+        );
+        assert_eq!(mapping.name(), "Xbox 360 Controller");
+        assert_eq!(mapping.platform(), Some("Linux"));
+        assert_eq!(mapping.elements().len(), 21);
+        assert!(mapping.elements().contains(&(
+            MappingOutput::Button(Button::North),
+            MappingInput::Button(3)
+        )));
+        assert!(mapping.elements().contains(&(
+            MappingOutput::Axis {
+                axis: Axis::RightTrigger,
+                range: AxisRange::Full
+            },
+            axis(5, AxisRange::Full, false)
+        )));
+        assert!(mapping.elements().contains(&(
+            MappingOutput::Button(Button::DPadWest),
+            MappingInput::Hat {
+                index: 0,
+                direction: HatDirection::West
+            }
+        )));
+
+        assert_eq!(mapping.to_string(), XBOX_360);
+    }
+
+    #[test]
+    fn unknown_elements_are_skipped() {
+        let mapping = "030000005e0400008e02000014010000,Pad,a:b0,misc6:b9,"
+            .parse::<GamepadMapping>()
+            .unwrap();
+
+        assert_eq!(
+            mapping.elements(),
+            &[(
+                MappingOutput::Button(Button::South),
+                MappingInput::Button(0)
+            )]
+        );
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        assert!("not a guid,Pad,a:b0,".parse::<GamepadMapping>().is_err());
+        assert!("030000005e0400008e02000014010000"
+            .parse::<GamepadMapping>()
+            .is_err());
+        assert!("030000005e0400008e02000014010000,Pad,a"
+            .parse::<GamepadMapping>()
+            .is_err());
+    }
+
+    #[test]
+    fn input_modifiers() {
+        assert_eq!(
+            "a2".parse::<MappingInput>().unwrap(),
+            axis(2, AxisRange::Full, false)
+        );
+        assert_eq!(
+            "+a2".parse::<MappingInput>().unwrap(),
+            axis(2, AxisRange::Positive, false)
+        );
+        assert_eq!(
+            "-a3".parse::<MappingInput>().unwrap(),
+            axis(3, AxisRange::Negative, false)
+        );
+        assert_eq!(
+            "a1~".parse::<MappingInput>().unwrap(),
+            axis(1, AxisRange::Full, true)
+        );
+        assert_eq!(
+            "-a4~".parse::<MappingInput>().unwrap(),
+            axis(4, AxisRange::Negative, true)
+        );
+        assert_eq!(
+            "h1.8".parse::<MappingInput>().unwrap(),
+            MappingInput::Hat {
+                index: 1,
+                direction: HatDirection::West
+            }
+        );
+
+        for input in ["b12", "a0", "+a1", "-a2~", "h0.1", "h2.4"].iter() {
+            assert_eq!(input.parse::<MappingInput>().unwrap().to_string(), *input);
+        }
+
+        for input in ["+b0", "-h0.1", "h0.3", "h0", "x1", "a", "b-1"].iter() {
+            assert!(input.parse::<MappingInput>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn platform_filtering() {
+        let other_platform = if sdl_platform() == "Windows" {
+            "Linux"
+        } else {
+            "Windows"
+        };
+        let mappings = format!(
+            "# comment\n\
+             \n\
+             03000000000000000000000000000001,Here,a:b0,platform:{},\n\
+             03000000000000000000000000000002,There,a:b0,platform:{},\n\
+             03000000000000000000000000000003,Anywhere,a:b0,\n\
+             invalid line\n",
+            sdl_platform(),
+            other_platform
+        );
+
+        let mut database = MappingDatabase::new();
+        assert_eq!(database.add_mappings(&mappings), 2);
+
+        let mut names = database
+            .iter()
+            .map(|mapping| mapping.name())
+            .collect::<Vec<&str>>();
+        names.sort_unstable();
+        assert_eq!(names, vec!["Anywhere", "Here"]);
+    }
+
+    #[test]
+    fn apply_reads_elements_by_index() {
+        let mapping = XBOX_360.parse::<GamepadMapping>().unwrap();
+        let gamepad = mapping.apply(&device(
+            &[0.0, 1.0, -1.0, -1.0, 0.0, 1.0],
+            &[3, 7],
+            11,
+            HatDirection::South,
+        ));
+
+        assert!(gamepad.is_pressed(Button::North));
+        assert!(gamepad.is_pressed(Button::Start));
+        assert!(!gamepad.is_pressed(Button::South));
+        assert!(!gamepad.is_pressed(Button::West));
+        assert!(gamepad.is_pressed(Button::DPadSouth));
+        assert!(!gamepad.is_pressed(Button::DPadNorth));
+
+        // SDL treats down as positive, this crate treats up as positive
+        assert_eq!(gamepad.joystick(Joystick::Left), (0.0, -1.0));
+        assert_eq!(gamepad.joystick(Joystick::Right), (-1.0, 0.0));
+
+        // Full range trigger axes rest at -1.0
+        assert_eq!(gamepad.button_value(Button::LeftTrigger), 0.0);
+        assert_eq!(gamepad.button_value(Button::RightTrigger), 1.0);
+    }
+
+    #[test]
+    fn apply_half_and_inverted_axes() {
+        let mapping = "03000000000000000000000000000001,Pad,dpup:-a1,dpdown:+a1,\
+            -leftx:-a0,+leftx:+a2,righty:a3~,lefttrigger:+a4,"
+            .parse::<GamepadMapping>()
+            .unwrap();
+
+        let gamepad = mapping.apply(&device(
+            &[-1.0, -1.0, 0.0, 1.0, 0.5],
+            &[],
+            0,
+            HatDirection::Centered,
+        ));
+
+        assert!(gamepad.is_pressed(Button::DPadNorth));
+        assert!(!gamepad.is_pressed(Button::DPadSouth));
+        assert_eq!(gamepad.joystick(Joystick::Left).0, -1.0);
+        assert_eq!(gamepad.joystick(Joystick::Right).1, 1.0);
+        assert!((gamepad.button_value(Button::LeftTrigger) - 0.5).abs() < 1e-3);
+
+        let gamepad = mapping.apply(&device(
+            &[0.0, 0.0, 1.0, 0.0, -1.0],
+            &[],
+            0,
+            HatDirection::Centered,
+        ));
+
+        assert_eq!(gamepad.joystick(Joystick::Left).0, 1.0);
+        assert_eq!(gamepad.button_value(Button::LeftTrigger), 0.0);
+    }
+}
@@ -0,0 +1,497 @@
+use std::time::Duration;
+
+/// A single reading of the motion sensors of a gamepad.
+///
+/// The axes are fixed to the gamepad held level in front of the player: x points to
+/// the right, y points up and z points towards the player.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MotionState {
+    pub(crate) angular_velocity: (f32, f32, f32),
+    pub(crate) acceleration: (f32, f32, f32),
+    pub(crate) timestamp: Duration,
+}
+impl MotionState {
+    pub fn new(
+        angular_velocity: (f32, f32, f32),
+        acceleration: (f32, f32, f32),
+        timestamp: Duration,
+    ) -> Self {
+        MotionState {
+            angular_velocity,
+            acceleration,
+            timestamp,
+        }
+    }
+
+    /// The rotation around the x, y and z axis in degrees per second, following the right
+    /// hand rule: tilting the front of the gamepad up is a positive rotation around x
+    /// and turning it to the left is a positive rotation around y
+    pub fn angular_velocity(&self) -> (f32, f32, f32) {
+        self.angular_velocity
+    }
+
+    /// The acceleration along the x, y and z axis in g, a gamepad lying still on a table
+    /// reads about `(0.0, -1.0, 0.0)`
+    pub fn acceleration(&self) -> (f32, f32, f32) {
+        self.acceleration
+    }
+
+    /// When the sensors were read, measured by the clock of the gamepad from an
+    /// unspecified starting point
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+}
+
+/// Learns the bias of a gyroscope, the angular velocity it reports while it isn't moving,
+/// and removes it from the readings it is given.
+///
+/// The bias is found either by an explicit capture, during which the gamepad should be put
+/// down, or automatically whenever the readings stay still for `STATIONARY_DURATION`.
+/// All timing follows the timestamps of the readings, so the same readings always give
+/// the same result.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GyroCalibrator {
+    bias: (f32, f32, f32),
+    auto_calibration: bool,
+    capture: Option<GyroAverage>,
+    stationary: Option<StationaryWindow>,
+}
+impl GyroCalibrator {
+    /// How long the readings must stay still before they are used as bias
+    pub const STATIONARY_DURATION: Duration = Duration::from_secs(1);
+
+    /// How far the angular velocity may drift, in degrees per second, while still counting as still
+    pub const STATIONARY_GYRO_THRESHOLD: f32 = 1.0;
+
+    /// How far the acceleration may drift, in g, while still counting as still
+    pub const STATIONARY_ACCELERATION_THRESHOLD: f32 = 0.02;
+
+    /// Creates a calibrator without bias that calibrates automatically
+    pub fn new() -> Self {
+        GyroCalibrator {
+            bias: (0.0, 0.0, 0.0),
+            auto_calibration: true,
+            capture: None,
+            stationary: None,
+        }
+    }
+
+    /// The angular velocity in degrees per second that is removed from every reading
+    pub fn bias(&self) -> (f32, f32, f32) {
+        self.bias
+    }
+
+    /// Sets the bias, such as one that was saved from an earlier capture
+    pub fn set_bias(&mut self, bias: (f32, f32, f32)) {
+        self.bias = bias;
+    }
+
+    pub fn auto_calibration(&self) -> bool {
+        self.auto_calibration
+    }
+
+    /// Turns learning the bias while the gamepad is still on or off, turning it off
+    /// keeps the current bias
+    pub fn set_auto_calibration(&mut self, auto_calibration: bool) {
+        self.auto_calibration = auto_calibration;
+        self.stationary = None;
+    }
+
+    /// Starts averaging every reading into a new bias until `finish_capture` is called
+    pub fn begin_capture(&mut self) {
+        self.capture = Some(GyroAverage::default());
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Stops a capture without changing the bias
+    pub fn cancel_capture(&mut self) {
+        self.capture = None;
+    }
+
+    /// Stops a capture and uses the average of its readings as bias.
+    ///
+    /// The bias is returned so it can be saved and restored through `set_bias`,
+    /// `None` if no capture was in progress or no readings were captured.
+    pub fn finish_capture(&mut self) -> Option<(f32, f32, f32)> {
+        let bias = self.capture.take()?.average()?;
+        self.bias = bias;
+
+        Some(bias)
+    }
+
+    /// Learns from the reading, then returns it with the bias removed
+    pub fn process(&mut self, motion: &MotionState) -> MotionState {
+        self.sample(motion);
+
+        let mut motion = *motion;
+        motion.angular_velocity = (
+            motion.angular_velocity.0 - self.bias.0,
+            motion.angular_velocity.1 - self.bias.1,
+            motion.angular_velocity.2 - self.bias.2,
+        );
+
+        motion
+    }
+
+    /// Learns from the reading without correcting it
+    pub fn sample(&mut self, motion: &MotionState) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.add(motion.angular_velocity);
+            return;
+        }
+
+        if !self.auto_calibration {
+            return;
+        }
+
+        let window = match self.stationary.as_mut() {
+            Some(window) if window.is_still(motion) => window,
+            _ => {
+                self.stationary = Some(StationaryWindow::new(motion));
+                return;
+            }
+        };
+
+        window.average.add(motion.angular_velocity);
+        if motion.timestamp.saturating_sub(window.start) >= GyroCalibrator::STATIONARY_DURATION {
+            if let Some(bias) = window.average.average() {
+                self.bias = bias;
+            }
+        }
+    }
+}
+impl Default for GyroCalibrator {
+    fn default() -> GyroCalibrator {
+        GyroCalibrator::new()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct GyroAverage {
+    sum: (f64, f64, f64),
+    samples: u32,
+}
+impl GyroAverage {
+    fn add(&mut self, angular_velocity: (f32, f32, f32)) {
+        self.sum.0 += angular_velocity.0 as f64;
+        self.sum.1 += angular_velocity.1 as f64;
+        self.sum.2 += angular_velocity.2 as f64;
+        self.samples += 1;
+    }
+
+    fn average(&self) -> Option<(f32, f32, f32)> {
+        if self.samples == 0 {
+            return None;
+        }
+
+        let samples = self.samples as f64;
+        Some((
+            (self.sum.0 / samples) as f32,
+            (self.sum.1 / samples) as f32,
+            (self.sum.2 / samples) as f32,
+        ))
+    }
+}
+
+/// A run of readings that all stayed close to the first reading of the run
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct StationaryWindow {
+    start: Duration,
+    reference: MotionState,
+    average: GyroAverage,
+}
+impl StationaryWindow {
+    fn new(motion: &MotionState) -> Self {
+        let mut average = GyroAverage::default();
+        average.add(motion.angular_velocity);
+
+        StationaryWindow {
+            start: motion.timestamp,
+            reference: *motion,
+            average,
+        }
+    }
+
+    fn is_still(&self, motion: &MotionState) -> bool {
+        let close = |a: (f32, f32, f32), b: (f32, f32, f32), threshold: f32| {
+            (a.0 - b.0).abs() <= threshold
+                && (a.1 - b.1).abs() <= threshold
+                && (a.2 - b.2).abs() <= threshold
+        };
+
+        motion.timestamp >= self.start
+            && close(
+                motion.angular_velocity,
+                self.reference.angular_velocity,
+                GyroCalibrator::STATIONARY_GYRO_THRESHOLD,
+            )
+            && close(
+                motion.acceleration,
+                self.reference.acceleration,
+                GyroCalibrator::STATIONARY_ACCELERATION_THRESHOLD,
+            )
+    }
+}
+
+/// Turns the rotation of a gamepad into aiming input, either as a mouse movement or as
+/// a joystick deflection.
+///
+/// Turning the gamepad to the right moves the output to the right and tilting its front
+/// up moves the output up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GyroAim {
+    pub(crate) sensitivity: f32,
+    pub(crate) tightening: f32,
+    pub(crate) full_deflection: f32,
+}
+impl GyroAim {
+    /// The default angular velocity in degrees per second that fully deflects the output
+    /// of `to_joystick`
+    pub const DEFAULT_FULL_DEFLECTION: f32 = 360.0;
+
+    /// Creates an aim where turning the gamepad by one degree moves the mouse by
+    /// `sensitivity` units
+    pub fn new(sensitivity: f32) -> Self {
+        GyroAim {
+            sensitivity,
+            tightening: 0.0,
+            full_deflection: GyroAim::DEFAULT_FULL_DEFLECTION,
+        }
+    }
+
+    /// Scales down rotations slower than `tightening` degrees per second, which hides the
+    /// shaking of the player's hands while holding still
+    pub fn with_tightening(mut self, tightening: f32) -> Self {
+        self.tightening = tightening.max(0.0);
+        self
+    }
+
+    /// Sets the angular velocity in degrees per second that fully deflects the output
+    /// of `to_joystick`
+    pub fn with_full_deflection(mut self, full_deflection: f32) -> Self {
+        self.full_deflection = full_deflection.max(f32::EPSILON);
+        self
+    }
+
+    pub fn sensitivity(&self) -> f32 {
+        self.sensitivity
+    }
+
+    pub fn tightening(&self) -> f32 {
+        self.tightening
+    }
+
+    pub fn full_deflection(&self) -> f32 {
+        self.full_deflection
+    }
+
+    /// The rotation as a pair of angular velocities in degrees per second, after tightening
+    fn rotation(&self, motion: &MotionState) -> (f32, f32) {
+        let rotation = (-motion.angular_velocity.1, motion.angular_velocity.0);
+
+        let speed = (rotation.0 * rotation.0 + rotation.1 * rotation.1).sqrt();
+        if speed < self.tightening {
+            let scale = speed / self.tightening;
+            return (rotation.0 * scale, rotation.1 * scale);
+        }
+
+        rotation
+    }
+
+    /// How far the mouse moves for a reading that lasted `delta`, positive values are
+    /// to the right (x-axis) or up (y-axis)
+    pub fn to_mouse(&self, motion: &MotionState, delta: Duration) -> (f32, f32) {
+        let rotation = self.rotation(motion);
+        let scale = delta.as_secs_f32() * self.sensitivity;
+
+        (rotation.0 * scale, rotation.1 * scale)
+    }
+
+    /// The reading as a joystick value with a length of at most 1.0, positive values are
+    /// to the right (x-axis) or up (y-axis)
+    pub fn to_joystick(&self, motion: &MotionState) -> (f32, f32) {
+        let rotation = self.rotation(motion);
+        let value = (
+            rotation.0 / self.full_deflection,
+            rotation.1 / self.full_deflection,
+        );
+
+        let length = (value.0 * value.0 + value.1 * value.1).sqrt();
+        if length > 1.0 {
+            (value.0 / length, value.1 / length)
+        } else {
+            value
+        }
+    }
+}
+impl Default for GyroAim {
+    fn default() -> GyroAim {
+        GyroAim::new(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STILL: (f32, f32, f32) = (0.0, -1.0, 0.0);
+
+    /// A reading every 10ms starting at `start_ms`
+    fn reading(index: u64, start_ms: u64, angular_velocity: (f32, f32, f32)) -> MotionState {
+        MotionState::new(
+            angular_velocity,
+            STILL,
+            Duration::from_millis(start_ms + index * 10),
+        )
+    }
+
+    fn assert_close(a: (f32, f32, f32), b: (f32, f32, f32)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4 && (a.2 - b.2).abs() < 1e-4,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn capture_averages_every_reading() {
+        let mut calibrator = GyroCalibrator::new();
+        calibrator.set_bias((9.0, 9.0, 9.0));
+        calibrator.begin_capture();
+        assert!(calibrator.is_capturing());
+
+        for (i, offset) in [-0.5, 0.5, -0.25, 0.25].iter().enumerate() {
+            let reading = reading(i as u64, 0, (1.0 + offset, -2.0, 0.5 - offset));
+            // The bias only changes once the capture is finished
+            assert_close(calibrator.process(&reading).angular_velocity(), {
+                let (x, y, z) = reading.angular_velocity();
+                (x - 9.0, y - 9.0, z - 9.0)
+            });
+        }
+
+        assert_close(calibrator.finish_capture().unwrap(), (1.0, -2.0, 0.5));
+        assert_close(calibrator.bias(), (1.0, -2.0, 0.5));
+        assert!(!calibrator.is_capturing());
+        assert_eq!(calibrator.finish_capture(), None);
+
+        calibrator.begin_capture();
+        assert_eq!(calibrator.finish_capture(), None);
+        calibrator.begin_capture();
+        calibrator.sample(&reading(0, 0, (5.0, 5.0, 5.0)));
+        calibrator.cancel_capture();
+        assert_close(calibrator.bias(), (1.0, -2.0, 0.5));
+    }
+
+    #[test]
+    fn auto_calibration_converges_while_still() {
+        let mut calibrator = GyroCalibrator::new();
+        let drift = (0.3, -0.2, 0.1);
+
+        // Just short of a second of still readings
+        for i in 0..100 {
+            calibrator.sample(&reading(i, 0, drift));
+        }
+        assert_eq!(calibrator.bias(), (0.0, 0.0, 0.0));
+
+        calibrator.sample(&reading(100, 0, drift));
+        assert_close(calibrator.bias(), drift);
+        assert_close(
+            calibrator
+                .process(&reading(101, 0, drift))
+                .angular_velocity(),
+            (0.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn auto_calibration_restarts_on_motion() {
+        let mut calibrator = GyroCalibrator::new();
+
+        for i in 0..60 {
+            calibrator.sample(&reading(i, 0, (0.3, 0.0, 0.0)));
+        }
+        // Turning the gamepad starts a new window
+        calibrator.sample(&reading(60, 0, (45.0, 0.0, 0.0)));
+        for i in 61..120 {
+            calibrator.sample(&reading(i, 0, (0.3, 0.0, 0.0)));
+        }
+        assert_eq!(calibrator.bias(), (0.0, 0.0, 0.0));
+
+        // So does moving it without turning it, the window starts again once it is still
+        let mut moved = reading(120, 0, (0.3, 0.0, 0.0));
+        moved.acceleration = (0.5, -1.0, 0.0);
+        calibrator.sample(&moved);
+        for i in 121..221 {
+            calibrator.sample(&reading(i, 0, (0.3, 0.0, 0.0)));
+        }
+        assert_eq!(calibrator.bias(), (0.0, 0.0, 0.0));
+
+        calibrator.sample(&reading(221, 0, (0.3, 0.0, 0.0)));
+        assert_close(calibrator.bias(), (0.3, 0.0, 0.0));
+    }
+
+    #[test]
+    fn auto_calibration_can_be_turned_off() {
+        let mut calibrator = GyroCalibrator::new();
+        calibrator.set_auto_calibration(false);
+
+        for i in 0..200 {
+            calibrator.sample(&reading(i, 0, (0.3, 0.0, 0.0)));
+        }
+        assert_eq!(calibrator.bias(), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn mouse_movement_follows_the_rotation() {
+        let aim = GyroAim::new(2.0);
+        let motion = reading(0, 0, (10.0, -20.0, 30.0));
+
+        let (x, y) = aim.to_mouse(&motion, Duration::from_millis(500));
+        assert!((x - 20.0).abs() < 1e-4);
+        assert!((y - 10.0).abs() < 1e-4);
+        assert_eq!(aim.to_mouse(&motion, Duration::ZERO), (0.0, 0.0));
+    }
+
+    #[test]
+    fn tightening_scales_slow_rotations() {
+        let aim = GyroAim::new(1.0).with_tightening(10.0);
+
+        let (x, y) = aim.to_mouse(&reading(0, 0, (0.0, -5.0, 0.0)), Duration::from_secs(1));
+        assert!((x - 2.5).abs() < 1e-4);
+        assert_eq!(y, 0.0);
+
+        let (x, _) = aim.to_mouse(&reading(0, 0, (0.0, -20.0, 0.0)), Duration::from_secs(1));
+        assert!((x - 20.0).abs() < 1e-4);
+        assert_eq!(GyroAim::new(1.0).with_tightening(-1.0).tightening(), 0.0);
+    }
+
+    #[test]
+    fn joystick_deflection_is_clamped() {
+        let aim = GyroAim::new(1.0).with_full_deflection(100.0);
+
+        let (x, y) = aim.to_joystick(&reading(0, 0, (25.0, 50.0, 0.0)));
+        assert!((x + 0.5).abs() < 1e-4);
+        assert!((y - 0.25).abs() < 1e-4);
+
+        let (x, y) = aim.to_joystick(&reading(0, 0, (300.0, -400.0, 0.0)));
+        assert!((x - 0.8).abs() < 1e-4);
+        assert!((y - 0.6).abs() < 1e-4);
+
+        assert_eq!(
+            GyroAim::new(1.0)
+                .with_full_deflection(0.0)
+                .full_deflection(),
+            f32::EPSILON
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::calibration::JoystickCalibration;
+use crate::curve::ResponseCurve;
+use crate::deadzone::Deadzone;
+use crate::types::*;
+
+use std::collections::HashMap;
+
+/// Controls how the engine turns the input reported by a backend into a `GamepadState`.
+///
+/// Settings are kept per gamepad, see `GamepadEngine::settings_mut`, gamepads without
+/// their own settings use `GamepadEngine::default_settings`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadSettings {
+    pub(crate) press_threshold: f32,
+    pub(crate) joysticks: HashMap<Joystick, JoystickSettings>,
+}
+impl GamepadSettings {
+    pub fn new() -> Self {
+        GamepadSettings {
+            press_threshold: ButtonState::DEFAULT_PRESS_THRESHOLD,
+            joysticks: HashMap::new(),
+        }
+    }
+
+    /// The normalized value at which analog buttons, such as triggers, count as pressed
+    pub fn press_threshold(&self) -> f32 {
+        self.press_threshold
+    }
+
+    /// Sets the normalized value at which analog buttons, such as triggers, count as pressed.
+    ///
+    /// The threshold is clamped to the range 0.0 to 1.0, digital buttons always report
+    /// a value of 1.0 while held so they are not affected by it.
+    pub fn set_press_threshold(&mut self, threshold: f32) {
+        self.press_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    pub fn joystick(&self, joystick: Joystick) -> JoystickSettings {
+        self.joysticks.get(&joystick).cloned().unwrap_or_default()
+    }
+
+    pub fn joystick_mut(&mut self, joystick: Joystick) -> &mut JoystickSettings {
+        self.joysticks.entry(joystick).or_default()
+    }
+
+    pub fn deadzone(&self, joystick: Joystick) -> Deadzone {
+        self.joystick(joystick).deadzone
+    }
+
+    pub fn set_deadzone(&mut self, joystick: Joystick, deadzone: Deadzone) {
+        self.joystick_mut(joystick).deadzone = deadzone;
+    }
+
+    /// Uses the same deadzone for every joystick
+    pub fn set_deadzones(&mut self, deadzone: Deadzone) {
+        for joystick in [Joystick::Left, Joystick::Right].iter() {
+            self.set_deadzone(*joystick, deadzone);
+        }
+    }
+
+    /// Sets the curve applied to the distance of the joystick from its center.
+    ///
+    /// Settings holding a `ResponseCurve::Custom` curve fail to serialize.
+    pub fn set_response_curve(&mut self, joystick: Joystick, curve: ResponseCurve) {
+        self.joystick_mut(joystick).set_response_curve(curve);
+    }
+
+    /// Sets the curves applied to the x and y axis of the joystick on their own, like
+    /// `set_response_curve` custom curves can't be serialized
+    pub fn set_axis_response_curves(
+        &mut self,
+        joystick: Joystick,
+        x_curve: ResponseCurve,
+        y_curve: ResponseCurve,
+    ) {
+        self.joystick_mut(joystick)
+            .set_axis_response_curves(x_curve, y_curve);
+    }
+
+    pub fn calibration(&self, joystick: Joystick) -> Option<JoystickCalibration> {
+        self.joystick(joystick).calibration
+    }
+
+    /// Sets the calibration used to normalize the raw values of the joystick,
+    /// `None` uses the range declared by the backend
+    pub fn set_calibration(
+        &mut self,
+        joystick: Joystick,
+        calibration: Option<JoystickCalibration>,
+    ) {
+        self.joystick_mut(joystick).calibration = calibration;
+    }
+
+    /// Produces the state handed out by the engine from the state reported by the backend.
+    ///
+    /// `previous` is the processed state from the last update, it provides `was_pressed`.
+    pub(crate) fn process(
+        &self,
+        gamepad: &GamepadState,
+        previous: Option<&GamepadState>,
+    ) -> GamepadState {
+        let mut gamepad = gamepad.clone();
+
+        for (button, button_state) in gamepad.buttons.iter_mut() {
+            button_state.apply_threshold(self.press_threshold);
+            button_state.was_pressed = match previous {
+                Some(previous) => previous.is_pressed(*button),
+                None => false,
+            };
+        }
+
+        for (code, button_state) in gamepad.raw_buttons.iter_mut() {
+            button_state.apply_threshold(self.press_threshold);
+            button_state.was_pressed = previous
+                .and_then(|previous| previous.raw_buttons.get(code))
+                .is_some_and(|previous| previous.is_pressed);
+        }
+
+        for (joystick, joystick_state) in gamepad.joysticks.iter_mut() {
+            joystick_state.normalized_value = self.joystick(*joystick).process(joystick_state);
+        }
+
+        gamepad.previous_axes = match previous {
+            Some(previous) => Axis::ALL
+                .iter()
+                .map(|axis| (*axis, previous.axis(*axis)))
+                .collect(),
+            None => HashMap::new(),
+        };
+
+        gamepad
+    }
+}
+impl Default for GamepadSettings {
+    fn default() -> GamepadSettings {
+        GamepadSettings::new()
+    }
+}
+
+/// The processing applied to a joystick, in order: calibration, deadzone, response curve,
+/// axis response curves
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoystickSettings {
+    pub(crate) calibration: Option<JoystickCalibration>,
+    pub(crate) deadzone: Deadzone,
+    pub(crate) response_curve: ResponseCurve,
+    pub(crate) x_response_curve: ResponseCurve,
+    pub(crate) y_response_curve: ResponseCurve,
+}
+impl JoystickSettings {
+    pub fn calibration(&self) -> Option<JoystickCalibration> {
+        self.calibration
+    }
+
+    pub fn set_calibration(&mut self, calibration: Option<JoystickCalibration>) {
+        self.calibration = calibration;
+    }
+
+    pub fn deadzone(&self) -> Deadzone {
+        self.deadzone
+    }
+
+    pub fn set_deadzone(&mut self, deadzone: Deadzone) {
+        self.deadzone = deadzone;
+    }
+
+    /// The curve applied to the distance of the joystick from its center
+    pub fn response_curve(&self) -> &ResponseCurve {
+        &self.response_curve
+    }
+
+    /// Sets the curve applied to the distance of the joystick from its center, custom
+    /// curves can't be serialized
+    pub fn set_response_curve(&mut self, curve: ResponseCurve) {
+        self.response_curve = curve;
+    }
+
+    /// The curves applied to the x and y axis on their own
+    pub fn axis_response_curves(&self) -> (&ResponseCurve, &ResponseCurve) {
+        (&self.x_response_curve, &self.y_response_curve)
+    }
+
+    pub fn set_axis_response_curves(&mut self, x_curve: ResponseCurve, y_curve: ResponseCurve) {
+        self.x_response_curve = x_curve;
+        self.y_response_curve = y_curve;
+    }
+
+    /// Produces the normalized value of the joystick from the value reported by the backend
+    pub(crate) fn process(&self, joystick_state: &JoystickState) -> (f32, f32) {
+        match self.calibration {
+            Some(calibration) => self.apply(calibration.apply(joystick_state.raw_value)),
+            None => self.apply(joystick_state.normalized_value),
+        }
+    }
+
+    /// Applies the deadzone and response curves to a normalized joystick value
+    pub fn apply(&self, value: (f32, f32)) -> (f32, f32) {
+        let value = self.deadzone.apply(value);
+        let value = self.response_curve.apply_radial(value);
+
+        (
+            self.x_response_curve.apply(value.0),
+            self.y_response_curve.apply(value.1),
+        )
+    }
+}
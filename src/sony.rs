@@ -0,0 +1,1046 @@
+use crate::device::HatDirection;
+use crate::motion::MotionState;
+use crate::touchpad::{TouchContact, TouchpadState};
+use crate::types::*;
+
+use std::time::Duration;
+
+/// The USB vendor id of Sony
+pub const SONY_VENDOR_ID: u16 = 0x054c;
+
+/// The Playstation gamepads whose raw HID reports can be read
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SonyController {
+    DualShock4,
+    DualSense,
+}
+impl SonyController {
+    /// Identifies the gamepad from its USB product id, `None` for other Sony devices
+    pub fn from_product_id(product_id: u16) -> Option<Self> {
+        match product_id {
+            // DualShock 4 first and second revision, and the wireless adapter
+            0x05c4 | 0x09cc | 0x0ba0 => Some(SonyController::DualShock4),
+            // DualSense and DualSense Edge
+            0x0ce6 | 0x0df2 => Some(SonyController::DualSense),
+            _ => None,
+        }
+    }
+
+    /// The largest position the touchpad reports on each axis
+    fn touchpad_max(&self) -> (u16, u16) {
+        match self {
+            SonyController::DualShock4 => (1919, 941),
+            SonyController::DualSense => (1919, 1079),
+        }
+    }
+}
+
+/// How a HID gamepad is connected, which changes the layout of its reports
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HidConnection {
+    Usb,
+    Bluetooth,
+}
+
+/// A parsed input report of a Playstation gamepad
+#[derive(Clone, Debug)]
+pub struct SonyInputReport {
+    pub(crate) gamepad: GamepadState,
+    pub(crate) power_info: PowerInfo,
+    pub(crate) connection: HidConnection,
+}
+impl SonyInputReport {
+    /// The state of the gamepad, including its motion sensors and touchpad if the report
+    /// carries them
+    pub fn gamepad(&self) -> &GamepadState {
+        &self.gamepad
+    }
+
+    pub fn into_gamepad(self) -> GamepadState {
+        self.gamepad
+    }
+
+    /// The state of the battery, unknown for the reduced reports sent over Bluetooth
+    /// before the gamepad is switched to full reports
+    pub fn power_info(&self) -> PowerInfo {
+        self.power_info
+    }
+
+    /// The connection the report was sent over, as told by its report id and size
+    pub fn connection(&self) -> HidConnection {
+        self.connection
+    }
+}
+
+/// Turns raw HID input reports of a DualShock 4 or DualSense into gamepad states.
+///
+/// Reports are accepted as read from the device, starting with their report id. Bluetooth
+/// reports are checked against their CRC. The parser keeps the clock of the motion
+/// sensors running across reports, so a parser should be used for a single gamepad.
+///
+/// Motion is scaled by the nominal resolution of the sensors, leaving the gyroscope bias
+/// to `GyroCalibrator`.
+#[derive(Clone, Debug)]
+pub struct SonyReportParser {
+    controller: SonyController,
+    timestamp: Duration,
+    last_sensor_timestamp: Option<u32>,
+}
+impl SonyReportParser {
+    /// Angular velocity in sensor units per degree per second
+    const GYRO_RESOLUTION: f32 = 16.384;
+
+    /// Acceleration in sensor units per g
+    const ACCELERATION_RESOLUTION: f32 = 8192.0;
+
+    pub fn new(controller: SonyController) -> Self {
+        SonyReportParser {
+            controller,
+            timestamp: Duration::ZERO,
+            last_sensor_timestamp: None,
+        }
+    }
+
+    pub fn controller(&self) -> SonyController {
+        self.controller
+    }
+
+    pub fn parse(&mut self, report: &[u8]) -> Result<SonyInputReport, GamepadError> {
+        let report_id = *report
+            .first()
+            .ok_or_else(|| invalid_report("Empty report"))?;
+
+        match (self.controller, report_id) {
+            (SonyController::DualShock4, 0x01) if report.len() >= 64 => {
+                Ok(self.parse_dualshock4(&report[1..], HidConnection::Usb))
+            }
+            (SonyController::DualShock4, 0x11) => {
+                check_bluetooth_report(report, 78)?;
+                Ok(self.parse_dualshock4(&report[3..], HidConnection::Bluetooth))
+            }
+            (SonyController::DualSense, 0x01) if report.len() >= 64 => {
+                Ok(self.parse_dualsense(&report[1..], HidConnection::Usb))
+            }
+            (SonyController::DualSense, 0x31) => {
+                check_bluetooth_report(report, 78)?;
+                Ok(self.parse_dualsense(&report[2..], HidConnection::Bluetooth))
+            }
+            // Both gamepads start out sending reduced reports over Bluetooth
+            (_, 0x01) if report.len() >= 10 => Ok(SonyInputReport {
+                gamepad: parse_basic_input(&report[1..10]),
+                power_info: PowerInfo::Unknown,
+                connection: HidConnection::Bluetooth,
+            }),
+            (_, 0x01) => Err(invalid_report("Report is too short")),
+            _ => Err(GamepadError::new(
+                format!("Unsupported report id {:#04x}", report_id),
+                ErrorType::Unsupported,
+            )),
+        }
+    }
+
+    /// Parses the report data following the report id and, over Bluetooth, its header
+    fn parse_dualshock4(&mut self, data: &[u8], connection: HidConnection) -> SonyInputReport {
+        let mut gamepad = parse_basic_input(&data[..9]);
+
+        let sensor_timestamp = u16::from_le_bytes([data[9], data[10]]);
+        // The sensor clock counts in steps of 16/3 µs and wraps at 16 bits
+        let elapsed = self.advance_clock(sensor_timestamp as u32, u16::MAX as u32);
+        self.timestamp += Duration::from_nanos(elapsed * 16_000 / 3);
+        gamepad.set_motion(Some(self.motion(&data[12..24])));
+
+        let touch_reports = match connection {
+            HidConnection::Usb => 3,
+            HidConnection::Bluetooth => 4,
+        };
+        // The last touch report holds the most recent contacts
+        let touch_report = (data[32] as usize).clamp(1, touch_reports) - 1;
+        let points = &data[33 + touch_report * 9 + 1..][..8];
+        gamepad.set_touchpad(Some(self.touchpad(points)));
+
+        let status = data[29];
+        let level = status & 0x0f;
+        let capacity = (level * 10 + 5).min(100);
+        let power_info = if status & 0x10 != 0 {
+            match level {
+                0..=10 => PowerInfo::Charging(capacity),
+                11 => PowerInfo::Charged,
+                _ => PowerInfo::Unknown,
+            }
+        } else {
+            PowerInfo::Discharging(capacity)
+        };
+
+        SonyInputReport {
+            gamepad,
+            power_info,
+            connection,
+        }
+    }
+
+    /// Parses the report data following the report id and, over Bluetooth, its sequence number
+    fn parse_dualsense(&mut self, data: &[u8], connection: HidConnection) -> SonyInputReport {
+        // The triggers come right after the sticks, ahead of the buttons
+        let basic = [
+            data[0], data[1], data[2], data[3], data[7], data[8], data[9], data[4], data[5],
+        ];
+        let mut gamepad = parse_basic_input(&basic);
+        // The mute button
+        gamepad
+            .buttons
+            .insert(Button::Misc, ButtonState::new(data[9] & 0x04 != 0, false));
+        gamepad
+            .raw_buttons
+            .insert(18, ButtonState::new(data[9] & 0x04 != 0, false));
+
+        // The sensor clock counts in steps of 1/3 µs and wraps at 32 bits
+        let sensor_timestamp = u32::from_le_bytes([data[27], data[28], data[29], data[30]]);
+        let elapsed = self.advance_clock(sensor_timestamp, u32::MAX);
+        self.timestamp += Duration::from_nanos(elapsed * 1000 / 3);
+        gamepad.set_motion(Some(self.motion(&data[15..27])));
+
+        gamepad.set_touchpad(Some(self.touchpad(&data[32..40])));
+
+        let status = data[52];
+        let capacity = ((status & 0x0f) * 10 + 5).min(100);
+        let power_info = match status >> 4 {
+            0x0 => PowerInfo::Discharging(capacity),
+            0x1 => PowerInfo::Charging(capacity),
+            0x2 => PowerInfo::Charged,
+            _ => PowerInfo::Unknown,
+        };
+
+        SonyInputReport {
+            gamepad,
+            power_info,
+            connection,
+        }
+    }
+
+    /// Returns the sensor ticks since the previous report, handling the counter wrapping
+    /// around after `max`
+    fn advance_clock(&mut self, sensor_timestamp: u32, max: u32) -> u64 {
+        let elapsed = match self.last_sensor_timestamp {
+            Some(last) if sensor_timestamp >= last => (sensor_timestamp - last) as u64,
+            Some(last) => max as u64 - last as u64 + sensor_timestamp as u64 + 1,
+            None => 0,
+        };
+        self.last_sensor_timestamp = Some(sensor_timestamp);
+
+        elapsed
+    }
+
+    /// Reads three angular velocities followed by three accelerations
+    fn motion(&self, data: &[u8]) -> MotionState {
+        let value =
+            |index: usize| i16::from_le_bytes([data[index * 2], data[index * 2 + 1]]) as f32;
+        let gyro = |index: usize| value(index) / SonyReportParser::GYRO_RESOLUTION;
+        // The sensor reports the force holding the gamepad up, not gravity
+        let acceleration =
+            |index: usize| -value(index + 3) / SonyReportParser::ACCELERATION_RESOLUTION;
+
+        MotionState::new(
+            (gyro(0), gyro(1), gyro(2)),
+            (acceleration(0), acceleration(1), acceleration(2)),
+            self.timestamp,
+        )
+    }
+
+    /// Reads two touch points of four bytes each
+    fn touchpad(&self, points: &[u8]) -> TouchpadState {
+        let (max_x, max_y) = self.controller.touchpad_max();
+
+        let contacts = points
+            .chunks(4)
+            .map(|point| {
+                let x = point[1] as u16 | (point[2] as u16 & 0x0f) << 8;
+                let y = (point[2] as u16) >> 4 | (point[3] as u16) << 4;
+
+                TouchContact::new(
+                    (point[0] & 0x7f) as u32,
+                    (x as f32 / max_x as f32, 1.0 - y as f32 / max_y as f32),
+                    point[0] & 0x80 == 0,
+                )
+            })
+            .collect();
+
+        TouchpadState::new(contacts)
+    }
+}
+
+/// A trigger of a gamepad with adaptive triggers
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerSide {
+    Left,
+    Right,
+}
+
+/// The resistance an adaptive trigger of the DualSense puts up against being pressed.
+///
+/// Positions and strengths range from 0.0 to 1.0, positions are measured from the trigger
+/// at rest to fully pressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerEffect {
+    /// The trigger moves freely
+    #[default]
+    Off,
+    /// The trigger resists from `start` all the way down
+    Resistance { start: f32, force: f32 },
+    /// The trigger resists from `start` to `end` and gives way after, like the trigger
+    /// of a gun
+    SectionResistance { start: f32, end: f32, force: f32 },
+    /// The trigger vibrates from `start` all the way down, `frequency` is in Hz
+    Vibration {
+        start: f32,
+        amplitude: f32,
+        frequency: u8,
+    },
+}
+impl TriggerEffect {
+    /// The 11 bytes that describe the effect in an output report
+    fn to_bytes(self) -> [u8; 11] {
+        let byte = |value: f32| (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+
+        let mut bytes = [0; 11];
+        match self {
+            TriggerEffect::Off => bytes[0] = 0x05,
+            TriggerEffect::Resistance { start, force } => {
+                bytes[..3].copy_from_slice(&[0x01, byte(start), byte(force)]);
+            }
+            TriggerEffect::SectionResistance { start, end, force } => {
+                bytes[..4].copy_from_slice(&[0x02, byte(start), byte(end), byte(force)]);
+            }
+            TriggerEffect::Vibration {
+                start,
+                amplitude,
+                frequency,
+            } => {
+                bytes[..4].copy_from_slice(&[0x06, frequency, byte(amplitude), byte(start)]);
+            }
+        }
+
+        bytes
+    }
+}
+
+/// The state of the outputs of a Playstation gamepad: rumble motors, light bar, player
+/// LEDs and adaptive triggers, encoded into the output report the gamepad expects.
+///
+/// Every report sets every output, outputs a gamepad doesn't have are left out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SonyOutputReport {
+    pub(crate) rumble: (u8, u8),
+    pub(crate) light: (u8, u8, u8),
+    pub(crate) player_leds: u8,
+    pub(crate) left_trigger: TriggerEffect,
+    pub(crate) right_trigger: TriggerEffect,
+    pub(crate) sequence: u8,
+}
+impl SonyOutputReport {
+    pub fn new() -> Self {
+        SonyOutputReport::default()
+    }
+
+    /// Sets the magnitudes of the low frequency (`strong`) and high frequency (`weak`) motors
+    pub fn with_rumble(mut self, strong: u8, weak: u8) -> Self {
+        self.rumble = (strong, weak);
+        self
+    }
+
+    /// Sets the color of the light bar
+    pub fn with_light(mut self, light: (u8, u8, u8)) -> Self {
+        self.light = light;
+        self
+    }
+
+    /// Sets the five player LEDs of the DualSense, the lowest bit is the leftmost LED
+    pub fn with_player_leds(mut self, player_leds: u8) -> Self {
+        self.player_leds = player_leds & 0x1f;
+        self
+    }
+
+    /// Sets the effect of an adaptive trigger of the DualSense
+    pub fn with_trigger_effect(mut self, side: TriggerSide, effect: TriggerEffect) -> Self {
+        match side {
+            TriggerSide::Left => self.left_trigger = effect,
+            TriggerSide::Right => self.right_trigger = effect,
+        }
+        self
+    }
+
+    /// Sets the sequence number of DualSense Bluetooth reports, only the lowest four bits
+    /// are sent. Senders should count it up with every report.
+    pub fn with_sequence(mut self, sequence: u8) -> Self {
+        self.sequence = sequence & 0x0f;
+        self
+    }
+
+    pub fn rumble(&self) -> (u8, u8) {
+        self.rumble
+    }
+
+    pub fn light(&self) -> (u8, u8, u8) {
+        self.light
+    }
+
+    pub fn player_leds(&self) -> u8 {
+        self.player_leds
+    }
+
+    pub fn trigger_effect(&self, side: TriggerSide) -> TriggerEffect {
+        match side {
+            TriggerSide::Left => self.left_trigger,
+            TriggerSide::Right => self.right_trigger,
+        }
+    }
+
+    pub fn sequence(&self) -> u8 {
+        self.sequence
+    }
+
+    /// Encodes the report for the gamepad and connection, starting with its report id.
+    /// Bluetooth reports end with their CRC.
+    pub fn to_bytes(&self, controller: SonyController, connection: HidConnection) -> Vec<u8> {
+        match (controller, connection) {
+            (SonyController::DualShock4, HidConnection::Usb) => {
+                let mut report = vec![0; 32];
+                report[0] = 0x05;
+                report[1..12].copy_from_slice(&self.dualshock4_common());
+                report
+            }
+            (SonyController::DualShock4, HidConnection::Bluetooth) => {
+                let mut report = vec![0; 78];
+                report[0] = 0x11;
+                // Sent as a HID report with a CRC, polled every 4 ms
+                report[1] = 0xc4;
+                report[3..14].copy_from_slice(&self.dualshock4_common());
+                with_crc(report)
+            }
+            (SonyController::DualSense, HidConnection::Usb) => {
+                let mut report = vec![0; 63];
+                report[0] = 0x02;
+                report[1..48].copy_from_slice(&self.dualsense_common());
+                report
+            }
+            (SonyController::DualSense, HidConnection::Bluetooth) => {
+                let mut report = vec![0; 78];
+                report[0] = 0x31;
+                report[1] = self.sequence << 4;
+                report[2] = 0x10;
+                report[3..50].copy_from_slice(&self.dualsense_common());
+                with_crc(report)
+            }
+        }
+    }
+
+    fn dualshock4_common(&self) -> [u8; 11] {
+        let (red, green, blue) = self.light;
+
+        [
+            // Rumble and light bar are valid
+            0x03,
+            0x00,
+            0x00,
+            self.rumble.1,
+            self.rumble.0,
+            red,
+            green,
+            blue,
+            0x00,
+            0x00,
+            0x00,
+        ]
+    }
+
+    fn dualsense_common(&self) -> [u8; 47] {
+        let mut common = [0; 47];
+
+        // Rumble emulated by the haptics and both trigger effects are valid
+        common[0] = 0x01 | 0x02 | 0x04 | 0x08;
+        // The light bar and player LEDs are valid
+        common[1] = 0x04 | 0x10;
+        common[2] = self.rumble.1;
+        common[3] = self.rumble.0;
+        common[10..21].copy_from_slice(&self.right_trigger.to_bytes());
+        common[21..32].copy_from_slice(&self.left_trigger.to_bytes());
+        common[43] = self.player_leds;
+        common[44] = self.light.0;
+        common[45] = self.light.1;
+        common[46] = self.light.2;
+
+        common
+    }
+}
+
+/// Writes the CRC of a Bluetooth output report into its last four bytes, the CRC covers
+/// the report after the byte 0xa2 that starts every output report in the Bluetooth HID protocol
+fn with_crc(mut report: Vec<u8>) -> Vec<u8> {
+    let size = report.len();
+    let crc = crc32(0xa2, &report[..size - 4]);
+    report[size - 4..].copy_from_slice(&crc.to_le_bytes());
+
+    report
+}
+
+/// Parses the part every report shares: the sticks, three bytes of buttons and the triggers
+fn parse_basic_input(data: &[u8]) -> GamepadState {
+    let mut gamepad = GamepadState::new();
+
+    for (joystick, x, y) in [
+        (Joystick::Left, data[0], data[1]),
+        (Joystick::Right, data[2], data[3]),
+    ] {
+        // Down is positive in the report
+        let raw_value = (x as i16 - 128, 127 - y as i16);
+        gamepad
+            .joysticks
+            .insert(joystick, JoystickState::from_raw(raw_value, (-128, 127)));
+    }
+    for (index, value) in data[..4].iter().chain(&data[7..9]).enumerate() {
+        gamepad
+            .raw_axes
+            .insert(index as u32, *value as f32 / u8::MAX as f32 * 2.0 - 1.0);
+    }
+
+    let buttons = &data[4..7];
+    let bit = |byte: usize, bit: u8| buttons[byte] & (1 << bit) != 0;
+    for (button, byte, index) in [
+        (Button::West, 0, 4),
+        (Button::South, 0, 5),
+        (Button::East, 0, 6),
+        (Button::North, 0, 7),
+        (Button::LeftShoulder, 1, 0),
+        (Button::RightShoulder, 1, 1),
+        (Button::Share, 1, 4),
+        (Button::Start, 1, 5),
+        (Button::LeftStick, 1, 6),
+        (Button::RightStick, 1, 7),
+        (Button::Guide, 2, 0),
+        (Button::TouchpadClick, 2, 1),
+    ] {
+        gamepad
+            .buttons
+            .insert(button, ButtonState::new(bit(byte, index), false));
+    }
+    // Every bit but the hat, numbered from the lowest bit of the first button byte
+    for index in 4..18 {
+        gamepad.raw_buttons.insert(
+            index as u32,
+            ButtonState::new(bit(index / 8, (index % 8) as u8), false),
+        );
+    }
+
+    let hat = match buttons[0] & 0x0f {
+        0 => HatDirection::North,
+        1 => HatDirection::NorthEast,
+        2 => HatDirection::East,
+        3 => HatDirection::SouthEast,
+        4 => HatDirection::South,
+        5 => HatDirection::SouthWest,
+        6 => HatDirection::West,
+        7 => HatDirection::NorthWest,
+        _ => HatDirection::Centered,
+    };
+    for (button, pressed) in [
+        (Button::DPadNorth, hat.is_north()),
+        (Button::DPadSouth, hat.is_south()),
+        (Button::DPadWest, hat.is_west()),
+        (Button::DPadEast, hat.is_east()),
+    ] {
+        gamepad
+            .buttons
+            .insert(button, ButtonState::new(pressed, false));
+    }
+
+    for (button, value) in [
+        (Button::LeftTrigger, data[7]),
+        (Button::RightTrigger, data[8]),
+    ] {
+        gamepad.buttons.insert(
+            button,
+            ButtonState::analog(
+                value as f32 / u8::MAX as f32,
+                value as u16,
+                ButtonState::DEFAULT_PRESS_THRESHOLD,
+                false,
+            ),
+        );
+    }
+
+    gamepad
+}
+
+/// Checks the size and CRC of a Bluetooth report, the CRC covers the report after the
+/// byte 0xa1 that starts every input report in the Bluetooth HID protocol
+fn check_bluetooth_report(report: &[u8], size: usize) -> Result<(), GamepadError> {
+    if report.len() < size {
+        return Err(invalid_report("Report is too short"));
+    }
+
+    let (data, crc) = report[..size].split_at(size - 4);
+    let expected = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]);
+    if crc32(0xa1, data) != expected {
+        return Err(invalid_report("Report failed its CRC check"));
+    }
+
+    Ok(())
+}
+
+/// The CRC-32 used by Bluetooth HID reports, computed over `seed` followed by the bytes
+fn crc32(seed: u8, bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in std::iter::once(&seed).chain(bytes) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn invalid_report(msg: &str) -> GamepadError {
+    GamepadError::new(msg, ErrorType::InvalidReport)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::hex_fixture;
+
+    fn ds4_usb() -> Vec<u8> {
+        hex_fixture("sony/ds4_usb.hex")
+    }
+
+    fn ds4_bluetooth() -> Vec<u8> {
+        hex_fixture("sony/ds4_bluetooth.hex")
+    }
+
+    fn dualsense_usb() -> Vec<u8> {
+        hex_fixture("sony/dualsense_usb.hex")
+    }
+
+    fn dualsense_bluetooth() -> Vec<u8> {
+        hex_fixture("sony/dualsense_bluetooth.hex")
+    }
+
+    fn parse(controller: SonyController, report: &[u8]) -> SonyInputReport {
+        SonyReportParser::new(controller).parse(report).unwrap()
+    }
+
+    /// Rewrites the CRC of a Bluetooth input report after its bytes were changed
+    fn fix_crc(report: &mut [u8]) {
+        let size = report.len();
+        let crc = crc32(0xa1, &report[..size - 4]);
+        report[size - 4..].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn assert_close3(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        assert_close((actual.0, actual.1), (expected.0, expected.1));
+        assert_close((actual.2, 0.0), (expected.2, 0.0));
+    }
+
+    #[test]
+    fn fixtures_have_valid_crcs() {
+        assert_eq!(ds4_usb().len(), 64);
+        assert_eq!(dualsense_usb().len(), 64);
+        for report in [ds4_bluetooth(), dualsense_bluetooth()] {
+            assert_eq!(report.len(), 78);
+            assert!(check_bluetooth_report(&report, 78).is_ok());
+        }
+    }
+
+    #[test]
+    fn report_ids_select_the_layout() {
+        let usb = parse(SonyController::DualShock4, &ds4_usb());
+        assert_eq!(usb.connection(), HidConnection::Usb);
+        assert!(usb.gamepad().motion().is_some());
+
+        let bluetooth = parse(SonyController::DualShock4, &ds4_bluetooth());
+        assert_eq!(bluetooth.connection(), HidConnection::Bluetooth);
+        assert!(bluetooth.gamepad().motion().is_some());
+
+        let usb = parse(SonyController::DualSense, &dualsense_usb());
+        assert_eq!(usb.connection(), HidConnection::Usb);
+
+        let bluetooth = parse(SonyController::DualSense, &dualsense_bluetooth());
+        assert_eq!(bluetooth.connection(), HidConnection::Bluetooth);
+
+        // Before full reports are enabled, only the sticks, buttons and triggers are sent
+        let reduced = hex_fixture("sony/ds4_bluetooth_reduced.hex");
+        let reduced = parse(SonyController::DualShock4, &reduced);
+        assert_eq!(reduced.connection(), HidConnection::Bluetooth);
+        assert_eq!(reduced.power_info(), PowerInfo::Unknown);
+        assert!(reduced.gamepad().motion().is_none());
+        assert!(reduced.gamepad().is_pressed(Button::North));
+        assert!(reduced.gamepad().is_pressed(Button::DPadWest));
+        assert_close(reduced.gamepad().joystick(Joystick::Right), (-1.0, -1.0));
+        assert!(
+            (reduced.gamepad().button_value(Button::LeftTrigger) - 0x40 as f32 / 255.0).abs()
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn unexpected_reports_are_rejected() {
+        let mut parser = SonyReportParser::new(SonyController::DualShock4);
+
+        assert!(matches!(
+            parser.parse(&[]).unwrap_err().error_type,
+            ErrorType::InvalidReport
+        ));
+        assert!(matches!(
+            parser.parse(&[0x01, 0x80, 0x80]).unwrap_err().error_type,
+            ErrorType::InvalidReport
+        ));
+        assert!(matches!(
+            parser.parse(&ds4_bluetooth()[..60]).unwrap_err().error_type,
+            ErrorType::InvalidReport
+        ));
+        // The report id of the other gamepad
+        assert!(matches!(
+            parser.parse(&dualsense_bluetooth()).unwrap_err().error_type,
+            ErrorType::Unsupported
+        ));
+        assert!(matches!(
+            parser.parse(&[0x05; 32]).unwrap_err().error_type,
+            ErrorType::Unsupported
+        ));
+    }
+
+    #[test]
+    fn bluetooth_reports_with_a_bad_crc_are_rejected() {
+        for (controller, mut report) in [
+            (SonyController::DualShock4, ds4_bluetooth()),
+            (SonyController::DualSense, dualsense_bluetooth()),
+        ] {
+            report[5] ^= 0x01;
+            let error = SonyReportParser::new(controller)
+                .parse(&report)
+                .unwrap_err();
+            assert!(matches!(error.error_type, ErrorType::InvalidReport));
+
+            fix_crc(&mut report);
+            assert!(SonyReportParser::new(controller).parse(&report).is_ok());
+        }
+    }
+
+    #[test]
+    fn sticks_buttons_and_triggers() {
+        for report in [ds4_usb(), ds4_bluetooth()] {
+            let report = parse(SonyController::DualShock4, &report);
+            let gamepad = report.gamepad();
+
+            assert_close(gamepad.joystick(Joystick::Left), (1.0, 1.0));
+            assert_eq!(gamepad.joystick_raw(Joystick::Right), (0, -1));
+            assert!(gamepad.is_pressed(Button::South));
+            assert!(gamepad.is_pressed(Button::Start));
+            assert!(gamepad.is_pressed(Button::Guide));
+            assert!(gamepad.is_pressed(Button::DPadEast));
+            assert!(!gamepad.is_pressed(Button::DPadNorth));
+            assert!(!gamepad.is_pressed(Button::East));
+            assert!(!gamepad.is_pressed(Button::TouchpadClick));
+            assert_eq!(gamepad.button_value(Button::LeftTrigger), 0.0);
+            assert_eq!(gamepad.button_value(Button::RightTrigger), 1.0);
+            assert!(gamepad.is_pressed(Button::RightTrigger));
+        }
+
+        for report in [dualsense_usb(), dualsense_bluetooth()] {
+            let report = parse(SonyController::DualSense, &report);
+            let gamepad = report.gamepad();
+
+            assert_close(gamepad.joystick(Joystick::Right), (-1.0, -1.0));
+            assert!(gamepad.is_pressed(Button::North));
+            assert!(gamepad.is_pressed(Button::LeftShoulder));
+            assert!(gamepad.is_pressed(Button::Share));
+            assert!(gamepad.is_pressed(Button::Misc));
+            assert!(gamepad.raw_buttons()[&18].is_pressed());
+            assert!(!gamepad.is_pressed(Button::Guide));
+            for button in [
+                Button::DPadNorth,
+                Button::DPadSouth,
+                Button::DPadWest,
+                Button::DPadEast,
+            ] {
+                assert!(!gamepad.is_pressed(button));
+            }
+            assert!((gamepad.button_value(Button::LeftTrigger) - 128.0 / 255.0).abs() < 1e-5);
+            assert_eq!(gamepad.button_value(Button::RightTrigger), 0.0);
+        }
+    }
+
+    #[test]
+    fn motion_is_scaled_by_the_sensor_resolution() {
+        let report = parse(SonyController::DualShock4, &ds4_usb());
+        let motion = report.gamepad().motion().unwrap();
+        assert_close3(motion.angular_velocity(), (125.0, 0.0, -125.0));
+        // The sensor reports the force holding the gamepad up
+        assert_eq!(motion.acceleration(), (0.0, 1.0, 0.0));
+        assert_eq!(motion.timestamp(), Duration::ZERO);
+
+        let report = parse(SonyController::DualSense, &dualsense_bluetooth());
+        let motion = report.gamepad().motion().unwrap();
+        assert_close3(motion.angular_velocity(), (0.0, 0.0, -250.0));
+        assert_eq!(motion.acceleration(), (0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn motion_clock_follows_the_sensor_timestamp() {
+        let mut parser = SonyReportParser::new(SonyController::DualShock4);
+        let mut report = ds4_usb();
+        let mut timestamp_at = |ticks: u16| {
+            report[10..12].copy_from_slice(&ticks.to_le_bytes());
+            parser
+                .parse(&report)
+                .unwrap()
+                .gamepad()
+                .motion()
+                .unwrap()
+                .timestamp()
+        };
+
+        assert_eq!(timestamp_at(0xfffe), Duration::ZERO);
+        // Steps of 16/3 µs, across the counter wrapping around
+        assert_eq!(timestamp_at(0x0001), Duration::from_micros(16));
+
+        let mut parser = SonyReportParser::new(SonyController::DualSense);
+        let mut report = dualsense_usb();
+        let mut timestamp_at = |ticks: u32| {
+            report[28..32].copy_from_slice(&ticks.to_le_bytes());
+            parser
+                .parse(&report)
+                .unwrap()
+                .gamepad()
+                .motion()
+                .unwrap()
+                .timestamp()
+        };
+
+        assert_eq!(timestamp_at(3000), Duration::ZERO);
+        // Steps of 1/3 µs
+        assert_eq!(timestamp_at(6000), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn touch_points() {
+        let expected = (960.0 / 1919.0, 1.0 - 471.0 / 941.0);
+
+        for report in [ds4_usb(), ds4_bluetooth()] {
+            let report = parse(SonyController::DualShock4, &report);
+            let touchpad = report.gamepad().touchpad().unwrap();
+
+            // The Bluetooth fixture holds an older touch report first, the last one is used
+            assert_eq!(touchpad.contacts().len(), 2);
+            let contact = touchpad.contact(5).unwrap();
+            assert!(contact.is_pressed());
+            assert_close(contact.position(), expected);
+            assert!(!touchpad.contact(6).unwrap().is_pressed());
+            assert_eq!(touchpad.pressed_contacts().count(), 1);
+        }
+
+        let report = parse(SonyController::DualSense, &dualsense_usb());
+        let touchpad = report.gamepad().touchpad().unwrap();
+        let contact = touchpad.contact(1).unwrap();
+        assert!(contact.is_pressed());
+        assert_close(contact.position(), (1.0, 1.0));
+        let lifted = touchpad.contact(2).unwrap();
+        assert!(!lifted.is_pressed());
+        assert_close(lifted.position(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn battery() {
+        let power_info = |controller, report: &[u8]| parse(controller, report).power_info();
+
+        assert_eq!(
+            power_info(SonyController::DualShock4, &ds4_usb()),
+            PowerInfo::Charging(55)
+        );
+        assert_eq!(
+            power_info(SonyController::DualShock4, &ds4_bluetooth()),
+            PowerInfo::Discharging(85)
+        );
+        assert_eq!(
+            power_info(SonyController::DualSense, &dualsense_usb()),
+            PowerInfo::Charging(45)
+        );
+        assert_eq!(
+            power_info(SonyController::DualSense, &dualsense_bluetooth()),
+            PowerInfo::Discharging(75)
+        );
+
+        let mut report = ds4_usb();
+        report[30] = 0x1b;
+        assert_eq!(
+            power_info(SonyController::DualShock4, &report),
+            PowerInfo::Charged
+        );
+
+        let mut report = dualsense_usb();
+        report[53] = 0x2a;
+        assert_eq!(
+            power_info(SonyController::DualSense, &report),
+            PowerInfo::Charged
+        );
+        // The level is capped at 100
+        report[53] = 0x0a;
+        assert_eq!(
+            power_info(SonyController::DualSense, &report),
+            PowerInfo::Discharging(100)
+        );
+    }
+
+    fn output_report() -> SonyOutputReport {
+        SonyOutputReport::new()
+            .with_rumble(0x40, 0x80)
+            .with_light((0x10, 0x20, 0x30))
+            .with_player_leds(0x05)
+            .with_trigger_effect(
+                TriggerSide::Right,
+                TriggerEffect::Resistance {
+                    start: 0.2,
+                    force: 1.0,
+                },
+            )
+            .with_trigger_effect(
+                TriggerSide::Left,
+                TriggerEffect::SectionResistance {
+                    start: 0.25,
+                    end: 0.5,
+                    force: 0.5,
+                },
+            )
+            .with_sequence(3)
+    }
+
+    #[test]
+    fn output_reports_match_golden_bytes() {
+        for (controller, connection, expected) in [
+            (
+                SonyController::DualShock4,
+                HidConnection::Usb,
+                "sony/ds4_usb_output.hex",
+            ),
+            (
+                SonyController::DualShock4,
+                HidConnection::Bluetooth,
+                "sony/ds4_bluetooth_output.hex",
+            ),
+            (
+                SonyController::DualSense,
+                HidConnection::Usb,
+                "sony/dualsense_usb_output.hex",
+            ),
+            (
+                SonyController::DualSense,
+                HidConnection::Bluetooth,
+                "sony/dualsense_bluetooth_output.hex",
+            ),
+        ] {
+            assert_eq!(
+                output_report().to_bytes(controller, connection),
+                hex_fixture(expected),
+                "{:?} over {:?}",
+                controller,
+                connection
+            );
+        }
+    }
+
+    #[test]
+    fn bluetooth_output_reports_end_with_their_crc() {
+        for controller in [SonyController::DualShock4, SonyController::DualSense] {
+            let bytes = output_report().to_bytes(controller, HidConnection::Bluetooth);
+            let (data, crc) = bytes.split_at(bytes.len() - 4);
+
+            assert_eq!(crc, &crc32(0xa2, data).to_le_bytes()[..]);
+            // The seed of output reports differs from the one of input reports
+            assert_ne!(crc, &crc32(0xa1, data).to_le_bytes()[..]);
+        }
+
+        // The check value of the CRC-32, over the seed followed by the bytes
+        assert_eq!(crc32(b'1', b"23456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_at_four_bits() {
+        let bytes = output_report()
+            .with_sequence(0x1f)
+            .to_bytes(SonyController::DualSense, HidConnection::Bluetooth);
+
+        assert_eq!(bytes[1], 0xf0);
+    }
+
+    #[test]
+    fn trigger_effect_encodings() {
+        let bytes = |effect: TriggerEffect| effect.to_bytes();
+
+        assert_eq!(
+            bytes(TriggerEffect::Off),
+            [0x05, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            bytes(TriggerEffect::Resistance {
+                start: 0.0,
+                force: 0.5
+            }),
+            [0x01, 0x00, 0x80, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            bytes(TriggerEffect::SectionResistance {
+                start: 0.1,
+                end: 0.9,
+                force: 1.0
+            }),
+            [0x02, 0x1a, 0xe6, 0xff, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            bytes(TriggerEffect::Vibration {
+                start: 0.5,
+                amplitude: 1.0,
+                frequency: 30
+            }),
+            [0x06, 30, 0xff, 0x80, 0, 0, 0, 0, 0, 0, 0]
+        );
+        // Values outside of 0.0 to 1.0 are clamped
+        assert_eq!(
+            bytes(TriggerEffect::Resistance {
+                start: -1.0,
+                force: 2.0
+            }),
+            [0x01, 0x00, 0xff, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        // The DualShock 4 has no adaptive triggers, its report is unaffected by them
+        let plain = SonyOutputReport::new().with_rumble(0x40, 0x80);
+        let with_effect = plain.with_trigger_effect(
+            TriggerSide::Left,
+            TriggerEffect::Resistance {
+                start: 0.0,
+                force: 1.0,
+            },
+        );
+        assert_eq!(
+            plain.to_bytes(SonyController::DualShock4, HidConnection::Usb),
+            with_effect.to_bytes(SonyController::DualShock4, HidConnection::Usb)
+        );
+    }
+}
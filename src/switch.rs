@@ -0,0 +1,644 @@
+use crate::calibration::{AxisCalibration, JoystickCalibration};
+use crate::motion::MotionState;
+use crate::types::*;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The USB vendor id of Nintendo
+pub const NINTENDO_VENDOR_ID: u16 = 0x057e;
+
+/// The Nintendo Switch controllers whose raw HID reports can be read
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SwitchController {
+    ProController,
+    JoyConLeft,
+    JoyConRight,
+}
+impl SwitchController {
+    /// Identifies the controller from its USB product id, `None` for other Nintendo devices
+    pub fn from_product_id(product_id: u16) -> Option<Self> {
+        match product_id {
+            0x2009 => Some(SwitchController::ProController),
+            0x2006 => Some(SwitchController::JoyConLeft),
+            0x2007 => Some(SwitchController::JoyConRight),
+            _ => None,
+        }
+    }
+
+    fn has_joystick(&self, joystick: Joystick) -> bool {
+        !matches!(
+            (self, joystick),
+            (SwitchController::JoyConLeft, Joystick::Right)
+                | (SwitchController::JoyConRight, Joystick::Left)
+        )
+    }
+}
+
+/// A request sent to a Switch controller, wrapped in output report 0x01
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwitchSubcommand {
+    /// Selects the input reports the controller sends, 0x30 for standard full reports
+    SetInputReportMode(u8),
+    /// Turns the motion sensors on or off
+    EnableImu(bool),
+    /// Reads up to 29 bytes of the SPI flash, the controller replies with report 0x21
+    SpiRead { address: u32, length: u8 },
+}
+impl SwitchSubcommand {
+    /// Encodes the output report, `packet_counter` should be counted up with every report
+    /// sent and only its lowest four bits are sent. The rumble data is left neutral.
+    pub fn to_bytes(&self, packet_counter: u8) -> Vec<u8> {
+        let mut report = vec![0; 49];
+        report[0] = 0x01;
+        report[1] = packet_counter & 0x0f;
+        report[2..10].copy_from_slice(&[0x00, 0x01, 0x40, 0x40, 0x00, 0x01, 0x40, 0x40]);
+
+        match *self {
+            SwitchSubcommand::SetInputReportMode(mode) => {
+                report[10] = 0x03;
+                report[11] = mode;
+            }
+            SwitchSubcommand::EnableImu(enable) => {
+                report[10] = 0x40;
+                report[11] = enable as u8;
+            }
+            SwitchSubcommand::SpiRead { address, length } => {
+                report[10] = 0x10;
+                report[11..15].copy_from_slice(&address.to_le_bytes());
+                report[15] = length.min(0x1d);
+            }
+        }
+
+        report
+    }
+}
+
+/// A parsed input report of a Switch controller
+#[derive(Clone, Debug)]
+pub struct SwitchInputReport {
+    pub(crate) gamepad: GamepadState,
+    pub(crate) power_info: PowerInfo,
+}
+impl SwitchInputReport {
+    /// The state of the controller, including its motion sensors if the report carries them
+    pub fn gamepad(&self) -> &GamepadState {
+        &self.gamepad
+    }
+
+    pub fn into_gamepad(self) -> GamepadState {
+        self.gamepad
+    }
+
+    pub fn power_info(&self) -> PowerInfo {
+        self.power_info
+    }
+}
+
+/// Turns raw HID input reports of a Switch Pro Controller or Joy-Con into gamepad states.
+///
+/// Standard full input reports (0x30) and subcommand replies (0x21) are accepted as read
+/// from the device, starting with their report id. Replies to SPI flash reads of the stick
+/// calibration, see `FACTORY_STICK_CALIBRATION` and `USER_STICK_CALIBRATION`, are kept and
+/// used for the joysticks from then on. The user calibration wins over the factory one.
+///
+/// Joysticks keep their 12 bit raw values, from 0 to 4095 with up as positive.
+#[derive(Clone, Debug)]
+pub struct SwitchReportParser {
+    controller: SwitchController,
+    factory_calibration: HashMap<Joystick, JoystickCalibration>,
+    user_calibration: HashMap<Joystick, JoystickCalibration>,
+    timestamp: Duration,
+    last_timer: Option<u8>,
+}
+impl SwitchReportParser {
+    /// The SPI flash address and length of the factory stick calibration of both sticks
+    pub const FACTORY_STICK_CALIBRATION: (u32, u8) = (0x603d, 18);
+
+    /// The SPI flash address and length of the user stick calibration of both sticks,
+    /// each preceded by a two byte marker that tells if it is set
+    pub const USER_STICK_CALIBRATION: (u32, u8) = (0x8010, 22);
+
+    /// How far the sticks are assumed to reach from their center while no calibration
+    /// was read
+    const DEFAULT_STICK_RANGE: i16 = 1400;
+
+    /// Angular velocity in sensor units per degree per second, the gyroscope reports
+    /// 0.070 degrees per second per unit at its range of ±2000 degrees per second
+    const GYRO_RESOLUTION: f32 = 1.0 / 0.070;
+
+    /// Acceleration in sensor units per g
+    const ACCELERATION_RESOLUTION: f32 = 4096.0;
+
+    /// How often the controller counts up the timer of its reports
+    const TIMER_STEP: Duration = Duration::from_millis(5);
+
+    pub fn new(controller: SwitchController) -> Self {
+        SwitchReportParser {
+            controller,
+            factory_calibration: HashMap::new(),
+            user_calibration: HashMap::new(),
+            timestamp: Duration::ZERO,
+            last_timer: None,
+        }
+    }
+
+    pub fn controller(&self) -> SwitchController {
+        self.controller
+    }
+
+    /// The calibration applied to the joystick: the user calibration, the factory
+    /// calibration or a nominal one, in that order
+    pub fn calibration(&self, joystick: Joystick) -> JoystickCalibration {
+        self.user_calibration
+            .get(&joystick)
+            .or_else(|| self.factory_calibration.get(&joystick))
+            .copied()
+            .unwrap_or_else(|| {
+                let axis = AxisCalibration::new(
+                    2048,
+                    2048 - SwitchReportParser::DEFAULT_STICK_RANGE,
+                    2048 + SwitchReportParser::DEFAULT_STICK_RANGE,
+                );
+                JoystickCalibration::new(axis, axis)
+            })
+    }
+
+    /// Reads the stick calibration out of SPI flash data read from `address`, data that
+    /// doesn't cover a calibration block is ignored
+    pub fn apply_spi_data(&mut self, address: u32, data: &[u8]) {
+        let block = |start: u32, length: usize| {
+            let offset = start.checked_sub(address)? as usize;
+            data.get(offset..offset + length)
+        };
+        // Unset user calibrations are missing the marker
+        let user_block = |start: u32| match block(start, 11)? {
+            [0xb2, 0xa1, calibration @ ..] => Some(calibration),
+            _ => None,
+        };
+
+        for (joystick, factory, user) in [
+            (Joystick::Left, 0x603d, 0x8010),
+            (Joystick::Right, 0x6046, 0x801b),
+        ] {
+            if let Some(calibration) = block(factory, 9)
+                .and_then(|data| SwitchReportParser::parse_stick_calibration(data, joystick))
+            {
+                self.factory_calibration.insert(joystick, calibration);
+            }
+            if let Some(calibration) = user_block(user)
+                .and_then(|data| SwitchReportParser::parse_stick_calibration(data, joystick))
+            {
+                self.user_calibration.insert(joystick, calibration);
+            }
+        }
+    }
+
+    /// Reads a nine byte stick calibration block, `None` if the block is unset
+    pub fn parse_stick_calibration(data: &[u8], joystick: Joystick) -> Option<JoystickCalibration> {
+        if data.len() < 9 || data[..9].iter().all(|byte| *byte == 0xff) {
+            return None;
+        }
+
+        let values = [
+            SwitchReportParser::unpack_stick(&data[0..3]),
+            SwitchReportParser::unpack_stick(&data[3..6]),
+            SwitchReportParser::unpack_stick(&data[6..9]),
+        ];
+        // The blocks of the left and right stick list the same values in a different order
+        let (above, center, below) = match joystick {
+            Joystick::Left => (values[0], values[1], values[2]),
+            Joystick::Right => (values[2], values[0], values[1]),
+        };
+
+        let axis = |center: u16, above: u16, below: u16| {
+            let center = center as i16;
+            AxisCalibration::new(center, center - below as i16, center + above as i16)
+        };
+        Some(JoystickCalibration::new(
+            axis(center.0, above.0, below.0),
+            axis(center.1, above.1, below.1),
+        ))
+    }
+
+    /// Unpacks two 12 bit values from three bytes, as used by sticks and calibration blocks
+    pub fn unpack_stick(data: &[u8]) -> (u16, u16) {
+        let x = data[0] as u16 | (data[1] as u16 & 0x0f) << 8;
+        let y = (data[1] as u16) >> 4 | (data[2] as u16) << 4;
+
+        (x, y)
+    }
+
+    pub fn parse(&mut self, report: &[u8]) -> Result<SwitchInputReport, GamepadError> {
+        let report_id = *report
+            .first()
+            .ok_or_else(|| invalid_report("Empty report"))?;
+
+        match report_id {
+            0x30 if report.len() >= 49 => {
+                let mut input = self.parse_input(report);
+                // Three readings 5 ms apart, the last is the most recent
+                input.gamepad.set_motion(Some(self.motion(&report[37..49])));
+
+                Ok(input)
+            }
+            0x21 if report.len() >= 15 => {
+                // Acknowledged SPI flash reads carry the address, length and data
+                let (ack, subcommand) = (report[13], report[14]);
+                if ack & 0x80 != 0 && subcommand == 0x10 && report.len() >= 20 {
+                    let address =
+                        u32::from_le_bytes([report[15], report[16], report[17], report[18]]);
+                    let length = report[19] as usize;
+                    let data = &report[20..];
+                    self.apply_spi_data(address, &data[..length.min(data.len())]);
+                }
+
+                Ok(self.parse_input(report))
+            }
+            0x30 | 0x21 => Err(invalid_report("Report is too short")),
+            _ => Err(GamepadError::new(
+                format!("Unsupported report id {:#04x}", report_id),
+                ErrorType::Unsupported,
+            )),
+        }
+    }
+
+    /// Parses the part of the report every standard report shares: timer, battery, buttons
+    /// and sticks
+    fn parse_input(&mut self, report: &[u8]) -> SwitchInputReport {
+        let timer = report[1];
+        let elapsed = match self.last_timer {
+            Some(last) => timer.wrapping_sub(last) as u32,
+            None => 0,
+        };
+        self.last_timer = Some(timer);
+        self.timestamp += SwitchReportParser::TIMER_STEP * elapsed;
+
+        let mut gamepad = GamepadState::new();
+
+        let buttons = &report[3..6];
+        let bit = |byte: usize, bit: u8| buttons[byte] & (1 << bit) != 0;
+        for index in 0..24 {
+            gamepad.raw_buttons.insert(
+                index as u32,
+                ButtonState::new(bit(index / 8, (index % 8) as u8), false),
+            );
+        }
+
+        let right = [
+            (Button::West, 0, 0),
+            (Button::North, 0, 1),
+            (Button::South, 0, 2),
+            (Button::East, 0, 3),
+            (Button::RightShoulder, 0, 6),
+            (Button::RightTrigger, 0, 7),
+            (Button::Start, 1, 1),
+            (Button::RightStick, 1, 2),
+            (Button::Guide, 1, 4),
+        ];
+        let left = [
+            (Button::Select, 1, 0),
+            (Button::LeftStick, 1, 3),
+            (Button::Share, 1, 5),
+            (Button::DPadSouth, 2, 0),
+            (Button::DPadNorth, 2, 1),
+            (Button::DPadEast, 2, 2),
+            (Button::DPadWest, 2, 3),
+            (Button::LeftShoulder, 2, 6),
+            (Button::LeftTrigger, 2, 7),
+        ];
+        // The SL and SR buttons on the rail of each Joy-Con
+        let right_rail = [(Button::Other(4), 0, 4), (Button::Other(5), 0, 5)];
+        let left_rail = [(Button::Other(20), 2, 4), (Button::Other(21), 2, 5)];
+
+        let layout: Vec<(Button, usize, u8)> = match self.controller {
+            SwitchController::ProController => right.iter().chain(&left).copied().collect(),
+            SwitchController::JoyConLeft => left.iter().chain(&left_rail).copied().collect(),
+            SwitchController::JoyConRight => right.iter().chain(&right_rail).copied().collect(),
+        };
+        for (button, byte, index) in layout {
+            gamepad
+                .buttons
+                .insert(button, ButtonState::new(bit(byte, index), false));
+        }
+
+        for (joystick, data) in [
+            (Joystick::Left, &report[6..9]),
+            (Joystick::Right, &report[9..12]),
+        ] {
+            if !self.controller.has_joystick(joystick) {
+                continue;
+            }
+
+            let (x, y) = SwitchReportParser::unpack_stick(data);
+            let raw_value = (x as i16, y as i16);
+            gamepad.joysticks.insert(
+                joystick,
+                JoystickState {
+                    raw_value,
+                    raw_range: (0, 4095),
+                    normalized_value: self.calibration(joystick).apply(raw_value),
+                },
+            );
+        }
+
+        // The battery level counts in steps of two from 0 to 8, the lowest bit tells
+        // whether it is charging
+        let battery = report[2] >> 4;
+        let capacity = (battery >> 1).min(4) * 25;
+        let power_info = match (battery & 0x01 != 0, capacity) {
+            (true, 100) => PowerInfo::Charged,
+            (true, _) => PowerInfo::Charging(capacity),
+            (false, _) => PowerInfo::Discharging(capacity),
+        };
+
+        SwitchInputReport {
+            gamepad,
+            power_info,
+        }
+    }
+
+    /// Reads the accelerations followed by the angular velocities of a single reading, the
+    /// sensor axes point to the top, the left and the face of the controller
+    fn motion(&self, data: &[u8]) -> MotionState {
+        let value =
+            |index: usize| i16::from_le_bytes([data[index * 2], data[index * 2 + 1]]) as f32;
+        let acceleration =
+            |index: usize| value(index) / SwitchReportParser::ACCELERATION_RESOLUTION;
+        let gyro = |index: usize| value(index + 3) / SwitchReportParser::GYRO_RESOLUTION;
+
+        // The sensor reports the force holding the controller up, not gravity
+        MotionState::new(
+            (-gyro(1), gyro(2), -gyro(0)),
+            (acceleration(1), -acceleration(2), acceleration(0)),
+            self.timestamp,
+        )
+    }
+}
+
+fn invalid_report(msg: &str) -> GamepadError {
+    GamepadError::new(msg, ErrorType::InvalidReport)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::hex_fixture;
+
+    fn full_report() -> Vec<u8> {
+        hex_fixture("switch/pro_controller_0x30.hex")
+    }
+
+    fn factory_calibration() -> Vec<u8> {
+        hex_fixture("switch/factory_calibration_0x21.hex")
+    }
+
+    fn user_calibration() -> Vec<u8> {
+        hex_fixture("switch/user_calibration_0x21.hex")
+    }
+
+    fn axis(center: i16, min: i16, max: i16) -> AxisCalibration {
+        AxisCalibration::new(center, min, max)
+    }
+
+    fn assert_close(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        for (actual, expected) in [
+            (actual.0, expected.0),
+            (actual.1, expected.1),
+            (actual.2, expected.2),
+        ] {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn unpack_stick() {
+        assert_eq!(
+            SwitchReportParser::unpack_stick(&[0x78, 0x0d, 0x80]),
+            (3448, 2048)
+        );
+        assert_eq!(
+            SwitchReportParser::unpack_stick(&[0x00, 0x88, 0x28]),
+            (2048, 648)
+        );
+        assert_eq!(
+            SwitchReportParser::unpack_stick(&[0xff, 0xff, 0xff]),
+            (4095, 4095)
+        );
+        assert_eq!(
+            SwitchReportParser::unpack_stick(&[0x00, 0x00, 0x00]),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn full_input_report() {
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+        let input = parser.parse(&full_report()).unwrap();
+        let gamepad = input.gamepad();
+
+        for button in [
+            Button::East,
+            Button::RightTrigger,
+            Button::Start,
+            Button::Guide,
+            Button::DPadSouth,
+            Button::LeftShoulder,
+        ] {
+            assert!(gamepad.is_pressed(button), "{:?}", button);
+        }
+        for button in [
+            Button::South,
+            Button::West,
+            Button::Select,
+            Button::DPadNorth,
+            Button::LeftTrigger,
+        ] {
+            assert!(!gamepad.is_pressed(button), "{:?}", button);
+        }
+        assert!(gamepad.raw_buttons()[&3].is_pressed());
+        assert!(!gamepad.raw_buttons()[&4].is_pressed());
+
+        // Without a calibration the sticks reach 1400 from the center
+        assert_eq!(gamepad.joystick_raw(Joystick::Left), (3448, 2048));
+        assert_eq!(gamepad.joystick(Joystick::Left), (1.0, 0.0));
+        assert_eq!(gamepad.joystick_raw(Joystick::Right), (2048, 648));
+        assert_eq!(gamepad.joystick(Joystick::Right), (0.0, -1.0));
+
+        assert_eq!(input.power_info(), PowerInfo::Discharging(75));
+    }
+
+    #[test]
+    fn full_report_motion() {
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+        let input = parser.parse(&full_report()).unwrap();
+        let motion = input.gamepad().motion().unwrap();
+
+        // The latest of the three samples, at 0.070 degrees per second per unit
+        assert_close(motion.angular_velocity(), (98.0, 49.0, 0.0));
+        assert_close(motion.acceleration(), (0.0, 1.0, 1.0));
+        assert_eq!(motion.timestamp(), Duration::ZERO);
+
+        // The timer counts up every 5 ms and wraps around
+        let mut report = full_report();
+        report[1] = 0xfe;
+        parser.parse(&report).unwrap();
+        report[1] = 0x01;
+        let input = parser.parse(&report).unwrap();
+        assert_eq!(
+            input.gamepad().motion().unwrap().timestamp(),
+            Duration::from_millis(5 * (0xfe - 0x10 + 3))
+        );
+    }
+
+    #[test]
+    fn joy_cons_only_have_their_half() {
+        let mut parser = SwitchReportParser::new(SwitchController::JoyConLeft);
+        let mut report = full_report();
+        // SL on the rail of the left Joy-Con
+        report[5] |= 0x20;
+        let input = parser.parse(&report).unwrap();
+        let gamepad = input.gamepad();
+
+        assert!(gamepad.is_pressed(Button::DPadSouth));
+        assert!(gamepad.is_pressed(Button::Other(21)));
+        assert!(!gamepad.is_pressed(Button::East));
+        assert_eq!(gamepad.joystick_raw(Joystick::Left), (3448, 2048));
+        assert_eq!(gamepad.joystick_raw(Joystick::Right), (0, 0));
+
+        let mut parser = SwitchReportParser::new(SwitchController::JoyConRight);
+        let input = parser.parse(&full_report()).unwrap();
+        assert!(input.gamepad().is_pressed(Button::East));
+        assert!(!input.gamepad().is_pressed(Button::DPadSouth));
+        assert_eq!(input.gamepad().joystick_raw(Joystick::Left), (0, 0));
+    }
+
+    #[test]
+    fn unexpected_reports_are_rejected() {
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+
+        assert!(matches!(
+            parser.parse(&[]).unwrap_err().error_type,
+            ErrorType::InvalidReport
+        ));
+        assert!(matches!(
+            parser.parse(&full_report()[..48]).unwrap_err().error_type,
+            ErrorType::InvalidReport
+        ));
+        assert!(matches!(
+            parser.parse(&[0x3f; 12]).unwrap_err().error_type,
+            ErrorType::Unsupported
+        ));
+    }
+
+    #[test]
+    fn factory_calibration_reply() {
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+        parser.parse(&factory_calibration()).unwrap();
+
+        assert_eq!(
+            parser.calibration(Joystick::Left),
+            JoystickCalibration::new(axis(2000, 700, 3500), axis(2100, 750, 3500))
+        );
+        // The right stick block lists the center first and the range above last
+        assert_eq!(
+            parser.calibration(Joystick::Right),
+            JoystickCalibration::new(axis(2050, 600, 3530), axis(1990, 570, 3430))
+        );
+
+        let input = parser.parse(&full_report()).unwrap();
+        let (x, y) = input.gamepad().joystick(Joystick::Left);
+        assert!((x - 1448.0 / 1500.0).abs() < 1e-5);
+        assert!((y - (2048.0 - 2100.0) / 1350.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn user_calibration_wins_when_marked() {
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+        parser.parse(&factory_calibration()).unwrap();
+        parser.parse(&user_calibration()).unwrap();
+
+        assert_eq!(
+            parser.calibration(Joystick::Left),
+            JoystickCalibration::new(axis(2048, 1048, 3048), axis(2048, 1048, 3048))
+        );
+        // The right stick has no user calibration, its block lacks the 0xB2A1 marker
+        assert_eq!(
+            parser.calibration(Joystick::Right),
+            JoystickCalibration::new(axis(2050, 600, 3530), axis(1990, 570, 3430))
+        );
+
+        // A calibration without the marker is ignored even if its values are valid
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+        let mut reply = user_calibration();
+        reply[20..22].copy_from_slice(&[0xff, 0xff]);
+        parser.parse(&reply).unwrap();
+        assert_eq!(
+            parser.calibration(Joystick::Left),
+            JoystickCalibration::new(axis(2048, 648, 3448), axis(2048, 648, 3448))
+        );
+    }
+
+    #[test]
+    fn spi_data_is_matched_by_address() {
+        let data = &factory_calibration()[20..38];
+
+        // Data read from further in only covers the right stick block
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+        parser.apply_spi_data(0x603d + 9, &data[9..]);
+        assert_eq!(
+            parser.calibration(Joystick::Right),
+            JoystickCalibration::new(axis(2050, 600, 3530), axis(1990, 570, 3430))
+        );
+        assert_eq!(
+            parser.calibration(Joystick::Left),
+            JoystickCalibration::new(axis(2048, 648, 3448), axis(2048, 648, 3448))
+        );
+
+        // Truncated blocks are ignored
+        let mut parser = SwitchReportParser::new(SwitchController::ProController);
+        parser.apply_spi_data(0x603d, &data[..8]);
+        assert_eq!(
+            parser.calibration(Joystick::Left),
+            JoystickCalibration::new(axis(2048, 648, 3448), axis(2048, 648, 3448))
+        );
+
+        // Unset blocks are erased flash
+        assert_eq!(
+            SwitchReportParser::parse_stick_calibration(&[0xff; 9], Joystick::Left),
+            None
+        );
+    }
+
+    #[test]
+    fn subcommand_requests() {
+        let bytes = SwitchSubcommand::SpiRead {
+            address: 0x603d,
+            length: 18,
+        }
+        .to_bytes(0x13);
+
+        assert_eq!(bytes.len(), 49);
+        assert_eq!(
+            bytes[..16],
+            [
+                0x01, 0x03, 0x00, 0x01, 0x40, 0x40, 0x00, 0x01, 0x40, 0x40, 0x10, 0x3d, 0x60, 0x00,
+                0x00, 0x12
+            ]
+        );
+        assert_eq!(
+            SwitchSubcommand::SetInputReportMode(0x30).to_bytes(0)[10..12],
+            [0x03, 0x30]
+        );
+        assert_eq!(
+            SwitchSubcommand::EnableImu(true).to_bytes(0)[10..12],
+            [0x40, 0x01]
+        );
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::Path;
+
+/// Reads a fixture of hex bytes from `tests/fixtures`, `#` starts a comment
+pub(crate) fn hex_fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let fixture = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Can't read {}: {}", path.display(), e));
+
+    fixture
+        .lines()
+        .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace())
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,340 @@
+use std::time::Duration;
+
+/// A finger on a touchpad
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TouchContact {
+    pub(crate) id: u32,
+    pub(crate) position: (f32, f32),
+    pub(crate) is_pressed: bool,
+}
+impl TouchContact {
+    pub fn new(id: u32, position: (f32, f32), is_pressed: bool) -> Self {
+        TouchContact {
+            id,
+            position: (position.0.clamp(0.0, 1.0), position.1.clamp(0.0, 1.0)),
+            is_pressed,
+        }
+    }
+
+    /// Identifies the finger for as long as it touches the touchpad, a finger that touches
+    /// the touchpad again may be given a new id
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The position on the touchpad from 0.0 to 1.0 on both axes, the x-axis grows to
+    /// the right and the y-axis grows upwards
+    pub fn position(&self) -> (f32, f32) {
+        self.position
+    }
+
+    /// Whether the finger is touching the touchpad, the last position of a finger that
+    /// was lifted is kept by some gamepads
+    pub fn is_pressed(&self) -> bool {
+        self.is_pressed
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A gesture recognized on a touchpad, see `TouchGestureRecognizer`
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TouchGesture {
+    /// A single finger briefly touched the touchpad without moving
+    Tap { position: (f32, f32) },
+    /// Two fingers briefly touched the touchpad at the same time without moving
+    TwoFingerTap,
+    /// A single finger moved across the touchpad
+    Swipe(SwipeDirection),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TouchpadState {
+    pub(crate) contacts: Vec<TouchContact>,
+    pub(crate) gestures: Vec<TouchGesture>,
+}
+impl TouchpadState {
+    pub fn new(contacts: Vec<TouchContact>) -> Self {
+        TouchpadState {
+            contacts,
+            gestures: Vec::new(),
+        }
+    }
+
+    /// Every contact reported by the gamepad, including the ones that are not pressed
+    pub fn contacts(&self) -> &[TouchContact] {
+        &self.contacts
+    }
+
+    pub fn contacts_mut(&mut self) -> &mut Vec<TouchContact> {
+        &mut self.contacts
+    }
+
+    /// The contacts of the fingers currently touching the touchpad
+    pub fn pressed_contacts(&self) -> impl Iterator<Item = &TouchContact> {
+        self.contacts.iter().filter(|contact| contact.is_pressed)
+    }
+
+    pub fn contact(&self, id: u32) -> Option<&TouchContact> {
+        self.contacts.iter().find(|contact| contact.id == id)
+    }
+
+    /// The gestures completed during the last call to `GamepadEngine::update`
+    pub fn gestures(&self) -> &[TouchGesture] {
+        &self.gestures
+    }
+}
+
+/// Recognizes gestures from the touchpad states of successive updates.
+///
+/// A gesture begins when a finger touches the touchpad and is recognized once every
+/// finger has been lifted. Time only moves forward through `process`, which makes the
+/// recognized gestures fully deterministic.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TouchGestureRecognizer {
+    session: Option<TouchSession>,
+}
+impl TouchGestureRecognizer {
+    /// The longest time fingers may touch the touchpad for a tap
+    pub const TAP_DURATION: Duration = Duration::from_millis(250);
+
+    /// The farthest a finger may move for a tap, in touchpad widths and heights
+    pub const TAP_DISTANCE: f32 = 0.05;
+
+    /// The shortest distance a finger must move for a swipe, in touchpad widths and heights
+    pub const SWIPE_DISTANCE: f32 = 0.25;
+
+    pub fn new() -> Self {
+        TouchGestureRecognizer { session: None }
+    }
+
+    /// Records the touchpad state, `delta` is the time since the previous state.
+    ///
+    /// The gestures that were completed by this state are returned.
+    pub fn process(&mut self, touchpad: &TouchpadState, delta: Duration) -> Vec<TouchGesture> {
+        if let Some(session) = self.session.as_mut() {
+            session.elapsed += delta;
+        }
+
+        let pressed = touchpad.pressed_contacts().collect::<Vec<&TouchContact>>();
+        if pressed.is_empty() {
+            return match self.session.take() {
+                Some(session) => session.finish(),
+                None => Vec::new(),
+            };
+        }
+
+        let session = self.session.get_or_insert_with(TouchSession::default);
+        session.max_contacts = session.max_contacts.max(pressed.len());
+
+        for contact in pressed {
+            match session
+                .tracks
+                .iter_mut()
+                .find(|track| track.id == contact.id)
+            {
+                Some(track) => track.move_to(contact.position),
+                None => session.tracks.push(TouchTrack::new(contact)),
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// Forgets the gesture in progress
+    pub fn reset(&mut self) {
+        self.session = None;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct TouchSession {
+    elapsed: Duration,
+    max_contacts: usize,
+    tracks: Vec<TouchTrack>,
+}
+impl TouchSession {
+    fn finish(self) -> Vec<TouchGesture> {
+        let is_tap = self.elapsed <= TouchGestureRecognizer::TAP_DURATION
+            && self
+                .tracks
+                .iter()
+                .all(|track| track.max_distance <= TouchGestureRecognizer::TAP_DISTANCE);
+
+        match (self.max_contacts, self.tracks.first()) {
+            (1, Some(track)) if is_tap => vec![TouchGesture::Tap {
+                position: track.start,
+            }],
+            (1, Some(track)) => track.swipe().map(TouchGesture::Swipe).into_iter().collect(),
+            (2, _) if is_tap => vec![TouchGesture::TwoFingerTap],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct TouchTrack {
+    id: u32,
+    start: (f32, f32),
+    last: (f32, f32),
+    max_distance: f32,
+}
+impl TouchTrack {
+    fn new(contact: &TouchContact) -> Self {
+        TouchTrack {
+            id: contact.id,
+            start: contact.position,
+            last: contact.position,
+            max_distance: 0.0,
+        }
+    }
+
+    fn move_to(&mut self, position: (f32, f32)) {
+        let offset = (position.0 - self.start.0, position.1 - self.start.1);
+
+        self.last = position;
+        self.max_distance = self
+            .max_distance
+            .max((offset.0 * offset.0 + offset.1 * offset.1).sqrt());
+    }
+
+    fn swipe(&self) -> Option<SwipeDirection> {
+        let offset = (self.last.0 - self.start.0, self.last.1 - self.start.1);
+
+        if offset.0.abs() >= offset.1.abs() {
+            if offset.0 >= TouchGestureRecognizer::SWIPE_DISTANCE {
+                Some(SwipeDirection::Right)
+            } else if offset.0 <= -TouchGestureRecognizer::SWIPE_DISTANCE {
+                Some(SwipeDirection::Left)
+            } else {
+                None
+            }
+        } else if offset.1 >= TouchGestureRecognizer::SWIPE_DISTANCE {
+            Some(SwipeDirection::Up)
+        } else if offset.1 <= -TouchGestureRecognizer::SWIPE_DISTANCE {
+            Some(SwipeDirection::Down)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(10);
+
+    fn touch(contacts: &[(u32, (f32, f32))]) -> TouchpadState {
+        TouchpadState::new(
+            contacts
+                .iter()
+                .map(|(id, position)| TouchContact::new(*id, *position, true))
+                .collect(),
+        )
+    }
+
+    /// Feeds one state per frame, returning the gestures recognized along the way
+    fn recognize(states: &[TouchpadState]) -> Vec<TouchGesture> {
+        let mut recognizer = TouchGestureRecognizer::new();
+
+        states
+            .iter()
+            .flat_map(|state| recognizer.process(state, FRAME))
+            .collect()
+    }
+
+    #[test]
+    fn tap() {
+        let gestures = recognize(&[
+            touch(&[(0, (0.5, 0.5))]),
+            touch(&[(0, (0.52, 0.5))]),
+            touch(&[]),
+        ]);
+
+        assert_eq!(
+            gestures,
+            vec![TouchGesture::Tap {
+                position: (0.5, 0.5)
+            }]
+        );
+    }
+
+    #[test]
+    fn long_press_is_not_a_tap() {
+        let mut states = vec![touch(&[(0, (0.5, 0.5))]); 30];
+        states.push(touch(&[]));
+
+        assert!(recognize(&states).is_empty());
+    }
+
+    #[test]
+    fn moving_finger_is_not_a_tap() {
+        let gestures = recognize(&[
+            touch(&[(0, (0.5, 0.5))]),
+            touch(&[(0, (0.6, 0.5))]),
+            touch(&[]),
+        ]);
+
+        assert!(gestures.is_empty());
+    }
+
+    #[test]
+    fn two_finger_tap() {
+        let gestures = recognize(&[
+            touch(&[(0, (0.3, 0.5))]),
+            touch(&[(0, (0.3, 0.5)), (1, (0.7, 0.5))]),
+            touch(&[(1, (0.7, 0.5))]),
+            touch(&[]),
+        ]);
+
+        assert_eq!(gestures, vec![TouchGesture::TwoFingerTap]);
+    }
+
+    #[test]
+    fn swipes() {
+        let swipe =
+            |to: (f32, f32)| recognize(&[touch(&[(0, (0.5, 0.5))]), touch(&[(0, to)]), touch(&[])]);
+
+        assert_eq!(
+            swipe((0.8, 0.55)),
+            vec![TouchGesture::Swipe(SwipeDirection::Right)]
+        );
+        assert_eq!(
+            swipe((0.2, 0.45)),
+            vec![TouchGesture::Swipe(SwipeDirection::Left)]
+        );
+        assert_eq!(
+            swipe((0.55, 0.8)),
+            vec![TouchGesture::Swipe(SwipeDirection::Up)]
+        );
+        assert_eq!(
+            swipe((0.45, 0.2)),
+            vec![TouchGesture::Swipe(SwipeDirection::Down)]
+        );
+    }
+
+    #[test]
+    fn short_swipe_is_ignored() {
+        let gestures = recognize(&[
+            touch(&[(0, (0.5, 0.5))]),
+            touch(&[(0, (0.7, 0.5))]),
+            touch(&[]),
+        ]);
+
+        assert!(gestures.is_empty());
+    }
+}
@@ -1,12 +1,30 @@
+use crate::device::HatDirection;
+use crate::motion::MotionState;
+use crate::touchpad::TouchpadState;
+
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ErrorType {
     GamepadNotConnected { slot: u8 },
+    GamepadNotFound { id: GamepadId },
+    InvalidCalibration,
+    InvalidBackendName,
+    InvalidButtonName,
+    InvalidGuid,
+    InvalidMapping,
+    InvalidReport,
+    Io,
+    Unsupported, // The backend or device does not support the requested feature
+    ForceFeedback,
     Unknown, // Uncommon errors not documented by gamepad lib
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GamepadError {
     pub msg: String,
     pub error_type: ErrorType,
@@ -20,22 +38,213 @@ impl GamepadError {
     }
 }
 
+/// Identifies a gamepad for as long as it stays connected.
+///
+/// Ids are handed out by the backend and are not reused by a different gamepad while
+/// the original one is connected, so they are safe to keep around as a player handle.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadId(pub(crate) usize);
+impl GamepadId {
+    pub fn new(id: usize) -> Self {
+        GamepadId(id)
+    }
+
+    pub fn value(&self) -> usize {
+        self.0
+    }
+}
+impl fmt::Display for GamepadId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A 128 bit device identifier using the layout of SDL joystick GUIDs.
+///
+/// For most USB and Bluetooth devices the GUID embeds the vendor and product ids, which is
+/// what makes it suitable as a key for per device settings.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Guid(pub [u8; 16]);
+impl Guid {
+    pub fn bytes(&self) -> [u8; 16] {
+        self.0
+    }
+
+    /// The USB vendor id, `None` if the GUID does not embed one
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.embedded_ids().map(|(vendor_id, _)| vendor_id)
+    }
+
+    /// The USB product id, `None` if the GUID does not embed one
+    pub fn product_id(&self) -> Option<u16> {
+        self.embedded_ids().map(|(_, product_id)| product_id)
+    }
+
+    /// SDL stores the vendor and product ids as little endian words 2 and 4,
+    /// followed by zero padding in words 3 and 5.
+    fn embedded_ids(&self) -> Option<(u16, u16)> {
+        let word = |i: usize| u16::from_le_bytes([self.0[i * 2], self.0[i * 2 + 1]]);
+
+        if word(3) != 0 || word(5) != 0 || word(2) == 0 {
+            return None;
+        }
+
+        Some((word(2), word(4)))
+    }
+}
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+impl FromStr for Guid {
+    type Err = GamepadError;
+
+    /// Reads a GUID written as 32 hexadecimal digits, as in SDL mappings
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || GamepadError::new(format!("Invalid GUID: {}", s), ErrorType::InvalidGuid);
+
+        let s = s.trim();
+        if s.len() != 32 || !s.is_ascii() {
+            return Err(invalid());
+        }
+
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+
+        Ok(Guid(bytes))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PowerInfo {
+    /// The backend could not determine the power status
+    #[default]
+    Unknown,
+    /// The gamepad has no battery
+    Wired,
+    /// The gamepad is running on its battery, with the remaining charge in percent
+    Discharging(u8),
+    /// The battery is charging, with the current charge in percent
+    Charging(u8),
+    /// The battery is fully charged
+    Charged,
+}
+/// Describes the device behind a gamepad, see `GamepadEngine::gamepad_info`
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadInfo {
+    pub(crate) name: String,
+    pub(crate) guid: Guid,
+    pub(crate) power_info: PowerInfo,
+}
+impl GamepadInfo {
+    pub fn new<T: Into<String>>(name: T, guid: Guid, power_info: PowerInfo) -> Self {
+        GamepadInfo {
+            name: name.into(),
+            guid,
+            power_info,
+        }
+    }
+
+    /// The name of the device as reported by the backend
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn guid(&self) -> Guid {
+        self.guid
+    }
+
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.guid.vendor_id()
+    }
+
+    pub fn product_id(&self) -> Option<u16> {
+        self.guid.product_id()
+    }
+
+    pub fn power_info(&self) -> PowerInfo {
+        self.power_info
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GamepadEvent {
-    Connected {},
-    Disconnected {},
+    Connected {
+        id: GamepadId,
+        name: String,
+    },
+    Disconnected {
+        id: GamepadId,
+        name: String,
+    },
+    ButtonPressed {
+        id: GamepadId,
+        button: Button,
+    },
+    ButtonReleased {
+        id: GamepadId,
+        button: Button,
+    },
+    /// The normalized value of a button changed, this is sent alongside pressed and released events
+    ButtonChanged {
+        id: GamepadId,
+        button: Button,
+        value: f32,
+    },
+    /// The normalized value of a joystick changed
+    AxisChanged {
+        id: GamepadId,
+        joystick: Joystick,
+        value: (f32, f32),
+    },
+}
+impl GamepadEvent {
+    /// The id of the gamepad this event belongs to
+    pub fn id(&self) -> GamepadId {
+        match self {
+            GamepadEvent::Connected { id, .. } => *id,
+            GamepadEvent::Disconnected { id, .. } => *id,
+            GamepadEvent::ButtonPressed { id, .. } => *id,
+            GamepadEvent::ButtonReleased { id, .. } => *id,
+            GamepadEvent::ButtonChanged { id, .. } => *id,
+            GamepadEvent::AxisChanged { id, .. } => *id,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GamepadState {
     pub(crate) buttons: HashMap<Button, ButtonState>,
     pub(crate) joysticks: HashMap<Joystick, JoystickState>,
+    pub(crate) raw_buttons: HashMap<u32, ButtonState>,
+    pub(crate) raw_axes: HashMap<u32, f32>,
+    pub(crate) motion: Option<MotionState>,
+    pub(crate) touchpad: Option<TouchpadState>,
+    pub(crate) previous_axes: HashMap<Axis, f32>,
 }
 impl GamepadState {
     pub fn new() -> Self {
         GamepadState {
             buttons: HashMap::new(),
             joysticks: HashMap::new(),
+            raw_buttons: HashMap::new(),
+            raw_axes: HashMap::new(),
+            motion: None,
+            touchpad: None,
+            previous_axes: HashMap::new(),
         }
     }
 
@@ -63,6 +272,54 @@ impl GamepadState {
         (0, 0)
     }
 
+    /// Every button reported by the backend keyed by its backend code, including the ones
+    /// that are also available through `buttons`.
+    ///
+    /// Codes are specific to the backend and platform, they are meant for binding hardware
+    /// this crate has no name for, such as the buttons of flight sticks and wheels.
+    pub fn raw_buttons(&self) -> &HashMap<u32, ButtonState> {
+        &self.raw_buttons
+    }
+
+    pub fn raw_buttons_mut(&mut self) -> &mut HashMap<u32, ButtonState> {
+        &mut self.raw_buttons
+    }
+
+    /// Every axis reported by the backend keyed by its backend code, with the value
+    /// normalized by the backend but without any deadzone or calibration applied
+    pub fn raw_axes(&self) -> &HashMap<u32, f32> {
+        &self.raw_axes
+    }
+
+    pub fn raw_axes_mut(&mut self) -> &mut HashMap<u32, f32> {
+        &mut self.raw_axes
+    }
+
+    /// The value of the axis with the given backend code, 0.0 if it was never reported
+    pub fn raw_axis(&self, code: u32) -> f32 {
+        self.raw_axes.get(&code).copied().unwrap_or(0.0)
+    }
+
+    /// The latest reading of the gamepad's motion sensors with the gyroscope bias removed,
+    /// `None` if the gamepad has no motion sensors or the backend can't read them
+    pub fn motion(&self) -> Option<&MotionState> {
+        self.motion.as_ref()
+    }
+
+    pub fn set_motion(&mut self, motion: Option<MotionState>) {
+        self.motion = motion;
+    }
+
+    /// The fingers on the gamepad's touchpad and the gestures they completed,
+    /// `None` if the gamepad has no touchpad or the backend can't read it
+    pub fn touchpad(&self) -> Option<&TouchpadState> {
+        self.touchpad.as_ref()
+    }
+
+    pub fn set_touchpad(&mut self, touchpad: Option<TouchpadState>) {
+        self.touchpad = touchpad;
+    }
+
     pub fn joysticks(&self) -> &HashMap<Joystick, JoystickState> {
         &self.joysticks
     }
@@ -90,42 +347,157 @@ impl GamepadState {
             None => false,
         }
     }
+
+    /// The normalized value of the button, ranging from 0.0 (released) to 1.0 (fully pressed)
+    pub fn button_value(&self, button: Button) -> f32 {
+        match self.buttons.get(&button) {
+            Some(button_state) => button_state.value,
+            None => 0.0,
+        }
+    }
+
+    /// The direction the D-pad is pushed in.
+    ///
+    /// Backends report the D-pad through the `DPad*` buttons, whether the device
+    /// sends it as buttons, as a hat or as a pair of axes.
+    pub fn hat(&self) -> HatDirection {
+        HatDirection::from_buttons(
+            self.is_pressed(Button::DPadNorth),
+            self.is_pressed(Button::DPadSouth),
+            self.is_pressed(Button::DPadWest),
+            self.is_pressed(Button::DPadEast),
+        )
+    }
+
+    /// The value of the axis, stick and D-pad axes range from -1.0 to 1.0 while
+    /// trigger axes range from 0.0 (released) to 1.0 (fully pressed)
+    pub fn axis(&self, axis: Axis) -> f32 {
+        match axis {
+            Axis::LeftStickX => self.joystick(Joystick::Left).0,
+            Axis::LeftStickY => self.joystick(Joystick::Left).1,
+            Axis::RightStickX => self.joystick(Joystick::Right).0,
+            Axis::RightStickY => self.joystick(Joystick::Right).1,
+            Axis::LeftTrigger => self.button_value(Button::LeftTrigger),
+            Axis::RightTrigger => self.button_value(Button::RightTrigger),
+            Axis::DPadX => self.hat().to_axes().0 as f32,
+            Axis::DPadY => self.hat().to_axes().1 as f32,
+        }
+    }
+
+    /// The value the axis had on the previous update, see `axis`
+    pub fn previous_axis(&self, axis: Axis) -> f32 {
+        self.previous_axes.get(&axis).copied().unwrap_or(0.0)
+    }
+}
+impl Default for GamepadState {
+    fn default() -> GamepadState {
+        GamepadState::new()
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct JoystickState {
     pub(crate) raw_value: (i16, i16),
+    pub(crate) raw_range: (i16, i16),
     pub(crate) normalized_value: (f32, f32),
 }
 impl JoystickState {
+    /// The range of raw values assumed when a backend does not declare one
+    pub const DEFAULT_RAW_RANGE: (i16, i16) = (i16::MIN, i16::MAX);
+
     pub fn new(raw_value: (i16, i16), normalized_value: (f32, f32)) -> Self {
         JoystickState {
             raw_value,
+            raw_range: JoystickState::DEFAULT_RAW_RANGE,
             normalized_value,
         }
     }
+
+    /// Creates the state from a raw value in device units, `raw_range` is the inclusive
+    /// range the device reports its values in.
+    ///
+    /// The normalized value is scaled linearly from the range, use a calibration
+    /// if the device is not centered in the middle of it.
+    pub fn from_raw(raw_value: (i16, i16), raw_range: (i16, i16)) -> Self {
+        let normalize = |raw: i16| {
+            let (min, max) = (raw_range.0 as f32, raw_range.1 as f32);
+            let center = (min + max) / 2.0;
+            let half_span = (max - min) / 2.0;
+
+            if half_span > 0.0 {
+                ((raw as f32 - center) / half_span).clamp(-1.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+
+        JoystickState {
+            raw_value,
+            raw_range,
+            normalized_value: (normalize(raw_value.0), normalize(raw_value.1)),
+        }
+    }
+
+    /// The value in device units as reported by the backend
+    pub fn raw_value(&self) -> (i16, i16) {
+        self.raw_value
+    }
+
+    /// The inclusive range the backend reports raw values in
+    pub fn raw_range(&self) -> (i16, i16) {
+        self.raw_range
+    }
+
+    /// The value after calibration, deadzones and response curves, ranging from -1.0 to 1.0
+    pub fn normalized_value(&self) -> (f32, f32) {
+        self.normalized_value
+    }
 }
 impl Default for JoystickState {
     fn default() -> JoystickState {
-        JoystickState {
-            raw_value: (0, 0),
-            normalized_value: (0.0, 0.0),
-        }
+        JoystickState::new((0, 0), (0.0, 0.0))
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ButtonState {
     pub(crate) is_pressed: bool,
     pub(crate) was_pressed: bool,
+    pub(crate) value: f32,
+    pub(crate) raw_value: u16,
 }
 impl ButtonState {
+    /// The default normalized value at which an analog button counts as pressed
+    pub const DEFAULT_PRESS_THRESHOLD: f32 = 0.5;
+
+    /// Creates the state of a digital button, its value is either 0.0 or 1.0
     pub fn new(is_pressed: bool, was_pressed: bool) -> Self {
         ButtonState {
             is_pressed,
             was_pressed,
+            value: if is_pressed { 1.0 } else { 0.0 },
+            raw_value: is_pressed as u16,
         }
     }
+
+    /// Creates the state of an analog button such as a trigger.
+    ///
+    /// `value` is clamped to the range 0.0 to 1.0, `raw_value` is the value as reported
+    /// by the backend. The button counts as pressed once `value` reaches `threshold`.
+    pub fn analog(value: f32, raw_value: u16, threshold: f32, was_pressed: bool) -> Self {
+        let mut state = ButtonState {
+            is_pressed: false,
+            was_pressed,
+            value: value.clamp(0.0, 1.0),
+            raw_value,
+        };
+        state.apply_threshold(threshold);
+
+        state
+    }
+
     pub fn is_pressed(&self) -> bool {
         self.is_pressed
     }
@@ -137,6 +509,20 @@ impl ButtonState {
     pub fn is_just_released(&self) -> bool {
         !self.is_pressed && self.was_pressed
     }
+
+    /// The normalized value of the button, ranging from 0.0 (released) to 1.0 (fully pressed)
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// The value of the button as reported by the backend
+    pub fn raw_value(&self) -> u16 {
+        self.raw_value
+    }
+
+    pub(crate) fn apply_threshold(&mut self, threshold: f32) {
+        self.is_pressed = self.value > 0.0 && self.value >= threshold;
+    }
 }
 impl Default for ButtonState {
     fn default() -> ButtonState {
@@ -144,6 +530,9 @@ impl Default for ButtonState {
     }
 }
 
+/// Buttons are written by name through `Display` and read back through `FromStr`, such as
+/// `south`, `dpad_north` or `left_shoulder`. Buttons without a name are written as
+/// `other_` followed by their code.
 #[derive(Clone, Debug, Hash, Copy, PartialEq, Eq)]
 pub enum Button {
     DPadNorth,
@@ -163,10 +552,200 @@ pub enum Button {
     Menu,
     Select,
     Start,
+    /// The button with the platform logo.
+    ///
+    /// * Nintendo: Home
+    /// * Playstation: PS
+    /// * XBox: Guide
+    Guide,
+    /// * Nintendo: Capture
+    /// * Playstation: Share (DualShock 4), Create (DualSense)
+    /// * XBox: Share
+    Share,
+    /// An additional button that varies by gamepad, such as the mute button of the DualSense
+    Misc,
+    /// The upper right back paddle (XBox Elite P1)
+    Paddle1,
+    /// The upper left back paddle (XBox Elite P3)
+    Paddle2,
+    /// The lower right back paddle (XBox Elite P2)
+    Paddle3,
+    /// The lower left back paddle (XBox Elite P4)
+    Paddle4,
+    /// Pressing down on the touchpad of Playstation gamepads
+    TouchpadClick,
+    /// An additional action button found on Sega style layouts
+    C,
+    /// An additional action button found on Sega style layouts
+    Z,
+    /// A button the crate has no name for, identified by its backend code.
+    ///
+    /// The code is the same one used as key in `GamepadState::raw_buttons`.
+    Other(u32),
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+impl Button {
+    const NAMES: [(Button, &'static str); 27] = [
+        (Button::DPadNorth, "dpad_north"),
+        (Button::DPadSouth, "dpad_south"),
+        (Button::DPadWest, "dpad_west"),
+        (Button::DPadEast, "dpad_east"),
+        (Button::North, "north"),
+        (Button::South, "south"),
+        (Button::West, "west"),
+        (Button::East, "east"),
+        (Button::LeftShoulder, "left_shoulder"),
+        (Button::RightShoulder, "right_shoulder"),
+        (Button::LeftTrigger, "left_trigger"),
+        (Button::RightTrigger, "right_trigger"),
+        (Button::RightStick, "right_stick"),
+        (Button::LeftStick, "left_stick"),
+        (Button::Menu, "menu"),
+        (Button::Select, "select"),
+        (Button::Start, "start"),
+        (Button::Guide, "guide"),
+        (Button::Share, "share"),
+        (Button::Misc, "misc"),
+        (Button::Paddle1, "paddle1"),
+        (Button::Paddle2, "paddle2"),
+        (Button::Paddle3, "paddle3"),
+        (Button::Paddle4, "paddle4"),
+        (Button::TouchpadClick, "touchpad_click"),
+        (Button::C, "c"),
+        (Button::Z, "z"),
+    ];
+
+    /// Orders named buttons as they are declared, followed by the other buttons by code
+    pub(crate) fn order(&self) -> (usize, u32) {
+        match self {
+            Button::Other(code) => (Button::NAMES.len(), *code),
+            button => (
+                Button::NAMES
+                    .iter()
+                    .position(|(named, _)| named == button)
+                    .unwrap_or(Button::NAMES.len()),
+                0,
+            ),
+        }
+    }
+}
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Button::Other(code) => write!(f, "other_{}", code),
+            button => {
+                let name = Button::NAMES
+                    .iter()
+                    .find(|(named, _)| named == button)
+                    .map_or("", |(_, name)| name);
+
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+impl FromStr for Button {
+    type Err = GamepadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+
+        if let Some(code) = name.strip_prefix("other_") {
+            if let Ok(code) = code.parse::<u32>() {
+                return Ok(Button::Other(code));
+            }
+        }
+
+        Button::NAMES
+            .iter()
+            .find(|(_, button_name)| *button_name == name)
+            .map(|(button, _)| *button)
+            .ok_or_else(|| {
+                GamepadError::new(
+                    format!("Invalid button name: {}", s),
+                    ErrorType::InvalidButtonName,
+                )
+            })
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for Button {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Button {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+
+        name.parse()
+            .map_err(|error: GamepadError| serde::de::Error::custom(error.msg))
+    }
+}
+
+#[derive(Clone, Debug, Hash, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Joystick {
     Left,
     Right,
 }
+
+/// A single analog input of a gamepad, read through `GamepadState::axis`.
+///
+/// Positive values are to the right (x-axis) or up (y-axis).
+#[derive(Clone, Debug, Hash, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Axis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+    DPadX,
+    DPadY,
+}
+impl Axis {
+    pub const ALL: [Axis; 8] = [
+        Axis::LeftStickX,
+        Axis::LeftStickY,
+        Axis::RightStickX,
+        Axis::RightStickY,
+        Axis::LeftTrigger,
+        Axis::RightTrigger,
+        Axis::DPadX,
+        Axis::DPadY,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_names_round_trip() {
+        for (button, name) in Button::NAMES.iter() {
+            assert!(!matches!(button, Button::Other(_)));
+            assert_eq!(button.to_string(), *name);
+            assert_eq!(name.parse::<Button>().unwrap(), *button);
+        }
+    }
+
+    #[test]
+    fn other_buttons_round_trip_by_code() {
+        for code in [0, 1, 0x130, u32::MAX].iter() {
+            let button = Button::Other(*code);
+            assert_eq!(button.to_string().parse::<Button>().unwrap(), button);
+        }
+        assert_eq!(" other_7 ".parse::<Button>().unwrap(), Button::Other(7));
+    }
+
+    #[test]
+    fn invalid_button_names() {
+        for name in ["", "other_", "other_x", "other_-1", "North", "dpad"].iter() {
+            let error = name.parse::<Button>().unwrap_err();
+            assert!(matches!(error.error_type, ErrorType::InvalidButtonName));
+        }
+    }
+}
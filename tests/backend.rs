@@ -0,0 +1,115 @@
+use gamepad::*;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn engine_is_send_and_sync() {
+    assert_send_sync::<GamepadEngine>();
+}
+
+#[test]
+fn engine_can_move_between_threads() {
+    let id = GamepadId::new(0);
+    let mut backend = DummyBackend::new();
+    backend.gamepads_mut().insert(id, GamepadState::new());
+
+    let mut engine = GamepadEngine::with_backend(Box::new(backend));
+    let engine = std::thread::spawn(move || {
+        engine.update().unwrap();
+        engine
+    })
+    .join()
+    .unwrap();
+
+    assert!(engine.gamepad(id).is_some());
+}
+
+#[test]
+fn backend_is_reachable_through_the_engine() {
+    let id = GamepadId::new(0);
+    let mut backend = DummyBackend::new();
+    backend.gamepads_mut().insert(id, GamepadState::new());
+    let recording = backend.rumble_recording();
+
+    let mut engine = GamepadEngine::with_backend(Box::new(backend));
+    assert_eq!(
+        engine.backend().gamepads().keys().collect::<Vec<_>>(),
+        vec![&id]
+    );
+
+    engine
+        .backend_mut()
+        .set_rumble(id, 0.5, 0.5, Duration::from_millis(100))
+        .unwrap();
+    assert_eq!(recording.commands().len(), 1);
+
+    let mut backend = engine.into_backend();
+    backend.stop_rumble(id).unwrap();
+    assert_eq!(
+        recording.commands().last(),
+        Some(&RumbleCommand::Stop { id })
+    );
+    assert_eq!(backend.gamepads().len(), 1);
+}
+
+/// A backend implemented by the application, it only needs to be `Send + Sync`
+struct FixedBackend {
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+}
+impl GamepadEngineBackend for FixedBackend {
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
+    }
+}
+
+#[test]
+fn custom_backends_drive_the_engine() {
+    let id = GamepadId::new(3);
+    let mut gamepads = BTreeMap::new();
+    gamepads.insert(id, GamepadState::new());
+
+    let mut engine = GamepadEngine::with_backend(Box::new(FixedBackend { gamepads }));
+    engine.update().unwrap();
+
+    assert!(engine.gamepad(id).is_some());
+    assert!(engine.gamepad(GamepadId::new(0)).is_none());
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Action {
+    Jump,
+}
+
+#[test]
+fn actions_are_read_per_player() {
+    let (first, second) = (GamepadId::new(0), GamepadId::new(1));
+    let mut pressed = GamepadState::new();
+    pressed
+        .buttons_mut()
+        .insert(Button::South, ButtonState::new(true, false));
+
+    let mut backend = DummyBackend::new();
+    backend.gamepads_mut().insert(first, pressed);
+    backend.gamepads_mut().insert(second, GamepadState::new());
+
+    let mut engine = GamepadEngine::with_backend(Box::new(backend));
+    engine.update().unwrap();
+
+    let mut actions = ActionMap::new();
+    actions.bind(Action::Jump, Binding::Button(Button::South));
+
+    assert!(engine.action_pressed(&actions, &Action::Jump, first));
+    assert!(engine.action_just_pressed(&actions, &Action::Jump, first));
+    assert!(!engine.action_just_released(&actions, &Action::Jump, first));
+    assert_eq!(engine.action_value(&actions, &Action::Jump, first), 1.0);
+    assert!(!engine.action_pressed(&actions, &Action::Jump, second));
+    assert_eq!(engine.action_value(&actions, &Action::Jump, second), 0.0);
+
+    // Players whose gamepad is gone have no active actions
+    let missing = GamepadId::new(2);
+    assert!(!engine.action_pressed(&actions, &Action::Jump, missing));
+    assert_eq!(engine.action_value(&actions, &Action::Jump, missing), 0.0);
+}
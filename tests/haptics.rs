@@ -0,0 +1,43 @@
+use gamepad::*;
+
+use std::time::Duration;
+
+#[test]
+fn haptic_effects_follow_update_delta() {
+    let id = GamepadId::new(0);
+    let mut backend = DummyBackend::new();
+    backend.gamepads_mut().insert(id, GamepadState::new());
+    let recording = backend.rumble_recording();
+
+    let mut engine = GamepadEngine::with_backend(Box::new(backend));
+    let effect = HapticEffect::new(
+        0.5,
+        0.25,
+        HapticEnvelope::constant(Duration::from_millis(100)),
+    );
+    engine.update_with_delta(Duration::ZERO).unwrap();
+    engine.play_haptic(id, effect);
+
+    let rumble = RumbleCommand::Set {
+        id,
+        strong: 0.5,
+        weak: 0.25,
+        duration: Duration::from_millis(250),
+    };
+
+    engine.update_with_delta(Duration::ZERO).unwrap();
+    assert_eq!(recording.commands(), vec![rumble.clone()]);
+
+    // The same magnitudes are only sent again once the last request is about to expire
+    engine.update_with_delta(Duration::from_millis(50)).unwrap();
+    assert_eq!(recording.commands(), vec![rumble.clone()]);
+
+    engine.update_with_delta(Duration::from_millis(60)).unwrap();
+    assert_eq!(recording.commands(), vec![rumble.clone(), rumble.clone()]);
+
+    engine.update_with_delta(Duration::from_millis(10)).unwrap();
+    assert_eq!(
+        recording.commands(),
+        vec![rumble.clone(), rumble, RumbleCommand::Stop { id }]
+    );
+}
@@ -0,0 +1,49 @@
+use gamepad::*;
+
+use std::time::Duration;
+
+#[test]
+fn engine_rumble_is_recorded_by_dummy_backend() {
+    let id = GamepadId::new(0);
+    let mut backend = DummyBackend::new();
+    backend.gamepads_mut().insert(id, GamepadState::new());
+    let recording = backend.rumble_recording();
+
+    let mut engine = GamepadEngine::with_backend(Box::new(backend));
+    engine.update().unwrap();
+    engine
+        .set_rumble(id, 1.5, 0.25, Duration::from_millis(200))
+        .unwrap();
+    engine.stop_rumble(id).unwrap();
+
+    assert_eq!(
+        recording.commands(),
+        vec![
+            RumbleCommand::Set {
+                id,
+                strong: 1.0,
+                weak: 0.25,
+                duration: Duration::from_millis(200),
+            },
+            RumbleCommand::Stop { id },
+        ]
+    );
+
+    recording.clear();
+    assert!(recording.commands().is_empty());
+}
+
+#[test]
+fn rumble_on_unknown_gamepad_is_not_recorded() {
+    let backend = DummyBackend::new();
+    let recording = backend.rumble_recording();
+    let mut engine = GamepadEngine::with_backend(Box::new(backend));
+
+    let error = engine.stop_rumble(GamepadId::new(7)).unwrap_err();
+
+    assert!(matches!(
+        error.error_type,
+        ErrorType::GamepadNotFound { .. }
+    ));
+    assert!(recording.commands().is_empty());
+}
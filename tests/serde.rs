@@ -0,0 +1,87 @@
+#![cfg(feature = "serde")]
+
+use gamepad::*;
+
+fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+    let json = serde_json::to_string(value).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn settings_round_trip() {
+    let mut settings = GamepadSettings::new();
+    settings.set_press_threshold(0.25);
+    settings.set_deadzone(
+        Joystick::Left,
+        Deadzone::new(DeadzoneKind::ScaledRadial, 0.2, 0.9),
+    );
+    settings.set_response_curve(Joystick::Left, ResponseCurve::Power(2.0));
+    settings.set_axis_response_curves(
+        Joystick::Right,
+        ResponseCurve::piecewise(&[(0.5, 0.25)]),
+        ResponseCurve::SCurve(1.5),
+    );
+    let calibration = JoystickCalibration::new(
+        AxisCalibration::new(10, -30000, 31000),
+        AxisCalibration::new(-5, -32000, 32000),
+    );
+    settings.set_calibration(Joystick::Right, Some(calibration));
+
+    let restored = round_trip(&settings);
+    assert_eq!(restored.press_threshold(), 0.25);
+    assert_eq!(
+        restored.deadzone(Joystick::Left),
+        settings.deadzone(Joystick::Left)
+    );
+    assert_eq!(restored.calibration(Joystick::Right), Some(calibration));
+    assert_eq!(restored.calibration(Joystick::Left), None);
+    assert_eq!(
+        format!("{:?}", restored.joystick(Joystick::Left).response_curve()),
+        "Power(2.0)"
+    );
+    assert_eq!(
+        format!(
+            "{:?}",
+            restored.joystick(Joystick::Right).axis_response_curves()
+        ),
+        format!(
+            "{:?}",
+            settings.joystick(Joystick::Right).axis_response_curves()
+        )
+    );
+}
+
+#[test]
+fn custom_curves_fail_to_serialize() {
+    let mut settings = GamepadSettings::new();
+    settings.set_response_curve(Joystick::Left, ResponseCurve::custom(|x| x * x));
+
+    assert!(serde_json::to_string(&settings).is_err());
+}
+
+#[test]
+fn buttons_round_trip_by_name() {
+    for button in [Button::South, Button::DPadWest, Button::Other(0x2c0)] {
+        assert_eq!(round_trip(&button), button);
+    }
+    assert_eq!(
+        serde_json::to_string(&Button::Other(704)).unwrap(),
+        "\"other_704\""
+    );
+}
+
+#[test]
+fn backend_kinds_round_trip() {
+    for kind in [
+        BackendKind::Default,
+        BackendKind::Dummy,
+        BackendKind::Gilrs,
+        BackendKind::XInput,
+        BackendKind::Evdev,
+        BackendKind::Joydev,
+        BackendKind::Hidraw,
+        BackendKind::Wasm,
+    ] {
+        assert_eq!(round_trip(&kind), kind);
+    }
+}
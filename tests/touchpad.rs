@@ -0,0 +1,63 @@
+use gamepad::*;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+/// Reports one scripted touchpad state per update
+struct TouchpadScript {
+    states: VecDeque<TouchpadState>,
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+}
+impl GamepadEngineBackend for TouchpadScript {
+    fn update(&mut self) -> Result<(), GamepadError> {
+        let mut gamepad = GamepadState::new();
+        gamepad.set_touchpad(self.states.pop_front());
+        self.gamepads.insert(GamepadId::new(0), gamepad);
+
+        Ok(())
+    }
+
+    fn gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
+    }
+}
+
+fn pressed(position: (f32, f32)) -> TouchpadState {
+    TouchpadState::new(vec![TouchContact::new(0, position, true)])
+}
+
+fn gestures_with_delta(delta: Duration) -> Vec<TouchGesture> {
+    let states = vec![
+        pressed((0.5, 0.5)),
+        pressed((0.5, 0.5)),
+        TouchpadState::new(Vec::new()),
+    ];
+    let mut engine = GamepadEngine::with_backend(Box::new(TouchpadScript {
+        states: states.into(),
+        gamepads: BTreeMap::new(),
+    }));
+
+    let mut gestures = Vec::new();
+    for _ in 0..3 {
+        engine.update_with_delta(delta).unwrap();
+        let touchpad = engine.gamepads()[&GamepadId::new(0)].touchpad().unwrap();
+        gestures.extend(touchpad.gestures().iter().copied());
+    }
+
+    gestures
+}
+
+#[test]
+fn engine_recognizes_tap_within_update_delta() {
+    assert_eq!(
+        gestures_with_delta(Duration::from_millis(16)),
+        vec![TouchGesture::Tap {
+            position: (0.5, 0.5)
+        }]
+    );
+}
+
+#[test]
+fn engine_rejects_tap_held_longer_than_tap_duration() {
+    assert!(gestures_with_delta(Duration::from_millis(200)).is_empty());
+}